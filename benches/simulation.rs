@@ -0,0 +1,266 @@
+//! `cargo bench` (or `cargo bench -- <filter>`, e.g. `cargo bench -- update/random_walk`)
+//!
+//! Simulation benchmarks run for several point counts and rayon thread counts;
+//! they need to be run from the crate root (background image in `./assets`).
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use image::{DynamicImage, ImageOutputFormat};
+use life_of_boids::analysis::{analyze, AnalysisConfig};
+use life_of_boids::color::ColorConversion;
+use life_of_boids::field::{CurlNoiseField, ImageScalarField, ScalarField, VectorField};
+use life_of_boids::neighbours::SpatialGrid;
+use life_of_boids::point::Position;
+use life_of_boids::points_simulator::PointsSimulator;
+use life_of_boids::render::glx::image_from_gl_pixels;
+use life_of_boids::soa::PointsSoA;
+use life_of_boids::species::SpeciesSet;
+use life_of_boids::utils::{hashed_random, load_image};
+use rayon::ThreadPool;
+
+const SPACE_SIZE: (u32, u32) = (1920, 1080);
+const SAMPLES: usize = 10_000;
+
+fn simulator(count: usize) -> PointsSimulator {
+    let mut s = PointsSimulator::new(SPACE_SIZE).expect("run benchmarks from the crate root");
+    s.set_point_count(count);
+    s
+}
+
+/// 1, 2, 4... up to the available cores
+fn thread_pools() -> Vec<(usize, ThreadPool)> {
+    let cores = std::thread::available_parallelism().map_or(1, |n| n.get());
+    let mut counts: Vec<usize> = (0..).map(|i| 1 << i).take_while(|&n| n < cores).collect();
+    counts.push(cores);
+    counts
+        .into_iter()
+        .map(|threads| {
+            let pool = rayon::ThreadPoolBuilder::new()
+                .num_threads(threads)
+                .build()
+                .unwrap();
+            (threads, pool)
+        })
+        .collect()
+}
+
+/// Pseudo-random positions, some of them outside of the space
+fn positions(count: usize, margin: f32) -> Vec<Position> {
+    let (width, height) = (SPACE_SIZE.0 as f32, SPACE_SIZE.1 as f32);
+    let mut state = 1;
+    (0..count)
+        .map(|_| {
+            Position::new(
+                -margin + (width + 2.0 * margin) * hashed_random(&mut state),
+                -margin + (height + 2.0 * margin) * hashed_random(&mut state),
+            )
+        })
+        .collect()
+}
+
+fn update(c: &mut Criterion) {
+    type Step = fn(&mut PointsSimulator);
+    let configurations: [(&str, Option<SpeciesSet>, &[usize], Step); 3] = [
+        (
+            "random_walk",
+            None,
+            &[50_000, 200_000, 800_000],
+            PointsSimulator::update,
+        ),
+        // same walk on the array of structs layout (`random_walk` runs on structure of arrays)
+        ("random_walk_aos", None, &[50_000, 200_000, 800_000], |s| {
+            s.update_random_walk_aos(s.step() as u32)
+        }),
+        (
+            "mixed_flocks",
+            Some(SpeciesSet::mixed_flocks()),
+            &[10_000, 50_000],
+            PointsSimulator::update,
+        ),
+    ];
+    for (name, species, counts, step) in configurations.iter() {
+        let mut group = c.benchmark_group(format!("update/{}", name));
+        for &count in counts.iter() {
+            let mut s = simulator(count);
+            if let Some(species) = species {
+                s.set_species(species.clone());
+                s.set_point_count(count);
+            }
+            group.throughput(Throughput::Elements(count as u64));
+            for (threads, pool) in thread_pools() {
+                group.bench_with_input(
+                    BenchmarkId::new(format!("{}_threads", threads), count),
+                    &count,
+                    |b, _| pool.install(|| b.iter(|| step(&mut s))),
+                );
+            }
+        }
+        group.finish();
+    }
+}
+
+fn initialisation(c: &mut Criterion) {
+    let mut group = c.benchmark_group("init_points");
+    for &count in [50_000, 200_000, 800_000].iter() {
+        let mut s = simulator(count);
+        group.throughput(Throughput::Elements(count as u64));
+        for (threads, pool) in thread_pools() {
+            group.bench_with_input(
+                BenchmarkId::new(format!("{}_threads", threads), count),
+                &count,
+                |b, _| pool.install(|| b.iter(|| s.init_points())),
+            );
+        }
+    }
+    group.finish();
+}
+
+fn field_sampling(c: &mut Criterion) {
+    let img = load_image("./assets/pugs.jpg").unwrap();
+    let space_size = (SPACE_SIZE.0 as f32, SPACE_SIZE.1 as f32);
+    let brightness = ImageScalarField::from_image(&img, space_size, &ColorConversion::default());
+    let curl = CurlNoiseField::new(0, 300.0, 8);
+    let s = simulator(0);
+    let positions = positions(SAMPLES, 0.0);
+
+    let mut group = c.benchmark_group("fields");
+    group.throughput(Throughput::Elements(SAMPLES as u64));
+    group.bench_function("image_bilinear", |b| {
+        b.iter(|| positions.iter().map(|&p| brightness.sample(p)).sum::<f32>())
+    });
+    group.bench_function("image_gradient", |b| {
+        b.iter(|| {
+            positions
+                .iter()
+                .map(|&p| brightness.gradient(p).x)
+                .sum::<f32>()
+        })
+    });
+    group.bench_function("curl_noise", |b| {
+        b.iter(|| positions.iter().map(|&p| curl.sample(p).x).sum::<f32>())
+    });
+    group.bench_function("speed_factor", |b| {
+        let fields = s.field_effects();
+        b.iter(|| {
+            positions
+                .iter()
+                .map(|&p| fields.speed_factor(p))
+                .sum::<f32>()
+        })
+    });
+    group.bench_function("image_conversion", |b| {
+        b.iter(|| ImageScalarField::from_image(&img, space_size, &ColorConversion::CieLightness))
+    });
+    group.finish();
+}
+
+fn boundaries(c: &mut Criterion) {
+    let space_size = (SPACE_SIZE.0 as f32, SPACE_SIZE.1 as f32);
+    // points moved by at most a step: a few of them cross the boundaries
+    let positions = positions(SAMPLES, 5.0);
+    let periodize_while = PointsSimulator::periodize_point(space_size);
+    // single wrap: only right for moves shorter than the space size
+    let periodize_if = |mut p: Position| {
+        if p.x < 0.0 {
+            p.x += space_size.0
+        } else if p.x > space_size.0 {
+            p.x -= space_size.0
+        }
+        if p.y < 0.0 {
+            p.y += space_size.1
+        } else if p.y > space_size.1 {
+            p.y -= space_size.1
+        }
+        p
+    };
+    let periodize_rem =
+        |p: Position| Position::new(p.x.rem_euclid(space_size.0), p.y.rem_euclid(space_size.1));
+
+    let mut group = c.benchmark_group("periodize");
+    group.throughput(Throughput::Elements(SAMPLES as u64));
+    group.bench_function("while", |b| {
+        b.iter(|| positions.iter().map(|&p| periodize_while(p).x).sum::<f32>())
+    });
+    group.bench_function("if", |b| {
+        b.iter(|| positions.iter().map(|&p| periodize_if(p).x).sum::<f32>())
+    });
+    group.bench_function("rem_euclid", |b| {
+        b.iter(|| positions.iter().map(|&p| periodize_rem(p).x).sum::<f32>())
+    });
+    let mut soa = PointsSoA::from_points(simulator(SAMPLES).points());
+    group.bench_function("soa_integrate", |b| {
+        b.iter(|| soa.integrate(0.2, space_size))
+    });
+    group.finish();
+}
+
+fn neighbours(c: &mut Criterion) {
+    let space_size = (SPACE_SIZE.0 as f32, SPACE_SIZE.1 as f32);
+    let queries = positions(SAMPLES, 0.0);
+    let mut group = c.benchmark_group("neighbours");
+    for &count in [50_000, 200_000].iter() {
+        let s = simulator(count);
+        group.throughput(Throughput::Elements(count as u64));
+        group.bench_with_input(BenchmarkId::new("grid_build", count), &count, |b, _| {
+            b.iter(|| SpatialGrid::new(s.points(), space_size, 10.0))
+        });
+        let grid = SpatialGrid::new(s.points(), space_size, 10.0);
+        group.throughput(Throughput::Elements(SAMPLES as u64));
+        group.bench_with_input(BenchmarkId::new("queries_r10", count), &count, |b, _| {
+            b.iter(|| {
+                let mut found = 0;
+                for &p in queries.iter() {
+                    grid.for_each_neighbour(p, 10.0, |_, _, _| found += 1);
+                }
+                found
+            })
+        });
+    }
+    group.finish();
+}
+
+fn analysis(c: &mut Criterion) {
+    let config = AnalysisConfig::default();
+    let mut group = c.benchmark_group("analysis");
+    for &count in [50_000, 200_000].iter() {
+        let mut s = simulator(count);
+        s.update();
+        group.throughput(Throughput::Elements(count as u64));
+        for (threads, pool) in thread_pools() {
+            group.bench_with_input(
+                BenchmarkId::new(format!("{}_threads", threads), count),
+                &count,
+                |b, _| pool.install(|| b.iter(|| analyze(s.points(), s.space_size(), &config))),
+            );
+        }
+    }
+    group.finish();
+}
+
+fn image_export(c: &mut Criterion) {
+    let (width, height) = SPACE_SIZE;
+    let stride = (3 * width).div_ceil(4) * 4;
+    let pixels: Vec<u8> = (0..stride * height).map(|i| (i % 251) as u8).collect();
+    let img = image_from_gl_pixels(&pixels, width, height, stride);
+
+    let mut group = c.benchmark_group("export");
+    group.throughput(Throughput::Elements((width * height) as u64));
+    group.bench_function("gl_pixels_to_image", |b| {
+        b.iter(|| image_from_gl_pixels(&pixels, width, height, stride))
+    });
+    let img = DynamicImage::ImageRgb8(img);
+    group.bench_function("png_encoding", |b| {
+        b.iter(|| {
+            let mut png = Vec::new();
+            img.write_to(&mut png, ImageOutputFormat::Png).unwrap();
+            png
+        })
+    });
+    group.finish();
+}
+
+criterion_group! {
+    name = benches;
+    config = Criterion::default().sample_size(10);
+    targets = update, initialisation, field_sampling, boundaries, neighbours, analysis, image_export
+}
+criterion_main!(benches);
@@ -1,15 +1,22 @@
+#[cfg(feature = "gui")]
 use gl_generator::{Api, Fallbacks, Profile, Registry};
+#[cfg(feature = "gui")]
 use std::env;
+#[cfg(feature = "gui")]
 use std::fs::File;
+#[cfg(feature = "gui")]
 use std::path::PathBuf;
 
 fn main() {
-    let dest = PathBuf::from(&env::var("OUT_DIR").unwrap());
-
     println!("cargo:rerun-if-changed=build.rs");
 
-    let mut file = File::create(&dest.join("gl_bindings.rs")).unwrap();
-    Registry::new(Api::Gles2, (3, 3), Profile::Core, Fallbacks::All, [])
-        .write_bindings(gl_generator::StructGenerator, &mut file)
-        .unwrap();
+    // OpenGL bindings are only needed by the viewer
+    #[cfg(feature = "gui")]
+    {
+        let dest = PathBuf::from(&env::var("OUT_DIR").unwrap());
+        let mut file = File::create(dest.join("gl_bindings.rs")).unwrap();
+        Registry::new(Api::Gles2, (3, 3), Profile::Core, Fallbacks::All, [])
+            .write_bindings(gl_generator::StructGenerator, &mut file)
+            .unwrap();
+    }
 }
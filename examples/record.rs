@@ -0,0 +1,66 @@
+//! Headless run recording statistics or dumping trajectories:
+//! `cargo run --release --example record -- OUTPUT [STEPS [INTERVAL [MODEL [SAMPLE]]]]`
+//! (OUTPUT: statistics to `.csv` / `.jsonl`, trajectories to `.bin` / `.npy`;
+//! MODEL: name of a collective motion model, e.g. `Vicsek`, or `-`;
+//! SAMPLE: number of points of the trajectories, all by default)
+
+use anyhow::{anyhow, Context, Result};
+use life_of_boids::models::ModelPreset;
+use life_of_boids::points_simulator::PointsSimulator;
+use life_of_boids::recorder::{Recorder, RecorderConfig};
+use life_of_boids::trajectory::{TrajectoryConfig, TrajectoryFormat, TrajectoryWriter};
+
+fn main() -> Result<()> {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let output = args
+        .first()
+        .ok_or_else(|| anyhow!("Usage: record OUTPUT [STEPS [INTERVAL [MODEL [SAMPLE]]]]"))?;
+    let number = |index: usize, default: u64| -> Result<u64> {
+        match args.get(index) {
+            Some(arg) => arg
+                .parse()
+                .with_context(|| format!("Invalid number {:?}", arg)),
+            None => Ok(default),
+        }
+    };
+    let steps = number(1, 1000)?;
+    let interval = number(2, 10)?;
+
+    let mut simulator = PointsSimulator::new((1920, 1080))?;
+    if let Some(name) = args.get(3).filter(|name| *name != "-") {
+        let model = ModelPreset::presets()
+            .into_iter()
+            .find(|model| model.name.eq_ignore_ascii_case(name))
+            .ok_or_else(|| anyhow!("Unknown model {:?}", name))?;
+        simulator.apply_model(&model);
+    }
+
+    if TrajectoryFormat::from_path(output).is_ok() {
+        let config = TrajectoryConfig {
+            interval,
+            sample: match args.get(4) {
+                Some(_) => Some(number(4, 0)? as usize),
+                None => None,
+            },
+        };
+        let mut writer = TrajectoryWriter::create(output, &simulator, config)?;
+        writer.record(&simulator)?;
+        for _ in 0..steps {
+            simulator.update();
+            writer.record(&simulator)?;
+        }
+        writer.finish()
+    } else {
+        let config = RecorderConfig {
+            interval,
+            ..RecorderConfig::default()
+        };
+        let mut recorder = Recorder::create(output, config)?;
+        recorder.record(&simulator)?;
+        for _ in 0..steps {
+            simulator.update();
+            recorder.record(&simulator)?;
+        }
+        recorder.flush()
+    }
+}
@@ -0,0 +1,75 @@
+//! Points upload cost for each upload mode (buffer reallocation / orphaning):
+//! `cargo run --release --example upload -- [FRAMES]`
+//!
+//! The points of a running simulation are drawn in a hidden window; each frame is timed from the
+//! upload submission until `glFinish` returns, so that the copy to the GPU is included.
+
+use anyhow::{Context, Result};
+use glutin::dpi::PhysicalSize;
+use glutin::event_loop::EventLoop;
+use glutin::window::WindowBuilder;
+use glutin::ContextBuilder;
+use life_of_boids::points_simulator::PointsSimulator;
+use life_of_boids::render::glx::{self, UploadMode, WindowSizeInfo};
+use life_of_boids::render::shader_programs::points::PointsRenderProgram;
+use std::rc::Rc;
+use std::time::Instant;
+
+const SIZE: (u32, u32) = (1920, 1080);
+const POINT_COUNTS: [usize; 3] = [50_000, 200_000, 800_000];
+
+fn main() -> Result<()> {
+    let frames: usize = match std::env::args().nth(1) {
+        Some(arg) => arg
+            .parse()
+            .with_context(|| format!("Invalid frame count {:?}", arg))?,
+        None => 200,
+    };
+
+    let events_loop = EventLoop::new();
+    let wb = WindowBuilder::new()
+        .with_visible(false)
+        .with_inner_size(PhysicalSize::new(SIZE.0, SIZE.1));
+    let windowed_context = ContextBuilder::new()
+        .with_vsync(false)
+        .build_windowed(wb, &events_loop)?;
+    let windowed_context = unsafe { windowed_context.make_current().map_err(|(_, e)| e)? };
+    let gl = Rc::new(glx::gl_init(&windowed_context));
+
+    println!("points   mode        render (ms)  render + finish (ms)");
+    for &count in POINT_COUNTS.iter() {
+        let mut s = PointsSimulator::new(SIZE)?;
+        s.set_point_count(count);
+        for &mode in [UploadMode::Reallocate, UploadMode::Orphan].iter() {
+            let size = WindowSizeInfo {
+                width: SIZE.0,
+                height: SIZE.1,
+            };
+            let mut program = PointsRenderProgram::new(gl.clone(), size)?;
+            program.initialize()?;
+            program.upload_mode = mode;
+
+            // CPU time of the render call (upload and draw submission), then until the GPU is done
+            let (mut submit, mut total) = (0.0, 0.0);
+            for _ in 0..frames {
+                s.update();
+                let start = Instant::now();
+                program.render(s.points())?;
+                let submitted = start.elapsed();
+                unsafe {
+                    gl.Finish();
+                }
+                submit += submitted.as_secs_f64() * 1000.0;
+                total += start.elapsed().as_secs_f64() * 1000.0;
+            }
+            println!(
+                "{:<8} {:<11} {:>11.3}  {:>20.3}",
+                count,
+                format!("{:?}", mode),
+                submit / frames as f64,
+                total / frames as f64
+            );
+        }
+    }
+    Ok(())
+}
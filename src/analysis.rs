@@ -0,0 +1,359 @@
+//! Statistics of the points at a given step: the metrics reported about a run
+//! (order parameters, speeds, spacing, clusters, density fluctuations)
+
+use crate::neighbours::SpatialGrid;
+use crate::parallel::*;
+use crate::point::{Point, Position, Velocity};
+use crate::species::RANDOM_WALK_MAX_SPEED;
+use cgmath::{InnerSpace, Vector2};
+use std::f32::consts::TAU;
+
+#[derive(Debug, Clone)]
+pub struct AnalysisConfig {
+    /// points closer than this belong to the same cluster (friends of friends)
+    pub linking_length: f32,
+    /// smaller groups are not counted as clusters
+    pub min_cluster_size: usize,
+    pub speed_bins: usize,
+    /// upper bound of the speed histogram (faster points are counted in the last bin)
+    pub max_speed: f32,
+    /// side of the square cells in which points are counted for the density statistics
+    pub density_cell: f32,
+}
+
+impl Default for AnalysisConfig {
+    fn default() -> Self {
+        AnalysisConfig {
+            linking_length: 5.0,
+            min_cluster_size: 2,
+            speed_bins: 24,
+            max_speed: RANDOM_WALK_MAX_SPEED,
+            density_cell: 40.0,
+        }
+    }
+}
+
+/// Counts of values in `counts.len()` bins of `bin_width` starting at 0
+#[derive(Debug, Clone, PartialEq)]
+pub struct Histogram {
+    pub bin_width: f32,
+    pub counts: Vec<usize>,
+}
+
+impl Histogram {
+    /// Values above `max` are counted in the last bin
+    pub fn new(values: impl Iterator<Item = f32>, bins: usize, max: f32) -> Self {
+        let bins = bins.max(1);
+        let bin_width = max / bins as f32;
+        let mut counts = vec![0; bins];
+        for value in values {
+            let bin = ((value / bin_width).max(0.0) as usize).min(bins - 1);
+            counts[bin] += 1;
+        }
+        Histogram { bin_width, counts }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct StepStatistics {
+    pub point_count: usize,
+    /// norm of the mean normalized velocity: 0 (disorder) to 1 (all aligned)
+    pub polarization: f32,
+    /// norm of the mean normalized angular momentum around the centre of the points:
+    /// 1 for a mill (points turning around their centre)
+    pub milling: f32,
+    pub mean_speed: f32,
+    pub speed_std: f32,
+    pub speed_histogram: Histogram,
+    /// mean distance to the nearest neighbour
+    pub nearest_neighbour: f32,
+    /// number of clusters (cf. [AnalysisConfig::min_cluster_size])
+    pub cluster_count: usize,
+    /// cluster sizes, largest first
+    pub cluster_sizes: Vec<usize>,
+    /// mean and variance of the number of points per density cell
+    pub density_mean: f32,
+    pub density_variance: f32,
+}
+
+fn normalized(v: Velocity) -> Velocity {
+    if v.magnitude2() > 0.0 {
+        v.normalize()
+    } else {
+        v
+    }
+}
+
+/// Norm of the mean normalized velocity (points at rest count as zero vectors)
+pub fn polarization(points: &[Point]) -> f32 {
+    if points.is_empty() {
+        return 0.0;
+    }
+    let x: f32 = points.par_iter().map(|p| normalized(p.velocity).x).sum();
+    let y: f32 = points.par_iter().map(|p| normalized(p.velocity).y).sum();
+    Vector2::new(x, y).magnitude() / points.len() as f32
+}
+
+/// Centre of points in a periodic space (circular mean along each axis)
+pub fn periodic_centre(points: &[Point], space_size: (f32, f32)) -> Position {
+    let mean = |coordinate: fn(&Point) -> f32, size: f32| {
+        let (sin, cos) = points
+            .iter()
+            .map(|p| (coordinate(p) / size * TAU).sin_cos())
+            .fold((0.0, 0.0), |(s, c), (sin, cos)| (s + sin, c + cos));
+        (sin.atan2(cos) / TAU * size).rem_euclid(size)
+    };
+    Position::new(
+        mean(|p| p.position.x, space_size.0),
+        mean(|p| p.position.y, space_size.1),
+    )
+}
+
+/// Norm of the mean of `r̂ × v̂` (`r` from the centre of the points to each point)
+pub fn milling(points: &[Point], grid: &SpatialGrid, centre: Position) -> f32 {
+    if points.is_empty() {
+        return 0.0;
+    }
+    let momentum: f32 = points
+        .par_iter()
+        .map(|p| {
+            let r = normalized(grid.offset(centre, p.position));
+            let v = normalized(p.velocity);
+            r.x * v.y - r.y * v.x
+        })
+        .sum();
+    momentum.abs() / points.len() as f32
+}
+
+/// Distance from each point to its nearest neighbour (infinite when alone)
+pub fn nearest_neighbour_distances(
+    points: &[Point],
+    grid: &SpatialGrid,
+    space_size: (f32, f32),
+) -> Vec<f32> {
+    // mean spacing: most points (96% for uniformly spread points) find a neighbour at once
+    let initial_radius = (space_size.0 * space_size.1 / points.len().max(1) as f32).sqrt();
+    let max_radius = space_size.0.max(space_size.1);
+    points
+        .par_iter()
+        .enumerate()
+        .map(|(index, p)| {
+            let mut radius = initial_radius;
+            loop {
+                let mut nearest2 = f32::INFINITY;
+                grid.for_each_neighbour(p.position, radius, |other, _, offset| {
+                    if other != index {
+                        nearest2 = nearest2.min(offset.magnitude2());
+                    }
+                });
+                if nearest2.is_finite() || radius > max_radius {
+                    return nearest2.sqrt();
+                }
+                radius *= 2.0;
+            }
+        })
+        .collect()
+}
+
+pub(crate) fn find_root(parents: &mut [u32], mut index: u32) -> u32 {
+    while parents[index as usize] != index {
+        // path halving
+        parents[index as usize] = parents[parents[index as usize] as usize];
+        index = parents[index as usize];
+    }
+    index
+}
+
+/// Cluster of each point: points closer than `linking_length` are in the same cluster
+/// (friends of friends, periodic space), clusters are numbered from 0 in order of appearance
+pub fn cluster_labels(points: &[Point], grid: &SpatialGrid, linking_length: f32) -> Vec<u32> {
+    let mut parents: Vec<u32> = (0..points.len() as u32).collect();
+    for (index, p) in points.iter().enumerate() {
+        grid.for_each_neighbour(p.position, linking_length, |other, _, _| {
+            if other > index {
+                let (a, b) = (
+                    find_root(&mut parents, index as u32),
+                    find_root(&mut parents, other as u32),
+                );
+                if a != b {
+                    parents[a.max(b) as usize] = a.min(b);
+                }
+            }
+        });
+    }
+    let mut labels = vec![u32::MAX; points.len()];
+    let mut next = 0;
+    for index in 0..points.len() {
+        let root = find_root(&mut parents, index as u32) as usize;
+        if labels[root] == u32::MAX {
+            labels[root] = next;
+            next += 1;
+        }
+        labels[index] = labels[root];
+    }
+    labels
+}
+
+/// Size of each cluster (indexed by label)
+pub fn cluster_sizes(labels: &[u32]) -> Vec<usize> {
+    let count = labels.iter().map(|&l| l as usize + 1).max().unwrap_or(0);
+    let mut sizes = vec![0; count];
+    for &label in labels {
+        sizes[label as usize] += 1;
+    }
+    sizes
+}
+
+/// Number of points in each `cell` x `cell` square (cells are enlarged to tile the space)
+pub fn density_counts(points: &[Point], space_size: (f32, f32), cell: f32) -> Vec<usize> {
+    let columns = ((space_size.0 / cell) as usize).max(1);
+    let rows = ((space_size.1 / cell) as usize).max(1);
+    let mut counts = vec![0; columns * rows];
+    for p in points {
+        let column =
+            ((p.position.x / space_size.0 * columns as f32).max(0.0) as usize).min(columns - 1);
+        let row = ((p.position.y / space_size.1 * rows as f32).max(0.0) as usize).min(rows - 1);
+        counts[row * columns + column] += 1;
+    }
+    counts
+}
+
+fn mean_and_variance(values: impl Iterator<Item = f32> + Clone) -> (f32, f32) {
+    let count = values.clone().count().max(1) as f32;
+    let mean = values.clone().sum::<f32>() / count;
+    let variance = values.map(|v| (v - mean) * (v - mean)).sum::<f32>() / count;
+    (mean, variance)
+}
+
+/// All the statistics of the points
+pub fn analyze(
+    points: &[Point],
+    space_size: (f32, f32),
+    config: &AnalysisConfig,
+) -> StepStatistics {
+    let grid = SpatialGrid::new(points, space_size, config.linking_length);
+
+    let speeds: Vec<f32> = points.par_iter().map(|p| p.velocity.magnitude()).collect();
+    let (mean_speed, speed_variance) = mean_and_variance(speeds.iter().copied());
+
+    let distances = nearest_neighbour_distances(points, &grid, space_size);
+    let finite = distances.iter().copied().filter(|d| d.is_finite());
+    let nearest_neighbour = finite.clone().sum::<f32>() / finite.count().max(1) as f32;
+
+    let mut sizes: Vec<usize> =
+        cluster_sizes(&cluster_labels(points, &grid, config.linking_length))
+            .into_iter()
+            .filter(|&size| size >= config.min_cluster_size)
+            .collect();
+    sizes.sort_unstable_by(|a, b| b.cmp(a));
+
+    let counts = density_counts(points, space_size, config.density_cell);
+    let (density_mean, density_variance) = mean_and_variance(counts.iter().map(|&c| c as f32));
+
+    StepStatistics {
+        point_count: points.len(),
+        polarization: polarization(points),
+        milling: milling(points, &grid, periodic_centre(points, space_size)),
+        mean_speed,
+        speed_std: speed_variance.sqrt(),
+        speed_histogram: Histogram::new(speeds.into_iter(), config.speed_bins, config.max_speed),
+        nearest_neighbour,
+        cluster_count: sizes.len(),
+        cluster_sizes: sizes,
+        density_mean,
+        density_variance,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn point(x: f32, y: f32, vx: f32, vy: f32) -> Point {
+        Point {
+            position: Position::new(x, y),
+            velocity: Velocity::new(vx, vy),
+            species: 0,
+        }
+    }
+
+    #[test]
+    fn mill_should_have_angular_momentum_but_no_polarization() {
+        let space_size = (100.0, 100.0);
+        // a ring turning counterclockwise around a centre close to the edges
+        let ring: Vec<Point> = (0..360)
+            .map(|i| {
+                let angle = (i as f32).to_radians();
+                let (sin, cos) = angle.sin_cos();
+                let (x, y) = (5.0 + 20.0 * cos, 95.0 + 20.0 * sin);
+                point(
+                    x.rem_euclid(100.0),
+                    y.rem_euclid(100.0),
+                    -2.0 * sin,
+                    2.0 * cos,
+                )
+            })
+            .collect();
+        let stats = analyze(&ring, space_size, &AnalysisConfig::default());
+        assert!(stats.polarization < 0.01);
+        assert!(stats.milling > 0.99);
+        assert!((stats.mean_speed - 2.0).abs() < 1e-4);
+        assert!(stats.speed_std < 1e-3);
+
+        let aligned: Vec<Point> = ring
+            .iter()
+            .map(|p| point(p.position.x, p.position.y, 1.0, 1.0))
+            .collect();
+        let stats = analyze(&aligned, space_size, &AnalysisConfig::default());
+        assert!(stats.polarization > 0.999);
+        assert!(stats.milling < 0.01);
+    }
+
+    #[test]
+    fn clusters_should_be_linked_across_the_edges() {
+        let space_size = (100.0, 100.0);
+        let mut points = Vec::new();
+        // a line of points crossing the left/right edge, spaced by 2
+        for i in 0..10 {
+            points.push(point(
+                (90.0 + 2.0 * i as f32).rem_euclid(100.0),
+                50.0,
+                0.0,
+                0.0,
+            ));
+        }
+        // a pair and an isolated point
+        points.push(point(50.0, 10.0, 0.0, 0.0));
+        points.push(point(53.0, 10.0, 0.0, 0.0));
+        points.push(point(50.0, 80.0, 0.0, 0.0));
+
+        let stats = analyze(&points, space_size, &AnalysisConfig::default());
+        assert_eq!(stats.cluster_sizes, vec![10, 2]);
+        assert_eq!(stats.cluster_count, 2);
+        assert_eq!(stats.point_count, 13);
+        // 10 points 2 apart, 2 points 3 apart, the isolated one 30 from the pair
+        assert!((stats.nearest_neighbour - (20.0 + 6.0 + 30.0) / 13.0).abs() < 1e-4);
+        assert_eq!(stats.speed_histogram.counts[0], 13);
+    }
+
+    #[test]
+    fn uniform_lattice_should_have_no_density_fluctuations() {
+        let space_size = (80.0, 80.0);
+        let points: Vec<Point> = (0..64)
+            .map(|i| {
+                point(
+                    5.0 + 10.0 * (i % 8) as f32,
+                    5.0 + 10.0 * (i / 8) as f32,
+                    1.0,
+                    0.0,
+                )
+            })
+            .collect();
+        let stats = analyze(&points, space_size, &AnalysisConfig::default());
+        assert_eq!(stats.density_mean, 16.0);
+        assert_eq!(stats.density_variance, 0.0);
+        assert!((stats.nearest_neighbour - 10.0).abs() < 1e-4);
+        // spacing larger than the linking length: no cluster
+        assert_eq!(stats.cluster_count, 0);
+    }
+}
@@ -0,0 +1,494 @@
+use crate::field::FieldEffects;
+use crate::neighbours::SpatialGrid;
+use crate::noise::{perlin1, perlin3};
+use crate::point::{Point, Velocity};
+use crate::species::SpeciesSet;
+use crate::utils::{hash_u32, hashed_normals, hashed_random};
+use cgmath::{Basis2, InnerSpace, Rad, Rotation, Rotation2, Vector2, Zero};
+use std::f32::consts::{PI, TAU};
+use std::sync::Arc;
+
+/// What behaviours can look at to steer a point (the state of the simulation before the step)
+pub struct SteeringContext<'a> {
+    pub points: &'a [Point],
+    /// neighbour lookup (None when no behaviour or species rule uses neighbours)
+    pub grid: Option<&'a SpatialGrid>,
+    pub species: &'a SpeciesSet,
+    pub fields: &'a FieldEffects,
+    /// simulation step (random values are derived from it, cf. [random_state])
+    pub step: u64,
+    /// simulation time
+    pub time: f32,
+    pub time_step: f32,
+}
+
+/// Hash state of the random values of the point `index` at the current step, so that runs can be
+/// reproduced: behaviours combined in a pipeline use different `seed`s, 0 giving the values drawn
+/// by the random walk kernels (cf. [crate::soa::PointsSoA::random_walk]) and the GPU backend
+pub(crate) fn random_state(context: &SteeringContext, seed: u32, index: usize) -> u32 {
+    hash_u32(index as u32 ^ hash_u32(context.step as u32 ^ seed))
+}
+
+/// A per-point steering rule: contributes a velocity to the new velocity of each point,
+/// weighted in a [BehaviorPipeline]
+pub trait Behavior: Send + Sync {
+    /// Identifies the behaviour in a pipeline
+    fn name(&self) -> &str;
+
+    /// Contribution to the new velocity of the point `index`
+    fn steer(&self, context: &SteeringContext, index: usize) -> Velocity;
+
+    /// Radius within which neighbours are looked at (None when neighbours are not used):
+    /// [SteeringContext::grid] is then available
+    fn perception_radius(&self) -> Option<f32> {
+        None
+    }
+}
+
+/// Random walk velocity: a random heading deviation `angle` (rad) and speed `magnitude`,
+/// scaled by the fields (by default slowed down on bright pixels)
+pub(crate) fn random_walk_velocity(angle: f32, magnitude: f32, speed_factor: f32) -> Velocity {
+    Basis2::from_angle(Rad(angle)).rotate_vector(Vector2::new(0., magnitude)) * speed_factor
+}
+
+/// The historical brightness random walk: heading deviation in [-1, 1] rad and speed in [0, 10]
+/// scaled by the fields speed factor and by the random walk weight of the species
+pub struct RandomWalk;
+
+impl RandomWalk {
+    pub const NAME: &'static str = "random_walk";
+}
+
+impl Behavior for RandomWalk {
+    fn name(&self) -> &str {
+        Self::NAME
+    }
+
+    fn steer(&self, context: &SteeringContext, index: usize) -> Velocity {
+        let p = &context.points[index];
+        let mut state = random_state(context, 0, index);
+        let angle = -1.0 + 2.0 * hashed_random(&mut state);
+        let magnitude = 10.0 * hashed_random(&mut state);
+        random_walk_velocity(angle, magnitude, 1.0) * random_scale(context, p)
+    }
+}
+
+/// Direction of the point velocity (a fixed random one per point when it does not move)
+pub(crate) fn heading(p: &Point, index: usize) -> Vector2<f32> {
+    if p.velocity.magnitude2() > 0.0 {
+        p.velocity.normalize()
+    } else {
+        let angle = hash_u32(index as u32) as f32 / u32::MAX as f32 * TAU;
+        Vector2::new(angle.cos(), angle.sin())
+    }
+}
+
+/// Scale shared by the random behaviours: fields speed factor and random walk weight of the species
+fn random_scale(context: &SteeringContext, p: &Point) -> f32 {
+    context.fields.speed_factor(p.position) * context.species.get(p.species).weights.random_walk
+}
+
+/// Reynolds' wander: heads toward a target on a circle ahead of the point. The target moves
+/// smoothly along the circle (coherent noise in time, independent for each point) so that
+/// points follow smooth curves instead of jittering.
+/// Scaled like [RandomWalk].
+pub struct Wander {
+    pub speed: f32,
+    /// distance of the circle centre ahead of the point
+    pub distance: f32,
+    /// the largest turn at each step is `asin(radius / distance)`
+    pub radius: f32,
+    /// simulation time over which the target position on the circle decorrelates
+    pub correlation_time: f32,
+    pub seed: u32,
+}
+
+impl Default for Wander {
+    fn default() -> Self {
+        Wander {
+            speed: 5.0,
+            distance: 10.0,
+            radius: 2.0,
+            correlation_time: 10.0,
+            seed: 0,
+        }
+    }
+}
+
+impl Behavior for Wander {
+    fn name(&self) -> &str {
+        "wander"
+    }
+
+    fn steer(&self, context: &SteeringContext, index: usize) -> Velocity {
+        let p = &context.points[index];
+        let heading = heading(p, index);
+        let side = Vector2::new(-heading.y, heading.x);
+        let seed = hash_u32(self.seed) ^ index as u32;
+        let angle = PI * perlin1(seed, context.time / self.correlation_time);
+        let target = heading * (self.distance + self.radius * angle.cos())
+            + side * (self.radius * angle.sin());
+        target.normalize_to(self.speed * random_scale(context, p))
+    }
+}
+
+/// Ornstein–Uhlenbeck velocity noise: the velocity relaxes toward zero over `correlation_time`
+/// while being kicked by gaussian noise (exact discretization), so that headings stay correlated
+/// over this time. The stationary standard deviation of each velocity component is `sigma`
+/// (times the fields speed factor); the whole velocity is scaled by the species random walk weight.
+pub struct OrnsteinUhlenbeck {
+    pub sigma: f32,
+    pub correlation_time: f32,
+    /// stream of random values (0 is the one of [RandomWalk], cf. [random_state])
+    pub seed: u32,
+}
+
+impl Default for OrnsteinUhlenbeck {
+    fn default() -> Self {
+        OrnsteinUhlenbeck {
+            sigma: 4.0,
+            correlation_time: 5.0,
+            seed: 1,
+        }
+    }
+}
+
+impl Behavior for OrnsteinUhlenbeck {
+    fn name(&self) -> &str {
+        "ornstein_uhlenbeck"
+    }
+
+    fn steer(&self, context: &SteeringContext, index: usize) -> Velocity {
+        let p = &context.points[index];
+        let decay = (-context.time_step / self.correlation_time).exp();
+        let kick = self.sigma * (1.0 - decay * decay).sqrt();
+        let (x, y) = hashed_normals(&mut random_state(context, self.seed, index));
+        let noise = Vector2::new(x, y);
+        let speed_factor = context.fields.speed_factor(p.position);
+        (p.velocity * decay + noise * (kick * speed_factor))
+            * context.species.get(p.species).weights.random_walk
+    }
+}
+
+/// Spatiotemporal coherent noise: headings given by a 3D Perlin noise of the position and
+/// time, so that nearby points move alike along a flow which slowly evolves.
+/// Scaled like [RandomWalk].
+pub struct NoiseFlow {
+    pub speed: f32,
+    /// typical size of the flow structures (in simulation space units)
+    pub wavelength: f32,
+    /// simulation time over which the flow changes
+    pub period: f32,
+    pub seed: u32,
+}
+
+impl Default for NoiseFlow {
+    fn default() -> Self {
+        NoiseFlow {
+            speed: 5.0,
+            wavelength: 200.0,
+            period: 50.0,
+            seed: 0,
+        }
+    }
+}
+
+impl Behavior for NoiseFlow {
+    fn name(&self) -> &str {
+        "noise_flow"
+    }
+
+    fn steer(&self, context: &SteeringContext, index: usize) -> Velocity {
+        let p = &context.points[index];
+        let value = perlin3(
+            self.seed,
+            p.position.x / self.wavelength,
+            p.position.y / self.wavelength,
+            context.time / self.period,
+        );
+        // the noise rarely reaches ±1: the angle spans about a full turn
+        let angle = 2.0 * TAU * value;
+        Vector2::new(angle.cos(), angle.sin()) * (self.speed * random_scale(context, p))
+    }
+}
+
+#[derive(Clone)]
+pub struct WeightedBehavior {
+    pub weight: f32,
+    pub behavior: Arc<dyn Behavior>,
+}
+
+/// Behaviours whose weighted contributions are summed into the steering of each point
+/// (then combined with the species rules, cf. [crate::species::Species::steer])
+#[derive(Clone, Default)]
+pub struct BehaviorPipeline {
+    behaviors: Vec<WeightedBehavior>,
+}
+
+impl BehaviorPipeline {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Today's behaviour: the brightness random walk only
+    pub fn random_walk() -> Self {
+        Self::new().with(1.0, RandomWalk)
+    }
+
+    /// Random walk, wander, Ornstein–Uhlenbeck and coherent noise flow
+    pub fn presets() -> Vec<BehaviorPipeline> {
+        vec![
+            Self::random_walk(),
+            Self::new().with(1.0, Wander::default()),
+            Self::new().with(1.0, OrnsteinUhlenbeck::default()),
+            Self::new().with(1.0, NoiseFlow::default()),
+        ]
+    }
+
+    pub fn with<B: Behavior + 'static>(mut self, weight: f32, behavior: B) -> Self {
+        self.push(weight, Arc::new(behavior));
+        self
+    }
+
+    pub fn push(&mut self, weight: f32, behavior: Arc<dyn Behavior>) {
+        self.behaviors.push(WeightedBehavior { weight, behavior });
+    }
+
+    /// Removes the behaviours called `name` (false if there is none)
+    pub fn remove(&mut self, name: &str) -> bool {
+        let count = self.behaviors.len();
+        self.behaviors.retain(|b| b.behavior.name() != name);
+        self.behaviors.len() != count
+    }
+
+    /// Weight of the first behaviour called `name`
+    pub fn weight(&self, name: &str) -> Option<f32> {
+        self.find(name).map(|b| b.weight)
+    }
+
+    /// Changes the weight of the first behaviour called `name` (false if there is none)
+    pub fn set_weight(&mut self, name: &str, weight: f32) -> bool {
+        match self
+            .behaviors
+            .iter_mut()
+            .find(|b| b.behavior.name() == name)
+        {
+            Some(b) => {
+                b.weight = weight;
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn find(&self, name: &str) -> Option<&WeightedBehavior> {
+        self.behaviors.iter().find(|b| b.behavior.name() == name)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &WeightedBehavior> {
+        self.behaviors.iter()
+    }
+
+    pub fn len(&self) -> usize {
+        self.behaviors.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.behaviors.is_empty()
+    }
+
+    /// Names joined with '+' (for display)
+    pub fn names(&self) -> String {
+        let names: Vec<&str> = self.behaviors.iter().map(|b| b.behavior.name()).collect();
+        names.join("+")
+    }
+
+    /// Smallest perception radius of the behaviours using neighbours (None if there is none)
+    pub fn perception_radius(&self) -> Option<f32> {
+        self.behaviors
+            .iter()
+            .filter(|b| b.weight != 0.0)
+            .filter_map(|b| b.behavior.perception_radius())
+            .fold(None, |min: Option<f32>, r| {
+                Some(min.map_or(r, |min| min.min(r)))
+            })
+    }
+
+    /// True when the pipeline is the plain random walk (the only behaviour supported by the
+    /// random walk kernels and the GPU backend)
+    pub fn is_random_walk_only(&self) -> bool {
+        match self.behaviors.as_slice() {
+            [only] => only.weight == 1.0 && only.behavior.name() == RandomWalk::NAME,
+            _ => false,
+        }
+    }
+
+    /// Sum of the weighted contributions for the point `index`
+    pub fn steer(&self, context: &SteeringContext, index: usize) -> Velocity {
+        self.behaviors
+            .iter()
+            .filter(|b| b.weight != 0.0)
+            .fold(Velocity::zero(), |sum, b| {
+                sum + b.behavior.steer(context, index) * b.weight
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::field::ImageScalarField;
+    use crate::point::Position;
+    use image::RgbImage;
+
+    struct Constant(&'static str, Velocity);
+
+    impl Behavior for Constant {
+        fn name(&self) -> &str {
+            self.0
+        }
+
+        fn steer(&self, _: &SteeringContext, _: usize) -> Velocity {
+            self.1
+        }
+    }
+
+    /// Uniform black background: speed factor of 1.2
+    fn fixture() -> (SpeciesSet, FieldEffects) {
+        let background = Arc::new(ImageScalarField::from_image(
+            &RgbImage::new(4, 4),
+            (4.0, 4.0),
+            &Default::default(),
+        ));
+        (
+            SpeciesSet::single(),
+            FieldEffects::brightness_speed(background),
+        )
+    }
+
+    fn point(velocity: Velocity) -> Point {
+        Point {
+            position: Position::new(1.0, 1.0),
+            velocity,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn pipeline_should_sum_weighted_contributions() {
+        let (species, fields) = fixture();
+        let points = [point(Velocity::zero())];
+        let context = SteeringContext {
+            points: &points,
+            grid: None,
+            species: &species,
+            fields: &fields,
+            step: 0,
+            time: 0.0,
+            time_step: 0.2,
+        };
+
+        let mut pipeline = BehaviorPipeline::new()
+            .with(2.0, Constant("right", Vector2::new(1.0, 0.0)))
+            .with(0.5, Constant("up", Vector2::new(0.0, 4.0)));
+        assert_eq!(pipeline.steer(&context, 0), Vector2::new(2.0, 2.0));
+
+        assert!(pipeline.set_weight("up", 0.0));
+        assert_eq!(pipeline.steer(&context, 0), Vector2::new(2.0, 0.0));
+        assert!(pipeline.remove("right"));
+        assert!(!pipeline.remove("right"));
+        assert_eq!(pipeline.names(), "up");
+        assert!(!pipeline.is_random_walk_only());
+        assert!(BehaviorPipeline::random_walk().is_random_walk_only());
+    }
+
+    #[test]
+    fn wander_should_turn_smoothly() {
+        let (species, fields) = fixture();
+        let wander = Wander::default();
+        let max_turn = (wander.radius / wander.distance).asin() + 1e-4;
+        let mut points = [point(Velocity::new(1.0, 0.0))];
+        let mut previous_turn: Option<f32> = None;
+        for step in 0..500 {
+            let context = SteeringContext {
+                points: &points,
+                grid: None,
+                species: &species,
+                fields: &fields,
+                step,
+                time: step as f32 * 0.2,
+                time_step: 0.2,
+            };
+            let velocity = wander.steer(&context, 0);
+            assert!((velocity.magnitude() - wander.speed * 1.2).abs() < 1e-3);
+            let turn = points[0].velocity.angle(velocity).0;
+            assert!(turn.abs() <= max_turn);
+            // the turn rate itself changes slowly (white noise would jump around)
+            if let Some(previous_turn) = previous_turn {
+                assert!((turn - previous_turn).abs() < 0.06);
+            }
+            previous_turn = Some(turn);
+            points[0].velocity = velocity;
+        }
+    }
+
+    #[test]
+    fn noise_should_be_reproducible() {
+        let (species, fields) = fixture();
+        let points = [point(Velocity::new(1.0, 0.0))];
+        let context = |step| SteeringContext {
+            points: &points,
+            grid: None,
+            species: &species,
+            fields: &fields,
+            step,
+            time: step as f32 * 0.2,
+            time_step: 0.2,
+        };
+        let behaviors: [&dyn Behavior; 2] = [&RandomWalk, &OrnsteinUhlenbeck::default()];
+        for behavior in behaviors.iter() {
+            let velocity = behavior.steer(&context(7), 0);
+            assert_eq!(behavior.steer(&context(7), 0), velocity);
+            assert_ne!(behavior.steer(&context(8), 0), velocity);
+        }
+    }
+
+    #[test]
+    fn ornstein_uhlenbeck_should_reach_its_stationary_spread() {
+        let (species, fields) = fixture();
+        let ou = OrnsteinUhlenbeck::default();
+        let mut points = vec![point(Velocity::zero()); 4000];
+        let mut previous = points.clone();
+        // 8 correlation times
+        for step in 0..200 {
+            let context = SteeringContext {
+                points: &points,
+                grid: None,
+                species: &species,
+                fields: &fields,
+                step,
+                time: step as f32 * 0.2,
+                time_step: 0.2,
+            };
+            let velocities: Vec<Velocity> = (0..points.len())
+                .map(|index| ou.steer(&context, index))
+                .collect();
+            previous = points.clone();
+            for (p, velocity) in points.iter_mut().zip(velocities) {
+                p.velocity = velocity;
+            }
+        }
+        let count = points.len() as f32;
+        let variance = points.iter().map(|p| p.velocity.x.powi(2)).sum::<f32>() / count;
+        let expected = ou.sigma * 1.2;
+        assert!((variance.sqrt() / expected - 1.0).abs() < 0.1);
+        // one step correlation of the velocity (white noise: 0)
+        let covariance = points
+            .iter()
+            .zip(previous.iter())
+            .map(|(p, q)| p.velocity.x * q.velocity.x)
+            .sum::<f32>()
+            / count;
+        assert!(covariance / variance > 0.8);
+    }
+}
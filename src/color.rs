@@ -0,0 +1,221 @@
+use crate::utils::calculate_relative_brightness;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/// Conversion of an RGB colour (sRGB encoded) to a scalar in [0, 1] used to build fields
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ColorConversion {
+    /// historical conversion (cf. [calculate_relative_brightness])
+    #[default]
+    Brightness,
+    /// Rec.709 relative luminance Y of the linearised colour
+    Rec709Luminance,
+    /// CIE L* lightness (divided by 100)
+    CieLightness,
+    /// HSV value (max component)
+    HsvValue,
+    /// HSV saturation
+    HsvSaturation,
+    /// HSV hue (divided by 360°)
+    HsvHue,
+    /// a single component
+    Channel(Channel),
+    /// `1 - x` of another conversion
+    Inverted(Box<ColorConversion>),
+}
+
+/// Component of an RGB colour
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Channel {
+    Red,
+    Green,
+    Blue,
+}
+
+impl Channel {
+    fn select(self, red: u8, green: u8, blue: u8) -> u8 {
+        match self {
+            Channel::Red => red,
+            Channel::Green => green,
+            Channel::Blue => blue,
+        }
+    }
+}
+
+impl fmt::Display for Channel {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Channel::Red => write!(f, "red"),
+            Channel::Green => write!(f, "green"),
+            Channel::Blue => write!(f, "blue"),
+        }
+    }
+}
+
+/// sRGB transfer function inverse: encoded component in [0, 1] → linear light
+pub fn srgb_to_linear(c: f32) -> f32 {
+    if c <= 0.04045 {
+        c / 12.92
+    } else {
+        ((c + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+fn relative_luminance(red: u8, green: u8, blue: u8) -> f32 {
+    let linear = |c: u8| srgb_to_linear(c as f32 / 255.0);
+    0.2126 * linear(red) + 0.7152 * linear(green) + 0.0722 * linear(blue)
+}
+
+fn cie_lightness(red: u8, green: u8, blue: u8) -> f32 {
+    // CIE constants: ε = (6/29)³, κ = (29/3)³
+    const EPSILON: f32 = 216.0 / 24389.0;
+    const KAPPA: f32 = 24389.0 / 27.0;
+    let y = relative_luminance(red, green, blue);
+    let lightness = if y > EPSILON {
+        116.0 * y.cbrt() - 16.0
+    } else {
+        KAPPA * y
+    };
+    lightness / 100.0
+}
+
+fn hsv(red: u8, green: u8, blue: u8) -> (f32, f32, f32) {
+    let (r, g, b) = (
+        red as f32 / 255.0,
+        green as f32 / 255.0,
+        blue as f32 / 255.0,
+    );
+    let max = r.max(g).max(b);
+    let min = r.min(g).min(b);
+    let delta = max - min;
+    let hue = if delta == 0.0 {
+        0.0
+    } else if max == r {
+        ((g - b) / delta).rem_euclid(6.0)
+    } else if max == g {
+        (b - r) / delta + 2.0
+    } else {
+        (r - g) / delta + 4.0
+    } / 6.0;
+    let saturation = if max == 0.0 { 0.0 } else { delta / max };
+    (hue, saturation, max)
+}
+
+impl ColorConversion {
+    pub fn convert(&self, red: u8, green: u8, blue: u8) -> f32 {
+        match self {
+            ColorConversion::Brightness => calculate_relative_brightness(red, green, blue),
+            ColorConversion::Rec709Luminance => relative_luminance(red, green, blue),
+            ColorConversion::CieLightness => cie_lightness(red, green, blue),
+            ColorConversion::HsvValue => hsv(red, green, blue).2,
+            ColorConversion::HsvSaturation => hsv(red, green, blue).1,
+            ColorConversion::HsvHue => hsv(red, green, blue).0,
+            ColorConversion::Channel(channel) => channel.select(red, green, blue) as f32 / 255.0,
+            ColorConversion::Inverted(conversion) => 1.0 - conversion.convert(red, green, blue),
+        }
+    }
+
+    /// Conversions offered in the viewer
+    pub fn presets() -> Vec<ColorConversion> {
+        vec![
+            ColorConversion::Brightness,
+            ColorConversion::Rec709Luminance,
+            ColorConversion::CieLightness,
+            ColorConversion::HsvValue,
+            ColorConversion::HsvSaturation,
+            ColorConversion::HsvHue,
+            ColorConversion::Channel(Channel::Red),
+            ColorConversion::Channel(Channel::Green),
+            ColorConversion::Channel(Channel::Blue),
+            ColorConversion::Inverted(Box::new(ColorConversion::Brightness)),
+            ColorConversion::Inverted(Box::new(ColorConversion::CieLightness)),
+        ]
+    }
+}
+
+impl fmt::Display for ColorConversion {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ColorConversion::Brightness => write!(f, "brightness"),
+            ColorConversion::Rec709Luminance => write!(f, "Rec.709 luminance"),
+            ColorConversion::CieLightness => write!(f, "CIE L*"),
+            ColorConversion::HsvValue => write!(f, "HSV value"),
+            ColorConversion::HsvSaturation => write!(f, "HSV saturation"),
+            ColorConversion::HsvHue => write!(f, "HSV hue"),
+            ColorConversion::Channel(channel) => write!(f, "{} channel", channel),
+            ColorConversion::Inverted(conversion) => write!(f, "inverted {}", conversion),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_close(actual: f32, expected: f32) {
+        assert!(
+            (actual - expected).abs() < 1e-3,
+            "{} != {} (expected)",
+            actual,
+            expected
+        );
+    }
+
+    #[test]
+    fn rec709_luminance_should_linearise_srgb() {
+        let y = ColorConversion::Rec709Luminance;
+        assert_close(y.convert(0, 0, 0), 0.0);
+        assert_close(y.convert(255, 255, 255), 1.0);
+        assert_close(y.convert(255, 0, 0), 0.2126);
+        assert_close(y.convert(0, 255, 0), 0.7152);
+        assert_close(y.convert(0, 0, 255), 0.0722);
+        // sRGB 128 is 21.6% linear light, not 50%
+        assert_close(y.convert(128, 128, 128), 0.2159);
+    }
+
+    #[test]
+    fn cie_lightness_should_match_reference_values() {
+        let l = ColorConversion::CieLightness;
+        assert_close(l.convert(0, 0, 0), 0.0);
+        assert_close(l.convert(255, 255, 255), 1.0);
+        // sRGB 119 grey is the perceptual middle grey (L* = 50)
+        assert!((l.convert(119, 119, 119) - 0.5).abs() < 2e-3);
+        // pure red: L* = 53.24
+        assert_close(l.convert(255, 0, 0), 0.5324);
+    }
+
+    #[test]
+    fn hsv_conversions_should_match_reference_values() {
+        assert_close(ColorConversion::HsvHue.convert(255, 0, 0), 0.0);
+        assert_close(ColorConversion::HsvHue.convert(0, 255, 0), 1.0 / 3.0);
+        assert_close(ColorConversion::HsvHue.convert(0, 0, 255), 2.0 / 3.0);
+        assert_close(ColorConversion::HsvHue.convert(255, 0, 255), 5.0 / 6.0);
+        assert_close(
+            ColorConversion::HsvSaturation.convert(255, 128, 128),
+            127.0 / 255.0,
+        );
+        assert_close(ColorConversion::HsvSaturation.convert(0, 0, 0), 0.0);
+        assert_close(
+            ColorConversion::HsvValue.convert(64, 128, 32),
+            128.0 / 255.0,
+        );
+    }
+
+    #[test]
+    fn channel_and_inverted_conversions() {
+        let green = ColorConversion::Channel(Channel::Green);
+        assert_close(green.convert(0, 51, 0), 0.2);
+        let inverted = ColorConversion::Inverted(Box::new(green));
+        assert_close(inverted.convert(0, 51, 0), 0.8);
+        assert_close(
+            ColorConversion::Brightness.convert(10, 20, 30),
+            calculate_relative_brightness(10, 20, 30),
+        );
+
+        let blue: ColorConversion = serde_json::from_str(r#"{"channel": "blue"}"#).unwrap();
+        assert_eq!(blue, ColorConversion::Channel(Channel::Blue));
+        assert!(serde_json::from_str::<ColorConversion>(r#"{"channel": 3}"#).is_err());
+    }
+}
@@ -0,0 +1,392 @@
+use crate::color::{Channel, ColorConversion};
+use crate::point::{Position, Velocity};
+use crate::utils::{hash_u32, hashed_random};
+use cgmath::{InnerSpace, Vector2, Zero};
+use image::RgbImage;
+use std::sync::Arc;
+
+// Step (in simulation space units) of the finite differences used for gradients
+const GRADIENT_STEP: f32 = 1.0;
+
+/// Scalar value defined over the simulation space
+pub trait ScalarField: Send + Sync {
+    fn sample(&self, p: Position) -> f32;
+
+    /// Central differences of [ScalarField::sample]
+    fn gradient(&self, p: Position) -> Vector2<f32> {
+        let dx = Vector2::new(GRADIENT_STEP, 0.0);
+        let dy = Vector2::new(0.0, GRADIENT_STEP);
+        Vector2::new(
+            self.sample(p + dx) - self.sample(p - dx),
+            self.sample(p + dy) - self.sample(p - dy),
+        ) / (2.0 * GRADIENT_STEP)
+    }
+}
+
+/// Vector value defined over the simulation space
+pub trait VectorField: Send + Sync {
+    fn sample(&self, p: Position) -> Vector2<f32>;
+}
+
+/// Grid of values stretched over the simulation space, sampled with bilinear interpolation
+/// (values are located at pixel centres, edges are clamped)
+#[derive(Clone)]
+struct Grid<T> {
+    width: usize,
+    height: usize,
+    values: Vec<T>, // row-major
+    space_size: (f32, f32),
+}
+
+impl<T: Copy> Grid<T> {
+    fn from_image(img: &RgbImage, space_size: (f32, f32), convert: impl Fn([u8; 3]) -> T) -> Self {
+        let (width, height) = img.dimensions();
+        Grid {
+            width: width as usize,
+            height: height as usize,
+            values: img.pixels().map(|pixel| convert(pixel.0)).collect(),
+            space_size,
+        }
+    }
+
+    /// Indices of the 4 surrounding values and interpolation weights
+    /// (same computation as the GPU step shader)
+    fn bilinear(&self, p: Position) -> ([usize; 4], (f32, f32)) {
+        let u = p.x / self.space_size.0 * self.width as f32 - 0.5;
+        let v = p.y / self.space_size.1 * self.height as f32 - 0.5;
+        let (base_u, base_v) = (u.floor(), v.floor());
+        let clamp = |i: f32, n: usize| (i.max(0.0) as usize).min(n - 1);
+        let (x0, x1) = (clamp(base_u, self.width), clamp(base_u + 1.0, self.width));
+        let (y0, y1) = (clamp(base_v, self.height), clamp(base_v + 1.0, self.height));
+        (
+            [
+                y0 * self.width + x0,
+                y0 * self.width + x1,
+                y1 * self.width + x0,
+                y1 * self.width + x1,
+            ],
+            (u - base_u, v - base_v),
+        )
+    }
+}
+
+fn mix(a: f32, b: f32, t: f32) -> f32 {
+    a * (1.0 - t) + b * t
+}
+
+/// Scalar field from an image (brightness, single channel...)
+#[derive(Clone)]
+pub struct ImageScalarField {
+    grid: Grid<f32>,
+}
+
+impl ImageScalarField {
+    /// Field of the image pixels converted to scalars
+    pub fn from_image(
+        img: &RgbImage,
+        space_size: (f32, f32),
+        conversion: &ColorConversion,
+    ) -> Self {
+        ImageScalarField {
+            grid: Grid::from_image(img, space_size, |c| conversion.convert(c[0], c[1], c[2])),
+        }
+    }
+
+    /// Values (row-major) with the dimensions of the image
+    pub fn values(&self) -> (u32, u32, &[f32]) {
+        (
+            self.grid.width as u32,
+            self.grid.height as u32,
+            &self.grid.values,
+        )
+    }
+}
+
+impl ScalarField for ImageScalarField {
+    fn sample(&self, p: Position) -> f32 {
+        let ([i00, i10, i01, i11], (fx, fy)) = self.grid.bilinear(p);
+        let v = &self.grid.values;
+        mix(mix(v[i00], v[i10], fx), mix(v[i01], v[i11], fx), fy)
+    }
+}
+
+/// Flow map encoded in the red (x) and green (y) channels: 0 → -1, 255 → 1
+#[derive(Clone)]
+pub struct ImageFlowField {
+    grid: Grid<[f32; 2]>,
+}
+
+impl ImageFlowField {
+    pub fn from_image(img: &RgbImage, space_size: (f32, f32)) -> Self {
+        let decode = |c: u8| c as f32 / 255.0 * 2.0 - 1.0;
+        ImageFlowField {
+            grid: Grid::from_image(img, space_size, |c| [decode(c[0]), decode(c[1])]),
+        }
+    }
+}
+
+impl VectorField for ImageFlowField {
+    fn sample(&self, p: Position) -> Vector2<f32> {
+        let ([i00, i10, i01, i11], (fx, fy)) = self.grid.bilinear(p);
+        let v = &self.grid.values;
+        let component = |k: usize| {
+            mix(
+                mix(v[i00][k], v[i10][k], fx),
+                mix(v[i01][k], v[i11][k], fx),
+                fy,
+            )
+        };
+        Vector2::new(component(0), component(1))
+    }
+}
+
+/// Gradient of a scalar field
+pub struct GradientField(pub Arc<dyn ScalarField>);
+
+impl VectorField for GradientField {
+    fn sample(&self, p: Position) -> Vector2<f32> {
+        self.0.gradient(p)
+    }
+}
+
+/// Divergence free (swirling) field: curl of a potential made of random plane waves
+/// `ψ(p) = Σ a sin(k·p + φ)`, i.e. `(∂ψ/∂y, -∂ψ/∂x)` computed analytically
+pub struct CurlNoiseField {
+    waves: Vec<(Vector2<f32>, f32, f32)>, // wave vector k, phase φ, amplitude a
+}
+
+impl CurlNoiseField {
+    /// `wavelength` is the typical size of the swirls (in simulation space units);
+    /// the field magnitude is about 1
+    pub fn new(seed: u32, wavelength: f32, waves: usize) -> Self {
+        let mut state = hash_u32(seed);
+        let waves = (0..waves)
+            .map(|_| {
+                let angle = hashed_random(&mut state) * std::f32::consts::TAU;
+                let length = std::f32::consts::TAU / wavelength * (0.5 + hashed_random(&mut state));
+                let phase = hashed_random(&mut state) * std::f32::consts::TAU;
+                let k = Vector2::new(angle.cos(), angle.sin()) * length;
+                (k, phase, 1.0 / (length * (waves as f32).sqrt()))
+            })
+            .collect();
+        CurlNoiseField { waves }
+    }
+}
+
+impl VectorField for CurlNoiseField {
+    fn sample(&self, p: Position) -> Vector2<f32> {
+        let mut gradient = Vector2::zero();
+        for (k, phase, amplitude) in self.waves.iter() {
+            gradient += k * (amplitude * (k.x * p.x + k.y * p.y + phase).cos());
+        }
+        Vector2::new(gradient.y, -gradient.x)
+    }
+}
+
+/// How a field acts on the points
+#[derive(Clone)]
+pub enum FieldEffect {
+    /// random walk speed scaled by `offset + gain * value`
+    SpeedModulation {
+        field: Arc<dyn ScalarField>,
+        offset: f32,
+        gain: f32,
+    },
+    /// force along the gradient (ascent if `gain` > 0, descent otherwise)
+    GradientForce {
+        field: Arc<dyn ScalarField>,
+        gain: f32,
+    },
+    /// force given by a vector field
+    Force {
+        field: Arc<dyn VectorField>,
+        gain: f32,
+    },
+    /// heading turned toward the field direction (`strength` in [0, 1]), speed unchanged
+    HeadingBias {
+        field: Arc<dyn VectorField>,
+        strength: f32,
+    },
+}
+
+/// Historical coupling: slower on bright pixels (`1.2 - brightness`)
+pub const BRIGHTNESS_SPEED_OFFSET: f32 = 1.2;
+pub const BRIGHTNESS_SPEED_GAIN: f32 = -1.0;
+
+/// Applies field effects to the points
+#[derive(Clone, Default)]
+pub struct FieldEffects {
+    pub name: String,
+    pub effects: Vec<FieldEffect>,
+}
+
+impl FieldEffects {
+    /// Factor applied to the random walk speed at `p`
+    pub fn speed_factor(&self, p: Position) -> f32 {
+        self.effects
+            .iter()
+            .map(|effect| match effect {
+                FieldEffect::SpeedModulation {
+                    field,
+                    offset,
+                    gain,
+                } => offset + gain * field.sample(p),
+                _ => 1.0,
+            })
+            .product()
+    }
+
+    /// Sum of the field forces at `p`
+    pub fn force(&self, p: Position) -> Vector2<f32> {
+        self.effects
+            .iter()
+            .fold(Vector2::zero(), |force, effect| match effect {
+                FieldEffect::GradientForce { field, gain } => force + field.gradient(p) * *gain,
+                FieldEffect::Force { field, gain } => force + field.sample(p) * *gain,
+                _ => force,
+            })
+    }
+
+    /// `velocity` with its heading biased toward vector fields at `p`
+    pub fn bias_heading(&self, p: Position, velocity: Velocity) -> Velocity {
+        self.effects
+            .iter()
+            .fold(velocity, |velocity, effect| match effect {
+                FieldEffect::HeadingBias { field, strength } => {
+                    let direction = field.sample(p);
+                    let speed = velocity.magnitude();
+                    if speed == 0.0 || direction.magnitude2() == 0.0 {
+                        return velocity;
+                    }
+                    let heading =
+                        velocity / speed * (1.0 - strength) + direction.normalize() * *strength;
+                    if heading.magnitude2() == 0.0 {
+                        velocity
+                    } else {
+                        heading.normalize() * speed
+                    }
+                }
+                _ => velocity,
+            })
+    }
+
+    /// The historical brightness speed modulation only
+    pub fn brightness_speed(background: Arc<ImageScalarField>) -> Self {
+        FieldEffects {
+            name: "brightness speed".into(),
+            effects: vec![FieldEffect::SpeedModulation {
+                field: background,
+                offset: BRIGHTNESS_SPEED_OFFSET,
+                gain: BRIGHTNESS_SPEED_GAIN,
+            }],
+        }
+    }
+
+    /// True when made of the historical brightness speed modulation of `background` only
+    /// (the coupling implemented by the GPU backend)
+    pub fn is_brightness_speed(&self, background: &Arc<ImageScalarField>) -> bool {
+        match self.effects.as_slice() {
+            [FieldEffect::SpeedModulation {
+                field,
+                offset,
+                gain,
+            }] => {
+                Arc::as_ptr(field) as *const () == Arc::as_ptr(background) as *const ()
+                    && *offset == BRIGHTNESS_SPEED_OFFSET
+                    && *gain == BRIGHTNESS_SPEED_GAIN
+            }
+            _ => false,
+        }
+    }
+
+    /// Number of demo couplings (cf. [FieldEffects::preset])
+    pub const PRESET_COUNT: usize = 5;
+
+    /// Demo coupling `index` (wrapping around) with a background image: only the fields
+    /// it uses are built
+    pub fn preset(
+        index: usize,
+        background: &Arc<ImageScalarField>,
+        img: &RgbImage,
+        space_size: (f32, f32),
+    ) -> FieldEffects {
+        let mut effects = Self::brightness_speed(background.clone());
+        let (name, effect) = match index % Self::PRESET_COUNT {
+            0 => return effects,
+            1 => (
+                "gather in bright areas",
+                FieldEffect::GradientForce {
+                    field: background.clone(),
+                    gain: 40.0,
+                },
+            ),
+            2 => (
+                "image flow (RG channels)",
+                FieldEffect::Force {
+                    field: Arc::new(ImageFlowField::from_image(img, space_size)),
+                    gain: 4.0,
+                },
+            ),
+            3 => (
+                "head up the red channel",
+                FieldEffect::HeadingBias {
+                    field: Arc::new(GradientField(Arc::new(ImageScalarField::from_image(
+                        img,
+                        space_size,
+                        &ColorConversion::Channel(Channel::Red),
+                    )))),
+                    strength: 0.5,
+                },
+            ),
+            _ => (
+                "curl noise",
+                FieldEffect::HeadingBias {
+                    field: Arc::new(CurlNoiseField::new(0, 300.0, 8)),
+                    strength: 0.6,
+                },
+            ),
+        };
+        effects.name = name.into();
+        effects.effects.push(effect);
+        effects
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::Rgb;
+
+    #[test]
+    fn bilinear_sampling_should_interpolate_between_pixel_centres() {
+        // 2x1 image: black then white, stretched over a 20x10 space
+        let mut img = RgbImage::new(2, 1);
+        img.put_pixel(1, 0, Rgb([255, 255, 255]));
+        let field = ImageScalarField::from_image(
+            &img,
+            (20.0, 10.0),
+            &ColorConversion::Channel(Channel::Red),
+        );
+
+        assert_eq!(field.sample(Position::new(5.0, 5.0)), 0.0); // first pixel centre
+        assert_eq!(field.sample(Position::new(15.0, 5.0)), 1.0); // second pixel centre
+        assert!((field.sample(Position::new(10.0, 5.0)) - 0.5).abs() < 1e-6);
+        assert_eq!(field.sample(Position::new(0.0, 0.0)), 0.0); // clamped edge
+        assert!((field.gradient(Position::new(10.0, 5.0)).x - 0.1).abs() < 1e-5);
+    }
+
+    #[test]
+    fn curl_noise_should_be_divergence_free() {
+        let field = CurlNoiseField::new(42, 50.0, 8);
+        let h = 1e-2;
+        for &(x, y) in [(3.0, 7.0), (120.0, -40.0), (500.0, 250.0)].iter() {
+            let p = Position::new(x, y);
+            let divergence = (field.sample(p + Vector2::new(h, 0.0)).x
+                - field.sample(p - Vector2::new(h, 0.0)).x
+                + field.sample(p + Vector2::new(0.0, h)).y
+                - field.sample(p - Vector2::new(0.0, h)).y)
+                / (2.0 * h);
+            assert!(divergence.abs() < 1e-2, "divergence {}", divergence);
+        }
+    }
+}
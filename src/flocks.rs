@@ -0,0 +1,396 @@
+//! Flocks: DBSCAN clusters of the points (periodic space) tracked from one update to the next
+//! by the points they share, to follow splits and merges.
+//!
+//! Points are identified by their index: tracking is meaningless across a change of the points
+//! (reset, point count change, deaths of the population dynamics).
+
+use crate::analysis::find_root;
+use crate::neighbours::SpatialGrid;
+use crate::parallel::*;
+use crate::point::Point;
+use crate::species::MAX_SPECIES;
+use std::collections::HashMap;
+
+/// Flock id of the points belonging to no flock
+pub const NO_FLOCK: u32 = u32::MAX;
+
+/// Colours of the flocks (cf. [color_by_flock]): grey for points in no flock then 7 hues
+pub const FLOCK_PALETTE: [[f32; 3]; MAX_SPECIES] = [
+    [0.35, 0.35, 0.35],
+    [1.0, 0.3, 0.3],
+    [0.3, 1.0, 0.3],
+    [0.35, 0.55, 1.0],
+    [1.0, 0.9, 0.2],
+    [1.0, 0.3, 1.0],
+    [0.2, 1.0, 1.0],
+    [1.0, 0.6, 0.1],
+];
+
+#[derive(Debug, Clone)]
+pub struct FlockConfig {
+    /// neighbourhood radius (DBSCAN ε)
+    pub radius: f32,
+    /// points with at least this many points within `radius` (themselves included) are core points
+    /// (DBSCAN minPts); the other points join the flock of a core point within `radius`, if any
+    pub min_points: usize,
+    /// smaller clusters are not flocks
+    pub min_size: usize,
+    /// a previous and a new flock are linked (same flock, split or merge) when they share at least
+    /// this fraction of the smaller of them
+    pub min_overlap: f32,
+}
+
+impl Default for FlockConfig {
+    fn default() -> Self {
+        FlockConfig {
+            radius: 5.0,
+            min_points: 4,
+            min_size: 10,
+            min_overlap: 0.25,
+        }
+    }
+}
+
+/// Cluster of each point ([NO_FLOCK] for noise), clusters are numbered from 0 in order of appearance
+pub fn dbscan_labels(points: &[Point], grid: &SpatialGrid, config: &FlockConfig) -> Vec<u32> {
+    let core: Vec<bool> = points
+        .par_iter()
+        .map(|p| {
+            let mut count = 0;
+            grid.for_each_neighbour(p.position, config.radius, |_, _, _| count += 1);
+            count >= config.min_points
+        })
+        .collect();
+
+    let mut parents: Vec<u32> = (0..points.len() as u32).collect();
+    for (index, p) in points.iter().enumerate().filter(|&(index, _)| core[index]) {
+        grid.for_each_neighbour(p.position, config.radius, |other, _, _| {
+            if other > index && core[other] {
+                let (a, b) = (
+                    find_root(&mut parents, index as u32),
+                    find_root(&mut parents, other as u32),
+                );
+                if a != b {
+                    parents[a.max(b) as usize] = a.min(b);
+                }
+            }
+        });
+    }
+    // border points join the cluster of the first core point found
+    for (index, p) in points.iter().enumerate().filter(|&(index, _)| !core[index]) {
+        let mut attached = false;
+        grid.for_each_neighbour(p.position, config.radius, |other, _, _| {
+            if !attached && core[other] {
+                parents[index] = find_root(&mut parents, other as u32);
+                attached = true;
+            }
+        });
+    }
+
+    let roots: Vec<u32> = (0..points.len() as u32)
+        .map(|index| find_root(&mut parents, index))
+        .collect();
+    let mut sizes = vec![0; points.len()];
+    for &root in &roots {
+        sizes[root as usize] += 1;
+    }
+    let mut labels = vec![NO_FLOCK; points.len()];
+    let mut next = 0;
+    for index in 0..points.len() {
+        let root = roots[index] as usize;
+        // a lone non core point is its own root
+        if sizes[root] < config.min_size.max(2) {
+            continue;
+        }
+        if labels[root] == NO_FLOCK {
+            labels[root] = next;
+            next += 1;
+        }
+        labels[index] = labels[root];
+    }
+    labels
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Flock {
+    pub id: u32,
+    pub size: usize,
+    /// step of the update at which the flock appeared
+    pub born: u64,
+}
+
+/// Changes of the flocks
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct FlockEvents {
+    /// new flocks sharing no point with the previous ones
+    pub births: usize,
+    /// previous flocks sharing no point with the new ones
+    pub deaths: usize,
+    /// previous flocks linked to several new ones
+    pub splits: usize,
+    /// new flocks linked to several previous ones
+    pub merges: usize,
+}
+
+impl std::ops::AddAssign for FlockEvents {
+    fn add_assign(&mut self, other: Self) {
+        self.births += other.births;
+        self.deaths += other.deaths;
+        self.splits += other.splits;
+        self.merges += other.merges;
+    }
+}
+
+/// Flock of each point, with ids kept from one update to the next: a new flock continues
+/// the previous flock with which it shares the most points (when it is also the new flock
+/// sharing the most points with that one), other flocks get new ids
+#[derive(Debug, Clone, Default)]
+pub struct FlockTracker {
+    config: FlockConfig,
+    ids: Vec<u32>,
+    /// largest first
+    flocks: Vec<Flock>,
+    next_id: u32,
+    events: FlockEvents,
+    total: FlockEvents,
+}
+
+impl FlockTracker {
+    pub fn new(config: FlockConfig) -> Self {
+        FlockTracker {
+            config,
+            ..FlockTracker::default()
+        }
+    }
+
+    pub fn config(&self) -> &FlockConfig {
+        &self.config
+    }
+
+    /// Flock id of each point at the last update ([NO_FLOCK] for points in no flock)
+    pub fn ids(&self) -> &[u32] {
+        &self.ids
+    }
+
+    /// Flocks of the last update, largest first
+    pub fn flocks(&self) -> &[Flock] {
+        &self.flocks
+    }
+
+    /// Changes at the last update
+    pub fn events(&self) -> FlockEvents {
+        self.events
+    }
+
+    /// Changes since the creation (or reset) of the tracker
+    pub fn total_events(&self) -> FlockEvents {
+        self.total
+    }
+
+    /// Forgets the flocks (e.g. after a change of the points)
+    pub fn reset(&mut self) {
+        *self = FlockTracker::new(self.config.clone());
+    }
+
+    /// Clusters `points` and matches the clusters with the previous flocks
+    pub fn update(&mut self, points: &[Point], space_size: (f32, f32), step: u64) -> FlockEvents {
+        let grid = SpatialGrid::new(points, space_size, self.config.radius);
+        let labels = dbscan_labels(points, &grid, &self.config);
+        let cluster_count = labels
+            .iter()
+            .filter(|&&l| l != NO_FLOCK)
+            .map(|&l| l as usize + 1)
+            .max()
+            .unwrap_or(0);
+        let mut sizes = vec![0; cluster_count];
+        for &label in labels.iter().filter(|&&l| l != NO_FLOCK) {
+            sizes[label as usize] += 1;
+        }
+
+        // points shared by each (previous flock, new cluster) pair
+        let mut overlaps: HashMap<(u32, u32), usize> = HashMap::new();
+        for (&id, &label) in self.ids.iter().zip(&labels) {
+            if id != NO_FLOCK && label != NO_FLOCK {
+                *overlaps.entry((id, label)).or_insert(0) += 1;
+            }
+        }
+        // size and birth step of the previous flocks
+        let previous: HashMap<u32, (usize, u64)> = self
+            .flocks
+            .iter()
+            .map(|f| (f.id, (f.size, f.born)))
+            .collect();
+        let min_overlap = self.config.min_overlap;
+        let mut links: Vec<(u32, u32, usize)> = overlaps
+            .into_iter()
+            .filter(|&((id, label), overlap)| {
+                let smaller = previous[&id].0.min(sizes[label as usize]);
+                overlap as f32 >= min_overlap * smaller as f32
+            })
+            .map(|((id, label), overlap)| (id, label, overlap))
+            .collect();
+        // largest overlaps first (ties broken by ids for reproducibility)
+        links.sort_unstable_by(|a, b| b.2.cmp(&a.2).then(a.0.cmp(&b.0)).then(a.1.cmp(&b.1)));
+
+        let mut events = FlockEvents::default();
+        let mut links_of_previous: HashMap<u32, usize> = HashMap::new();
+        let mut links_of_cluster = vec![0; cluster_count];
+        for &(id, label, _) in &links {
+            *links_of_previous.entry(id).or_insert(0) += 1;
+            links_of_cluster[label as usize] += 1;
+        }
+        events.splits = links_of_previous.values().filter(|&&n| n > 1).count();
+        events.merges = links_of_cluster.iter().filter(|&&n| n > 1).count();
+        events.deaths = self.flocks.len() - links_of_previous.len();
+        events.births = links_of_cluster.iter().filter(|&&n| n == 0).count();
+
+        // continuations: best link of both its previous flock and its new cluster
+        let mut continued: HashMap<u32, u32> = HashMap::new(); // previous id -> cluster
+        let mut cluster_ids = vec![NO_FLOCK; cluster_count];
+        for &(id, label, _) in &links {
+            if !continued.contains_key(&id) && cluster_ids[label as usize] == NO_FLOCK {
+                continued.insert(id, label);
+                cluster_ids[label as usize] = id;
+            }
+        }
+        let mut flocks: Vec<Flock> = cluster_ids
+            .iter_mut()
+            .zip(&sizes)
+            .map(|(id, &size)| match previous.get(id) {
+                Some(&(_, born)) => Flock {
+                    id: *id,
+                    size,
+                    born,
+                },
+                None => {
+                    *id = self.next_id;
+                    self.next_id += 1;
+                    Flock {
+                        id: *id,
+                        size,
+                        born: step,
+                    }
+                }
+            })
+            .collect();
+        flocks.sort_by(|a, b| b.size.cmp(&a.size).then(a.id.cmp(&b.id)));
+
+        self.ids = labels
+            .iter()
+            .map(|&label| match label {
+                NO_FLOCK => NO_FLOCK,
+                label => cluster_ids[label as usize],
+            })
+            .collect();
+        self.flocks = flocks;
+        self.events = events;
+        self.total += events;
+        events
+    }
+}
+
+/// Copy of `points` whose species is replaced by a [FLOCK_PALETTE] index of their flock
+/// (points without a known flock id are drawn as points in no flock)
+pub fn color_by_flock(points: &[Point], ids: &[u32]) -> Vec<Point> {
+    points
+        .par_iter()
+        .enumerate()
+        .map(|(index, p)| {
+            let species = match ids.get(index) {
+                Some(&id) if id != NO_FLOCK => 1 + id % (FLOCK_PALETTE.len() as u32 - 1),
+                _ => 0,
+            };
+            Point { species, ..*p }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::point::{Position, Velocity};
+
+    const SPACE_SIZE: (f32, f32) = (200.0, 100.0);
+
+    /// `count` points on a line from `(x, y)` (spacing 1)
+    fn line(x: f32, y: f32, count: usize) -> impl Iterator<Item = Point> {
+        (0..count).map(move |i| Point {
+            position: Position::new((x + i as f32).rem_euclid(SPACE_SIZE.0), y),
+            velocity: Velocity::new(1.0, 0.0),
+            species: 0,
+        })
+    }
+
+    #[test]
+    fn dbscan_should_cluster_across_the_boundary() {
+        let mut points: Vec<Point> = line(190.0, 50.0, 20).collect(); // wraps around x = 0
+        points.extend(line(50.0, 20.0, 15));
+        points.extend(line(50.0, 80.0, 5)); // too small
+        points.push(Point {
+            position: Position::new(120.0, 50.0),
+            ..Point::default()
+        }); // isolated
+        points.push(Point {
+            position: Position::new(64.0, 24.0),
+            ..Point::default()
+        }); // border point of the second line (4 away from its end)
+        let grid = SpatialGrid::new(&points, SPACE_SIZE, 5.0);
+        let labels = dbscan_labels(&points, &grid, &FlockConfig::default());
+
+        assert!(labels[..20].iter().all(|&l| l == 0));
+        assert!(labels[20..35].iter().all(|&l| l == 1));
+        assert!(labels[35..41].iter().all(|&l| l == NO_FLOCK));
+        assert_eq!(labels[41], 1);
+    }
+
+    #[test]
+    fn tracker_should_follow_splits_and_merges() {
+        let mut tracker = FlockTracker::new(FlockConfig::default());
+        let flock: Vec<Point> = line(10.0, 50.0, 60).collect();
+        let events = tracker.update(&flock, SPACE_SIZE, 0);
+        assert_eq!(events.births, 1);
+        let id = tracker.ids()[0];
+
+        // the last 20 points move away: the larger part keeps the id
+        let mut split = flock.clone();
+        for (i, p) in split[40..].iter_mut().enumerate() {
+            p.position = Position::new(150.0 + i as f32, 20.0);
+        }
+        let events = tracker.update(&split, SPACE_SIZE, 10);
+        assert_eq!((events.splits, events.births, events.deaths), (1, 0, 0));
+        assert_eq!(tracker.flocks().len(), 2);
+        assert_eq!(tracker.ids()[0], id);
+        assert_ne!(tracker.ids()[59], id);
+        assert_eq!(tracker.flocks()[1].born, 10);
+
+        let events = tracker.update(&flock, SPACE_SIZE, 20);
+        assert_eq!((events.merges, events.splits), (1, 0));
+        assert!(tracker.ids().iter().all(|&i| i == id));
+        assert_eq!(tracker.flocks()[0].born, 0);
+        assert_eq!(tracker.total_events().splits, 1);
+
+        // the flock scatters while other points gather: a flock dies and another one is born
+        let mut moved: Vec<Point> = flock
+            .iter()
+            .enumerate()
+            .map(|(i, p)| Point {
+                position: Position::new((i % 20) as f32 * 10.0, 5.0 + (i / 20) as f32 * 10.0),
+                ..*p
+            })
+            .collect();
+        moved.extend(line(10.0, 90.0, 60));
+        let events = tracker.update(&moved, SPACE_SIZE, 30);
+        assert_eq!((events.births, events.deaths), (1, 1));
+        assert_eq!(tracker.ids()[0], NO_FLOCK);
+        assert_ne!(tracker.ids()[60], id);
+    }
+
+    #[test]
+    fn flock_colors_should_index_the_palette() {
+        let points: Vec<Point> = line(0.0, 0.0, 3).collect();
+        let colored = color_by_flock(&points, &[NO_FLOCK, 0, 7]);
+        let species: Vec<u32> = colored.iter().map(|p| p.species).collect();
+        assert_eq!(species, [0, 1, 1]);
+        assert_eq!(colored[1].position, points[1].position);
+    }
+}
@@ -0,0 +1,200 @@
+use crate::utils::load_image;
+use anyhow::{anyhow, Context, Result};
+#[cfg(feature = "images")]
+use image::codecs::gif::GifDecoder;
+use image::imageops::{self, FilterType};
+#[cfg(feature = "images")]
+use image::{AnimationDecoder, DynamicImage};
+use image::{ImageFormat, RgbImage};
+#[cfg(feature = "images")]
+use std::fs::File;
+#[cfg(feature = "images")]
+use std::io::BufReader;
+use std::path::{Path, PathBuf};
+
+/// Simulation time each frame is shown (cross-fade included)
+pub const DEFAULT_FRAME_DURATION: f32 = 60.0;
+/// Fraction of the frame duration spent fading into the next frame
+pub const DEFAULT_CROSSFADE: f32 = 0.25;
+
+// The cross-fade weight is quantized so that fields are not rebuilt at every step
+const CROSSFADE_LEVELS: u32 = 16;
+
+/// Image files of a directory (known image extensions), sorted by name
+pub fn list_images<P: AsRef<Path>>(dir: P) -> Result<Vec<PathBuf>> {
+    let dir = dir.as_ref();
+    let mut files = std::fs::read_dir(dir)
+        .with_context(|| format!("Failed to list images of {:?}", dir))?
+        .map(|entry| entry.map(|entry| entry.path()))
+        .collect::<std::io::Result<Vec<_>>>()?;
+    files.retain(|file| file.is_file() && ImageFormat::from_path(file).is_ok());
+    files.sort();
+    Ok(files)
+}
+
+/// Frame (or mix of two consecutive frames) shown at a given time
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct FramePosition {
+    pub frame: usize,
+    /// weight of the next frame, in 1/CROSSFADE_LEVELS units
+    pub blend: u32,
+}
+
+/// Frames of an animated GIF
+#[cfg(feature = "images")]
+fn load_gif_frames(path: &Path) -> Result<Vec<RgbImage>> {
+    let file = File::open(path).with_context(|| format!("Failed to open {:?}", path))?;
+    Ok(GifDecoder::new(BufReader::new(file))
+        .and_then(|decoder| decoder.into_frames().collect_frames())
+        .with_context(|| format!("Failed to decode GIF {:?}", path))?
+        .into_iter()
+        .map(|frame| DynamicImage::ImageRgba8(frame.into_buffer()).to_rgb8())
+        .collect())
+}
+
+#[cfg(not(feature = "images"))]
+fn load_gif_frames(path: &Path) -> Result<Vec<RgbImage>> {
+    load_image(path).map(|img| vec![img])
+}
+
+/// Background images shown one after the other, looping, with an optional cross-fade
+/// (all frames are resized to the dimensions of the first one)
+pub struct ImageSequence {
+    frames: Vec<RgbImage>,
+    frame_duration: f32,
+    crossfade: f32,
+}
+
+impl ImageSequence {
+    /// A single image that never changes
+    pub fn still(img: RgbImage) -> Self {
+        ImageSequence {
+            frames: vec![img],
+            frame_duration: DEFAULT_FRAME_DURATION,
+            crossfade: 0.0,
+        }
+    }
+
+    /// Loads a directory of images (sorted by file name), the frames of an animated GIF
+    /// or a single image
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let path = path.as_ref();
+        let frames = if path.is_dir() {
+            list_images(path)?
+                .iter()
+                .map(load_image)
+                .collect::<Result<Vec<_>>>()?
+        } else if ImageFormat::from_path(path).ok() == Some(ImageFormat::Gif) {
+            load_gif_frames(path)?
+        } else {
+            vec![load_image(path)?]
+        };
+        Self::new(frames).with_context(|| format!("Invalid image sequence {:?}", path))
+    }
+
+    pub fn new(mut frames: Vec<RgbImage>) -> Result<Self> {
+        let (width, height) = frames
+            .first()
+            .ok_or_else(|| anyhow!("no image found"))?
+            .dimensions();
+        for frame in frames.iter_mut() {
+            if frame.dimensions() != (width, height) {
+                *frame = imageops::resize(frame, width, height, FilterType::Triangle);
+            }
+        }
+        Ok(ImageSequence {
+            frames,
+            frame_duration: DEFAULT_FRAME_DURATION,
+            crossfade: DEFAULT_CROSSFADE,
+        })
+    }
+
+    /// `frame_duration` in simulation time, `crossfade` as a fraction of it (0: hard cuts)
+    pub fn with_timing(mut self, frame_duration: f32, crossfade: f32) -> Result<Self> {
+        if frame_duration <= 0.0 || frame_duration.is_nan() {
+            return Err(anyhow!("frame duration must be positive"));
+        }
+        if !(0.0..=1.0).contains(&crossfade) {
+            return Err(anyhow!("cross-fade must be within [0, 1]"));
+        }
+        self.frame_duration = frame_duration;
+        self.crossfade = crossfade;
+        Ok(self)
+    }
+
+    pub fn len(&self) -> usize {
+        self.frames.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.frames.is_empty()
+    }
+
+    pub fn position(&self, time: f32) -> FramePosition {
+        let count = self.frames.len();
+        let frames = (time / self.frame_duration).max(0.0);
+        let frame = frames as usize % count;
+        let fade_start = 1.0 - self.crossfade;
+        let progress = frames.fract();
+        let blend = if count > 1 && self.crossfade > 0.0 && progress > fade_start {
+            ((progress - fade_start) / self.crossfade * CROSSFADE_LEVELS as f32) as u32
+        } else {
+            0
+        };
+        FramePosition { frame, blend }
+    }
+
+    /// Image shown at `position` (cf. [ImageSequence::position])
+    pub fn image(&self, position: FramePosition) -> RgbImage {
+        let current = &self.frames[position.frame];
+        if position.blend == 0 {
+            return current.clone();
+        }
+        let next = &self.frames[(position.frame + 1) % self.frames.len()];
+        let weight = position.blend as f32 / CROSSFADE_LEVELS as f32;
+        let mut mixed = current.clone();
+        for (pixel, other) in mixed.pixels_mut().zip(next.pixels()) {
+            for (c, o) in pixel.0.iter_mut().zip(other.0.iter()) {
+                *c = (*c as f32 * (1.0 - weight) + *o as f32 * weight).round() as u8;
+            }
+        }
+        mixed
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::Rgb;
+
+    #[test]
+    fn sequence_should_loop_and_crossfade_into_the_next_frame() {
+        let frame = |value: u8| RgbImage::from_pixel(2, 2, Rgb([value, value, value]));
+        let sequence = ImageSequence::new(vec![frame(0), frame(200)])
+            .unwrap()
+            .with_timing(10.0, 0.5)
+            .unwrap();
+
+        assert_eq!(sequence.position(2.0), FramePosition { frame: 0, blend: 0 });
+        let halfway = sequence.position(7.5); // middle of the cross-fade
+        assert_eq!(halfway.frame, 0);
+        assert_eq!(halfway.blend, CROSSFADE_LEVELS / 2);
+        assert_eq!(
+            sequence.image(halfway).get_pixel(1, 1),
+            &Rgb([100, 100, 100])
+        );
+        assert_eq!(
+            sequence.position(12.0),
+            FramePosition { frame: 1, blend: 0 }
+        );
+        // last frame fades back into the first one
+        assert_eq!(
+            sequence.position(20.0),
+            FramePosition { frame: 0, blend: 0 }
+        );
+        assert_eq!(
+            sequence.image(sequence.position(19.9)).get_pixel(0, 0)[0],
+            13
+        );
+    }
+}
@@ -0,0 +1,29 @@
+//! Particles driven by an image: simulation core (points, species, fields, obstacles...)
+//! free of any windowing dependency, and the OpenGL `render` module used by the viewer binary
+//!
+//! Cargo features (all enabled by default): `gui` (the `render` module and the viewer),
+//! `images` (image decoding) and `parallel` (rayon); `--no-default-features` gives a headless core.
+
+pub mod analysis;
+pub mod behavior;
+pub mod color;
+pub mod field;
+pub mod flocks;
+pub mod image_sequence;
+pub mod lifecycle;
+pub mod models;
+pub mod neighbours;
+pub mod noise;
+pub mod obstacles;
+pub mod parallel;
+pub mod point;
+pub mod points_simulator;
+pub mod predators;
+pub mod recorder;
+#[cfg(feature = "gui")]
+pub mod render;
+pub mod scenario;
+pub mod soa;
+pub mod species;
+pub mod trajectory;
+pub mod utils;
@@ -0,0 +1,462 @@
+use crate::field::ImageScalarField;
+use crate::obstacles::Shape;
+use crate::point::{Point, Position, Velocity};
+use crate::species::SpeciesSet;
+use anyhow::{ensure, Context, Result};
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+
+/// Number of points of the historical fixed population
+pub const DEFAULT_POINT_COUNT: usize = 200_000;
+
+// Resolution of the random species choice of spawned points (cf. [SpeciesSet::species_for])
+const SPECIES_DRAWS: usize = 1 << 16;
+
+/// Where an emitter spawns points
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum EmitterShape {
+    Point {
+        position: Position,
+    },
+    /// uniformly along a segment
+    Line {
+        from: Position,
+        to: Position,
+    },
+    /// uniformly over an axis-aligned rectangle
+    Area {
+        min: Position,
+        max: Position,
+    },
+    /// over the whole space, proportionally to the background values (bright pixels spawn more
+    /// with the default colour conversion)
+    Image,
+}
+
+/// Spawns points at a constant rate
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Emitter {
+    pub shape: EmitterShape,
+    /// points spawned per simulation time unit
+    pub rate: f32,
+    /// lifetime of spawned points, drawn in [min, max] (simulation time)
+    pub lifetime: (f32, f32),
+    /// initial velocity: heading (rad, 0 along +x, y pointing down) with a random deviation
+    /// in [-spread / 2, spread / 2]
+    #[serde(default)]
+    pub direction: f32,
+    #[serde(default = "full_turn")]
+    pub spread: f32,
+    #[serde(default)]
+    pub speed: f32,
+    /// species of spawned points (by default drawn following species proportions)
+    #[serde(default)]
+    pub species: Option<u32>,
+}
+
+impl Emitter {
+    /// Checks what deserialization does not: a finite non-negative rate, an ordered
+    /// non-negative lifetime range and the species id (among `species_count` species)
+    pub fn check(&self, species_count: usize) -> Result<()> {
+        ensure!(
+            self.rate.is_finite() && self.rate >= 0.0,
+            "Emission rate must be finite and non-negative: {}",
+            self.rate
+        );
+        let (min, max) = self.lifetime;
+        ensure!(
+            0.0 <= min && min <= max && max.is_finite(),
+            "Lifetime range must be ordered and non-negative: {:?}",
+            self.lifetime
+        );
+        if let Some(id) = self.species {
+            ensure!(
+                (id as usize) < species_count,
+                "Unknown species {} ({} species)",
+                id,
+                species_count
+            );
+        }
+        Ok(())
+    }
+}
+
+fn full_turn() -> f32 {
+    std::f32::consts::TAU
+}
+
+/// Conditions removing points before the end of their lifetime
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct DeathConditions {
+    /// points leaving the simulation space die instead of wrapping around
+    #[serde(default)]
+    pub leaving_domain: bool,
+    /// points entering one of these regions die
+    #[serde(default)]
+    pub absorbing: Vec<Shape>,
+}
+
+fn default_point_count() -> usize {
+    DEFAULT_POINT_COUNT
+}
+
+/// Serialisable description of the population dynamics (cf. [Lifecycle])
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LifecycleConfig {
+    /// points created at (re)initialization; they never die of old age
+    #[serde(default = "default_point_count")]
+    pub initial_points: usize,
+    /// maximum number of live points (size of the pool, emitters pause when it is full)
+    #[serde(default = "default_point_count")]
+    pub capacity: usize,
+    #[serde(default)]
+    pub emitters: Vec<Emitter>,
+    #[serde(default)]
+    pub death: DeathConditions,
+}
+
+impl Default for LifecycleConfig {
+    fn default() -> Self {
+        LifecycleConfig {
+            initial_points: DEFAULT_POINT_COUNT,
+            capacity: DEFAULT_POINT_COUNT,
+            emitters: Vec::new(),
+            death: DeathConditions::default(),
+        }
+    }
+}
+
+impl LifecycleConfig {
+    /// Checks the emitters (spawning among `species_count` species) and the absorbing regions
+    pub fn check(&self, species_count: usize) -> Result<()> {
+        for (index, emitter) in self.emitters.iter().enumerate() {
+            emitter
+                .check(species_count)
+                .with_context(|| format!("Invalid emitter {}", index))?;
+        }
+        for (index, shape) in self.death.absorbing.iter().enumerate() {
+            shape
+                .check()
+                .with_context(|| format!("Invalid absorbing region {}", index))?;
+        }
+        Ok(())
+    }
+
+    /// True when the population never changes (no emitter nor death condition)
+    pub fn is_static(&self) -> bool {
+        self.emitters.is_empty() && !self.death.leaving_domain && self.death.absorbing.is_empty()
+    }
+
+    /// Same configuration in a simulation space scaled by `scale`
+    pub fn scaled(&self, scale: (f32, f32)) -> LifecycleConfig {
+        let scale_point = |p: &Position| Position::new(p.x * scale.0, p.y * scale.1);
+        let mut config = self.clone();
+        for emitter in config.emitters.iter_mut() {
+            emitter.shape = match &emitter.shape {
+                EmitterShape::Point { position } => EmitterShape::Point {
+                    position: scale_point(position),
+                },
+                EmitterShape::Line { from, to } => EmitterShape::Line {
+                    from: scale_point(from),
+                    to: scale_point(to),
+                },
+                EmitterShape::Area { min, max } => EmitterShape::Area {
+                    min: scale_point(min),
+                    max: scale_point(max),
+                },
+                EmitterShape::Image => EmitterShape::Image,
+            };
+        }
+        config.death.absorbing = self
+            .death
+            .absorbing
+            .iter()
+            .map(|s| s.scaled(scale))
+            .collect();
+        config
+    }
+}
+
+/// Age of a point and the age at which it dies
+#[derive(Debug, Copy, Clone)]
+struct Life {
+    age: f32,
+    lifetime: f32,
+}
+
+impl Life {
+    const IMMORTAL: Life = Life {
+        age: 0.0,
+        lifetime: f32::INFINITY,
+    };
+}
+
+/// What emitters need to know about the simulation
+pub struct SpawnContext<'a> {
+    pub space_size: (f32, f32),
+    pub species: &'a SpeciesSet,
+    pub background: &'a ImageScalarField,
+    /// cf. [crate::points_simulator::PointsSimulator::background_generation]
+    pub background_generation: u64,
+}
+
+/// Births, ageing and deaths of the points.
+/// Points are pooled: the vector of points never grows beyond the configured capacity
+/// (no reallocation) and dead points are replaced by the last ones, so that live points
+/// always stay contiguous (and can be uploaded to the GPU as they are).
+pub struct Lifecycle {
+    config: LifecycleConfig,
+    lives: Vec<Life>,  // same order as the points
+    pending: Vec<f32>, // fractional number of points to spawn, per emitter
+    // cumulated background values used by image emitters, with their background generation
+    image_weights: Option<(u64, Vec<f32>)>,
+}
+
+impl Lifecycle {
+    pub fn new(config: LifecycleConfig) -> Self {
+        Lifecycle {
+            pending: vec![0.0; config.emitters.len()],
+            config,
+            lives: Vec::new(),
+            image_weights: None,
+        }
+    }
+
+    pub fn config(&self) -> &LifecycleConfig {
+        &self.config
+    }
+
+    /// Resizes `points` to the initial population (to be initialized by the caller)
+    pub fn reset(&mut self, points: &mut Vec<Point>) {
+        let count = self.config.initial_points.min(self.config.capacity);
+        points.clear();
+        points.reserve_exact(self.config.capacity);
+        points.resize(count, Point::default());
+        self.lives.clear();
+        self.lives.resize(count, Life::IMMORTAL);
+        self.pending.iter_mut().for_each(|pending| *pending = 0.0);
+    }
+
+    /// Grows or shrinks the population to `count` points (the pool grows if needed, the last
+    /// points are removed first); returns the index of the first added point, to be initialized
+    /// by the caller. The count is kept by later resets.
+    pub fn resize(&mut self, points: &mut Vec<Point>, count: usize) -> usize {
+        let first = points.len().min(count);
+        self.config.initial_points = count;
+        if count > self.config.capacity {
+            self.config.capacity = count;
+            points.reserve_exact(count - points.len());
+        }
+        points.resize(count, Point::default());
+        self.lives.resize(count, Life::IMMORTAL);
+        first
+    }
+
+    /// Ages the points, removes the dead ones and spawns new ones
+    /// (`points` must be the ones given to [Lifecycle::reset] since then)
+    pub fn step(&mut self, points: &mut Vec<Point>, time_step: f32, context: &SpawnContext) {
+        if self.config.is_static() {
+            return;
+        }
+        let (width, height) = context.space_size;
+        let death = &self.config.death;
+        let mut index = 0;
+        while index < points.len() {
+            let life = &mut self.lives[index];
+            life.age += time_step;
+            let p = points[index].position;
+            let dead = life.age >= life.lifetime
+                || (death.leaving_domain
+                    && (p.x < 0.0 || p.x > width || p.y < 0.0 || p.y > height))
+                || death.absorbing.iter().any(|shape| shape.contains(p));
+            if dead {
+                points.swap_remove(index);
+                self.lives.swap_remove(index);
+            } else {
+                index += 1;
+            }
+        }
+
+        let mut rng = rand::thread_rng();
+        for (emitter_index, emitter) in self.config.emitters.iter().enumerate() {
+            let pending = &mut self.pending[emitter_index];
+            *pending += emitter.rate * time_step;
+            let free = self.config.capacity.saturating_sub(points.len());
+            let count = (pending.floor() as usize).min(free);
+            *pending -= pending.floor();
+            if count == 0 {
+                continue;
+            }
+            if let EmitterShape::Image = emitter.shape {
+                let outdated = !matches!(&self.image_weights,
+                    Some((generation, _)) if *generation == context.background_generation);
+                if outdated {
+                    let (_, _, values) = context.background.values();
+                    let mut total = 0.0;
+                    let cumulated = values
+                        .iter()
+                        .map(|value| {
+                            total += value.max(0.0);
+                            total
+                        })
+                        .collect();
+                    self.image_weights = Some((context.background_generation, cumulated));
+                }
+            }
+            for _ in 0..count {
+                let position = match &emitter.shape {
+                    EmitterShape::Point { position } => *position,
+                    EmitterShape::Line { from, to } => from + (to - from) * rng.gen::<f32>(),
+                    EmitterShape::Area { min, max } => Position::new(
+                        min.x + (max.x - min.x) * rng.gen::<f32>(),
+                        min.y + (max.y - min.y) * rng.gen::<f32>(),
+                    ),
+                    EmitterShape::Image => {
+                        let weights = &self.image_weights.as_ref().unwrap().1;
+                        let (columns, rows, _) = context.background.values();
+                        let target = rng.gen::<f32>() * weights.last().copied().unwrap_or(0.0);
+                        let pixel = weights
+                            .partition_point(|&w| w <= target)
+                            .min(weights.len() - 1);
+                        Position::new(
+                            ((pixel % columns as usize) as f32 + rng.gen::<f32>()) * width
+                                / columns as f32,
+                            ((pixel / columns as usize) as f32 + rng.gen::<f32>()) * height
+                                / rows as f32,
+                        )
+                    }
+                };
+                let heading = emitter.direction + (rng.gen::<f32>() - 0.5) * emitter.spread;
+                let (min_lifetime, max_lifetime) = emitter.lifetime;
+                points.push(Point {
+                    position,
+                    velocity: Velocity::new(heading.cos(), heading.sin()) * emitter.speed,
+                    species: emitter.species.unwrap_or_else(|| {
+                        context
+                            .species
+                            .species_for(rng.gen_range(0, SPECIES_DRAWS), SPECIES_DRAWS)
+                    }),
+                });
+                self.lives.push(Life {
+                    age: 0.0,
+                    lifetime: min_lifetime + (max_lifetime - min_lifetime) * rng.gen::<f32>(),
+                });
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::color::ColorConversion;
+    use image::{Rgb, RgbImage};
+
+    fn context<'a>(species: &'a SpeciesSet, background: &'a ImageScalarField) -> SpawnContext<'a> {
+        SpawnContext {
+            space_size: (100.0, 100.0),
+            species,
+            background,
+            background_generation: 0,
+        }
+    }
+
+    #[test]
+    fn emitted_points_should_age_and_die_while_the_pool_stays_contiguous() {
+        let species = SpeciesSet::single();
+        let background = ImageScalarField::from_image(
+            &RgbImage::new(1, 1),
+            (100.0, 100.0),
+            &ColorConversion::default(),
+        );
+        let mut lifecycle = Lifecycle::new(LifecycleConfig {
+            initial_points: 0,
+            capacity: 15,
+            emitters: vec![Emitter {
+                shape: EmitterShape::Point {
+                    position: Position::new(50.0, 50.0),
+                },
+                rate: 10.0,
+                lifetime: (2.0, 2.0),
+                direction: 0.0,
+                spread: 0.0,
+                speed: 1.0,
+                species: None,
+            }],
+            death: DeathConditions {
+                leaving_domain: true,
+                absorbing: Vec::new(),
+            },
+        });
+        let mut points = Vec::new();
+        lifecycle.reset(&mut points);
+        let capacity = points.capacity();
+
+        lifecycle.step(&mut points, 1.0, &context(&species, &background));
+        assert_eq!(points.len(), 10);
+        assert_eq!(points[0].velocity, Velocity::new(1.0, 0.0));
+        lifecycle.step(&mut points, 1.0, &context(&species, &background));
+        assert_eq!(points.len(), 15); // capacity reached
+        lifecycle.step(&mut points, 1.0, &context(&species, &background));
+        assert_eq!(points.len(), 15); // first 10 died of old age, 10 born
+        points[0].position.x = -1.0; // left the domain
+        lifecycle.step(&mut points, 0.1, &context(&species, &background));
+        assert_eq!(points.len(), 15); // 1 death, 1 birth
+        assert_eq!(lifecycle.lives.len(), points.len());
+        assert_eq!(points.capacity(), capacity);
+    }
+
+    #[test]
+    fn image_emitter_should_only_spawn_on_bright_pixels() {
+        let species = SpeciesSet::single();
+        let mut img = RgbImage::new(4, 4);
+        img.put_pixel(2, 1, Rgb([255, 255, 255]));
+        let background =
+            ImageScalarField::from_image(&img, (100.0, 100.0), &ColorConversion::default());
+        let mut lifecycle = Lifecycle::new(LifecycleConfig {
+            initial_points: 0,
+            capacity: 100,
+            emitters: vec![Emitter {
+                shape: EmitterShape::Image,
+                rate: 100.0,
+                lifetime: (10.0, 10.0),
+                direction: 0.0,
+                spread: full_turn(),
+                speed: 0.0,
+                species: Some(0),
+            }],
+            death: DeathConditions::default(),
+        });
+        let mut points = Vec::new();
+        lifecycle.reset(&mut points);
+        lifecycle.step(&mut points, 1.0, &context(&species, &background));
+
+        assert_eq!(points.len(), 100);
+        for p in points.iter() {
+            assert!((50.0..75.0).contains(&p.position.x) && (25.0..50.0).contains(&p.position.y));
+        }
+    }
+
+    #[test]
+    fn resize_should_keep_existing_points_and_the_new_count() {
+        let mut lifecycle = Lifecycle::new(LifecycleConfig {
+            initial_points: 10,
+            capacity: 10,
+            ..LifecycleConfig::default()
+        });
+        let mut points = Vec::new();
+        lifecycle.reset(&mut points);
+        points[3].position = Position::new(1.0, 2.0);
+
+        assert_eq!(lifecycle.resize(&mut points, 25), 10);
+        assert_eq!(points.len(), 25);
+        assert_eq!(points[3].position, Position::new(1.0, 2.0));
+        assert_eq!(lifecycle.resize(&mut points, 5), 5);
+        assert_eq!(points.len(), 5);
+        assert_eq!(points[3].position, Position::new(1.0, 2.0));
+        lifecycle.reset(&mut points);
+        assert_eq!(points.len(), 5);
+        assert_eq!(lifecycle.lives.len(), 5);
+    }
+}
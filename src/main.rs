@@ -1,26 +1,53 @@
-use glutin::event::{ElementState, Event, KeyboardInput, StartCause, VirtualKeyCode, WindowEvent};
+use glutin::event::{
+    ElementState, Event, KeyboardInput, MouseButton, StartCause, VirtualKeyCode, WindowEvent,
+};
 use glutin::event_loop::{ControlFlow, EventLoop};
 use glutin::window::{Fullscreen, Window, WindowBuilder};
 use glutin::{ContextBuilder, ContextWrapper, PossiblyCurrent};
 
-#[allow(unused_imports)]
-use crate::fps::{FpsCache, FpsCounter};
-use crate::render::{Renderer, RendererConfig};
 use glutin::dpi::PhysicalSize;
-use std::path::PathBuf;
-
-use crate::points_simulator::PointsSimulator;
-use anyhow::{anyhow, Result};
+use life_of_boids::analysis::analyze;
+use life_of_boids::behavior::BehaviorPipeline;
+use life_of_boids::color::ColorConversion;
+use life_of_boids::flocks::{color_by_flock, FlockConfig, FlockTracker, FLOCK_PALETTE};
+use life_of_boids::image_sequence::{
+    list_images, ImageSequence, DEFAULT_CROSSFADE, DEFAULT_FRAME_DURATION,
+};
+use life_of_boids::models::ModelPreset;
+use life_of_boids::point::Position;
+use life_of_boids::predators::PursuitTarget;
+use life_of_boids::recorder::{Metric, Recorder, RecorderConfig};
+use life_of_boids::render::capture::{CaptureConfig, FrameCapture};
+#[allow(unused_imports)]
+use life_of_boids::render::fps::{FpsCache, FpsCounter};
+use life_of_boids::render::glx::{self, UploadMode};
+use life_of_boids::render::gpu_simulator::GpuSimulator;
+use life_of_boids::render::hud::{Hud, HudStats};
+use life_of_boids::render::shader_programs::overlay::OverlayBuilder;
+use life_of_boids::render::{
+    draw_obstacles, draw_predators, PointsSource, Renderer, RendererConfig,
+};
+use life_of_boids::scenario::Scenario;
+use life_of_boids::species::SpeciesSet;
+use life_of_boids::trajectory::{TrajectoryConfig, TrajectoryWriter};
+use std::path::{Path, PathBuf};
 
-#[macro_use]
-mod glx;
-mod fps;
-mod points_simulator;
-mod render;
-mod shader_programs;
-mod utils;
+use anyhow::{anyhow, Context, Result};
+use life_of_boids::points_simulator::{PointsSimulator, DEFAULT_BACKGROUND};
 
 const TITLE: &str = "new rusty boids";
+// Distance (in simulation space) within which a right click removes a predator
+const PREDATOR_PICK_RADIUS: f32 = 30.0;
+const SCENARIO_FILE: &str = "scenario.json";
+const DEMO_SCENARIO_FILE: &str = "./assets/scenarios/obstacles.json";
+const FOUNTAIN_SCENARIO_FILE: &str = "./assets/scenarios/fountain.json";
+// Point count change applied by the +/- keys (and the lower bound of `-`)
+const POINT_COUNT_FACTOR: f32 = 1.25;
+const MIN_POINT_COUNT: usize = 1000;
+// Images cycled through by the next background key
+const BACKGROUNDS_DIR: &str = "./assets/";
+// Default steps between two updates of the tracked flocks (clustering all points is slow)
+const FLOCK_INTERVAL: u64 = 10;
 // const CACHE_FPS_MS: u64 = 500;
 
 pub enum WindowConfig {
@@ -29,7 +56,189 @@ pub enum WindowConfig {
     Default,
 }
 
+/// Background given on the command line:
+/// `[IMAGE | DIRECTORY | GIF [FRAME_DURATION [CROSSFADE]]]`
+/// (frame duration in simulation time, cross-fade as a fraction of it);
+/// the timing also applies to backgrounds loaded at runtime
+struct BackgroundArgs {
+    path: Option<PathBuf>,
+    frame_duration: f32,
+    crossfade: f32,
+}
+
+impl BackgroundArgs {
+    fn parse(args: &[String]) -> Result<Self> {
+        let number = |index: usize, default: f32| -> Result<f32> {
+            match args.get(index) {
+                Some(arg) => arg
+                    .parse()
+                    .with_context(|| format!("Invalid number {:?}", arg)),
+                None => Ok(default),
+            }
+        };
+        Ok(BackgroundArgs {
+            path: args.first().map(PathBuf::from),
+            frame_duration: number(1, DEFAULT_FRAME_DURATION)?,
+            crossfade: number(2, DEFAULT_CROSSFADE)?,
+        })
+    }
+
+    fn load(&self, path: &Path) -> Result<ImageSequence> {
+        ImageSequence::load(path)?.with_timing(self.frame_duration, self.crossfade)
+    }
+}
+
+/// Removes `name` and its value from `args`
+fn take_option(args: &mut Vec<String>, name: &str) -> Result<Option<String>> {
+    match args.iter().position(|arg| arg == name) {
+        Some(index) if index + 1 < args.len() => Ok(args.drain(index..index + 2).nth(1)),
+        Some(_) => Err(anyhow!("Missing value after {}", name)),
+        None => Ok(None),
+    }
+}
+
+/// Removes `name` and its value from `args`, parsed
+fn parse_option<T>(args: &mut Vec<String>, name: &str) -> Result<Option<T>>
+where
+    T: std::str::FromStr,
+    T::Err: std::error::Error + Send + Sync + 'static,
+{
+    take_option(args, name)?
+        .map(|value| {
+            value
+                .parse()
+                .with_context(|| format!("Invalid value {:?} for {}", value, name))
+        })
+        .transpose()
+}
+
+/// Statistics recording, trajectory dump and frame capture given on the command line
+/// (before the background):
+/// `--record FILE.csv|FILE.jsonl [--record-every STEPS] [--metrics NAME,...]`
+/// `--trajectory FILE.bin|FILE.npy [--trajectory-every STEPS] [--trajectory-sample POINTS]`
+/// `--capture DIRECTORY|FILE.y4m [--capture-every FRAMES] [--capture-fps FPS]`
+/// (the capture is started at launch when its path is given, cf. the `I` key otherwise)
+struct RecordArgs {
+    path: Option<PathBuf>,
+    config: RecorderConfig,
+    trajectory_path: Option<PathBuf>,
+    trajectory_config: TrajectoryConfig,
+    capture_at_launch: bool,
+    capture_config: CaptureConfig,
+}
+
+impl RecordArgs {
+    fn parse(args: &mut Vec<String>) -> Result<Self> {
+        let mut config = RecorderConfig::default();
+        if let Some(interval) = parse_option(args, "--record-every")? {
+            config.interval = interval;
+        }
+        if let Some(metrics) = take_option(args, "--metrics")? {
+            config.metrics = Metric::parse_list(&metrics)?;
+        }
+        let mut trajectory_config = TrajectoryConfig::default();
+        if let Some(interval) = parse_option(args, "--trajectory-every")? {
+            trajectory_config.interval = interval;
+        }
+        trajectory_config.sample = parse_option(args, "--trajectory-sample")?;
+        let mut capture_config = CaptureConfig::default();
+        if let Some(every) = parse_option(args, "--capture-every")? {
+            capture_config.every = every;
+        }
+        if let Some(fps) = parse_option(args, "--capture-fps")? {
+            capture_config.fps = fps;
+        }
+        let capture_path = take_option(args, "--capture")?;
+        if let Some(path) = &capture_path {
+            capture_config.path = PathBuf::from(path);
+        }
+        Ok(RecordArgs {
+            path: take_option(args, "--record")?.map(PathBuf::from),
+            config,
+            trajectory_path: take_option(args, "--trajectory")?.map(PathBuf::from),
+            trajectory_config,
+            capture_at_launch: capture_path.is_some(),
+            capture_config,
+        })
+    }
+}
+
+/// Starts capturing the frames of a window of `size`
+fn start_capture(
+    renderer: &Renderer,
+    size: (u32, u32),
+    config: &CaptureConfig,
+    hud: &mut Hud,
+) -> Option<FrameCapture> {
+    match FrameCapture::start(renderer.gl.clone(), size, config) {
+        Ok(capture) => {
+            hud.notify(format!("Capturing frames to {}", config.path.display()));
+            Some(capture)
+        }
+        Err(err) => {
+            hud.notify(format!("Cannot capture frames: {:#}", err));
+            None
+        }
+    }
+}
+
+/// Writes the last captured frames (waiting for them) and reports them on the HUD
+fn stop_capture(capture: FrameCapture, hud: &mut Hud) {
+    match capture.finish() {
+        Ok(report) => hud.notify(format!("Capture stopped: {}", report)),
+        Err(err) => hud.notify(format!("Capture failed: {:#}", err)),
+    }
+}
+
+/// Copies the GPU points back to `s`; on failure the CPU points are kept and the error is
+/// reported on the HUD
+fn sync_from_gpu(backend: &GpuSimulator, s: &mut PointsSimulator, hud: &mut Hud) {
+    if let Err(err) = backend.sync(s) {
+        hud.notify(format!("Cannot read back the GPU points: {:#}", err));
+    }
+}
+
+/// Next image of `BACKGROUNDS_DIR` after `current` (the first one if `current` is not there)
+fn next_background(current: &Path) -> Result<PathBuf> {
+    let images = list_images(BACKGROUNDS_DIR)?;
+    let current = current.canonicalize().ok();
+    let index = images
+        .iter()
+        .position(|image| image.canonicalize().ok() == current)
+        .map_or(0, |index| index + 1);
+    images
+        .get(index % images.len().max(1))
+        .cloned()
+        .ok_or_else(|| anyhow!("no image in {}", BACKGROUNDS_DIR))
+}
+
+/// Loads `path` as the simulator background; errors are reported on the HUD
+/// and leave the current background in place
+fn swap_background(
+    s: &mut PointsSimulator,
+    hud: &mut Hud,
+    args: &BackgroundArgs,
+    path: PathBuf,
+    current: &mut PathBuf,
+) {
+    match args.load(&path) {
+        Ok(sequence) => {
+            s.set_background(sequence);
+            hud.notify(format!("Background: {}", path.display()));
+            *current = path;
+        }
+        Err(err) => hud.notify(format!("Cannot load background: {:#}", err)),
+    }
+}
+
 fn main() -> Result<()> {
+    let mut args: Vec<String> = std::env::args().skip(1).collect();
+    let record_args = RecordArgs::parse(&mut args)?;
+    // `--flock-every STEPS` (1: flock ids of every step, splits and merges counted step by step)
+    let flock_interval = parse_option(&mut args, "--flock-every")?
+        .unwrap_or(FLOCK_INTERVAL)
+        .max(1);
+    let background_args = BackgroundArgs::parse(&args)?;
     let events_loop = EventLoop::new();
 
     let monitor = events_loop
@@ -54,14 +263,13 @@ fn main() -> Result<()> {
     let mut is_maximized = false;
     let mut decorations = true;
 
-    let start_time = std::time::SystemTime::now();
-
     // let mut fps_counter = FpsCounter::new();
     // let mut fps_cacher = FpsCache::new(CACHE_FPS_MS);
 
     let mut last_time = std::time::Instant::now();
     let mut count = 0;
     let mut accumulated_time = 0.0;
+    let mut fps = 0.0;
 
     let window_info =
         glx::get_window_size_info(windowed_context.window()).expect("Cannot get window size info");
@@ -74,7 +282,55 @@ fn main() -> Result<()> {
 
     println!("Current dir = {:?}", std::env::current_dir());
 
-    let mut s = PointsSimulator::new(window_info)?;
+    let mut s = PointsSimulator::new((window_info.width, window_info.height))?;
+    let mut background_path = PathBuf::from(DEFAULT_BACKGROUND);
+    if let Some(path) = &background_args.path {
+        let sequence = background_args.load(path)?;
+        println!("Background: {} frame(s)", sequence.len());
+        s.set_background(sequence);
+        background_path = path.clone();
+    }
+    renderer.set_species_colors(&s.species().colors());
+    let species_presets = SpeciesSet::presets();
+    let mut species_preset = 0;
+    let behavior_presets = BehaviorPipeline::presets();
+    let mut behavior_preset = 0;
+    // same statistics on the HUD and in the record
+    let analysis_config = record_args.config.analysis.clone();
+    let mut analysis_enabled = false;
+    // captured frames when enabled (started at the next frame when requested)
+    let mut capture: Option<FrameCapture> = None;
+    let mut capture_requested = record_args.capture_at_launch;
+    let capture_config = record_args.capture_config;
+    // flocks tracked and coloured when enabled
+    let mut flocks: Option<FlockTracker> = None;
+    let mut recorder = match &record_args.path {
+        Some(path) => {
+            println!("Recording statistics to {}", path.display());
+            Some(Recorder::create(path, record_args.config)?)
+        }
+        None => None,
+    };
+    let mut trajectory = match &record_args.trajectory_path {
+        Some(path) => {
+            println!("Dumping trajectories to {}", path.display());
+            Some(TrajectoryWriter::create(
+                path,
+                &s,
+                record_args.trajectory_config,
+            )?)
+        }
+        None => None,
+    };
+    let model_presets = ModelPreset::presets();
+    // current model and point count to restore when leaving the models
+    let mut model_preset: Option<(usize, usize)> = None;
+    let color_conversions = ColorConversion::presets();
+    let mut color_conversion = 0;
+    let mut hud = Hud::new();
+    // GPU backend when enabled (CPU backend otherwise)
+    let mut gpu: Option<GpuSimulator> = None;
+    let mut cursor = Position::new(0.0, 0.0); // in simulation space
 
     events_loop.run(move |event, _, control_flow| {
         // println!("{:?}", event);
@@ -82,10 +338,56 @@ fn main() -> Result<()> {
         *control_flow = ControlFlow::Poll;
 
         match event {
-            Event::LoopDestroyed => return,
+            // the event loop exits the process without dropping its closure
+            Event::LoopDestroyed => {
+                if let Some(Err(err)) = recorder.as_mut().map(Recorder::flush) {
+                    eprintln!("Cannot save statistics: {:#}", err);
+                }
+                if let Some(Err(err)) = trajectory.as_mut().map(TrajectoryWriter::finish) {
+                    eprintln!("Cannot save trajectories: {:#}", err);
+                }
+                match capture.take().map(FrameCapture::finish) {
+                    Some(Ok(report)) => println!("Capture: {}", report),
+                    Some(Err(err)) => eprintln!("Cannot save captured frames: {:#}", err),
+                    None => (),
+                }
+                return;
+            }
             Event::WindowEvent { event, .. } => match event {
                 WindowEvent::Resized(physical_size) => windowed_context.resize(physical_size),
                 WindowEvent::CloseRequested => *control_flow = ControlFlow::Exit,
+                WindowEvent::DroppedFile(path) => swap_background(
+                    &mut s,
+                    &mut hud,
+                    &background_args,
+                    path,
+                    &mut background_path,
+                ),
+                WindowEvent::CursorMoved { position, .. } => {
+                    let PhysicalSize { width, height } = windowed_context.window().inner_size();
+                    let (space_width, space_height) = s.space_size();
+                    cursor = Position::new(
+                        position.x as f32 / width as f32 * space_width,
+                        position.y as f32 / height as f32 * space_height,
+                    );
+                }
+                WindowEvent::MouseInput {
+                    state: ElementState::Pressed,
+                    button,
+                    ..
+                } => match button {
+                    MouseButton::Left => {
+                        if let Some(backend) = gpu.take() {
+                            sync_from_gpu(&backend, &mut s, &mut hud);
+                            hud.notify("Predators are not supported on GPU, back to CPU");
+                        }
+                        s.add_predator(cursor);
+                    }
+                    MouseButton::Right => {
+                        s.remove_predator_near(cursor, PREDATOR_PICK_RADIUS);
+                    }
+                    _ => (),
+                },
                 WindowEvent::KeyboardInput {
                     input:
                         KeyboardInput {
@@ -115,11 +417,15 @@ fn main() -> Result<()> {
                             "ScaleFactor info: {:?}",
                             windowed_context.window().scale_factor()
                         );
-                        glx::save_image(
+                        let filepath = PathBuf::from("export.png");
+                        match glx::save_image(
                             renderer.gl.clone(),
-                            &PathBuf::from("export.png"),
-                            &windowed_context.window(),
-                        ).unwrap();
+                            &filepath,
+                            windowed_context.window(),
+                        ) {
+                            Ok(()) => hud.notify(format!("Screenshot saved to {:?}", filepath)),
+                            Err(err) => hud.notify(format!("Cannot save screenshot: {}", err)),
+                        }
                     }
                     (VirtualKeyCode::M, ElementState::Pressed) => {
                         is_maximized = !is_maximized;
@@ -130,33 +436,360 @@ fn main() -> Result<()> {
                         windowed_context.window().set_decorations(decorations);
                     }
                     (VirtualKeyCode::R, ElementState::Pressed) => {
-                        println!("Reset points");
+                        hud.notify("Reset points");
                         s.init_points();
+                        if let Some(tracker) = &mut flocks {
+                            tracker.reset();
+                        }
+                        if let Some(gpu) = &mut gpu {
+                            gpu.reset(&s);
+                        }
+                    }
+                    (VirtualKeyCode::Plus, ElementState::Pressed)
+                    | (VirtualKeyCode::Equals, ElementState::Pressed)
+                    | (VirtualKeyCode::NumpadAdd, ElementState::Pressed)
+                    | (VirtualKeyCode::Minus, ElementState::Pressed)
+                    | (VirtualKeyCode::NumpadSubtract, ElementState::Pressed) => {
+                        let factor = match virtual_code {
+                            VirtualKeyCode::Minus | VirtualKeyCode::NumpadSubtract => {
+                                1.0 / POINT_COUNT_FACTOR
+                            }
+                            _ => POINT_COUNT_FACTOR,
+                        };
+                        let current = s.points().len();
+                        let scaled = (current as f32 * factor) as usize;
+                        // `+` always adds points, `-` never does nor goes below the floor
+                        let count = if factor > 1.0 {
+                            scaled.max(current + 1)
+                        } else {
+                            scaled.max(MIN_POINT_COUNT.min(current))
+                        };
+                        // existing points are kept: the GPU ones are fetched first
+                        if let Some(backend) = &gpu {
+                            sync_from_gpu(backend, &mut s, &mut hud);
+                        }
+                        s.set_point_count(count);
+                        if let Some(backend) = &mut gpu {
+                            backend.reset(&s);
+                        }
+                        if let Some(tracker) = &mut flocks {
+                            tracker.reset();
+                        }
+                        hud.notify(format!("{} points", count));
+                    }
+                    (VirtualKeyCode::H, ElementState::Pressed) => {
+                        hud.toggle();
+                    }
+                    (VirtualKeyCode::U, ElementState::Pressed) => {
+                        let mode = match renderer.upload_mode() {
+                            UploadMode::Reallocate => UploadMode::Orphan,
+                            UploadMode::Orphan => UploadMode::Reallocate,
+                        };
+                        renderer.set_upload_mode(mode);
+                        hud.notify(format!("Points upload mode: {:?}", mode));
+                    }
+                    (VirtualKeyCode::A, ElementState::Pressed) => {
+                        analysis_enabled = !analysis_enabled;
+                        hud.notify(match (analysis_enabled, gpu.is_some()) {
+                            (false, _) => "Analysis off",
+                            (true, false) => "Analysis on",
+                            (true, true) => "Analysis on (computed with the CPU backend only)",
+                        });
+                    }
+                    (VirtualKeyCode::I, ElementState::Pressed) => match capture.take() {
+                        Some(current) => stop_capture(current, &mut hud),
+                        // started at the next frame (with the current window size)
+                        None => capture_requested = true,
+                    },
+                    (VirtualKeyCode::T, ElementState::Pressed) => {
+                        flocks = match flocks {
+                            Some(_) => None,
+                            None => Some(FlockTracker::new(FlockConfig::default())),
+                        };
+                        hud.notify(match (flocks.is_some(), gpu.is_some()) {
+                            (false, _) => "Flocks off",
+                            (true, false) => "Flocks on",
+                            (true, true) => "Flocks on (computed with the CPU backend only)",
+                        });
+                    }
+                    (VirtualKeyCode::B, ElementState::Pressed) => {
+                        hud.toggle_labels();
+                    }
+                    (VirtualKeyCode::K, ElementState::Pressed) => {
+                        species_preset = (species_preset + 1) % species_presets.len();
+                        if model_preset.take().is_some() {
+                            s.set_behaviors(behavior_presets[behavior_preset].clone());
+                        }
+                        s.set_species(species_presets[species_preset].clone());
+                        renderer.set_species_colors(&s.species().colors());
+                        let mut message = format!("Species: {}", s.species().name);
+                        match &mut gpu {
+                            Some(backend) if s.species().random_walk_only() => backend.reset(&s),
+                            Some(_) => {
+                                gpu = None;
+                                message += " (not supported on GPU, back to CPU)";
+                            }
+                            None => (),
+                        }
+                        hud.notify(message);
+                    }
+                    (VirtualKeyCode::W, ElementState::Pressed) => {
+                        behavior_preset = (behavior_preset + 1) % behavior_presets.len();
+                        s.set_behaviors(behavior_presets[behavior_preset].clone());
+                        if model_preset.take().is_some() {
+                            s.set_species(species_presets[species_preset].clone());
+                            renderer.set_species_colors(&s.species().colors());
+                        }
+                        let mut message = format!("Behaviours: {}", s.behaviors().names());
+                        if let Some(backend) = gpu.take() {
+                            if s.behaviors().is_random_walk_only() {
+                                gpu = Some(backend);
+                            } else {
+                                sync_from_gpu(&backend, &mut s, &mut hud);
+                                message += " (not supported on GPU, back to CPU)";
+                            }
+                        }
+                        hud.notify(message);
+                    }
+                    (VirtualKeyCode::C, ElementState::Pressed) => {
+                        let (next, point_count) = match model_preset {
+                            None => (0, s.points().len()),
+                            Some((model, point_count)) => (model + 1, point_count),
+                        };
+                        let mut message = if next < model_presets.len() {
+                            model_preset = Some((next, point_count));
+                            s.apply_model(&model_presets[next]);
+                            format!("Model: {}", model_presets[next].name)
+                        } else {
+                            model_preset = None;
+                            s.set_behaviors(behavior_presets[behavior_preset].clone());
+                            s.set_species(species_presets[species_preset].clone());
+                            s.set_point_count(point_count);
+                            format!("Species: {}", s.species().name)
+                        };
+                        renderer.set_species_colors(&s.species().colors());
+                        if model_preset.is_some() && gpu.take().is_some() {
+                            message += " (not supported on GPU, back to CPU)";
+                        }
+                        hud.notify(message);
+                    }
+                    (VirtualKeyCode::E, ElementState::Pressed) => {
+                        s.set_field_preset(s.field_preset() + 1);
+                        let mut message = format!("Fields: {}", s.field_effects().name);
+                        if let Some(backend) = gpu.take() {
+                            if s.fields_are_brightness_speed() {
+                                gpu = Some(backend);
+                            } else {
+                                sync_from_gpu(&backend, &mut s, &mut hud);
+                                message += " (not supported on GPU, back to CPU)";
+                            }
+                        }
+                        hud.notify(message);
+                    }
+                    (VirtualKeyCode::X, ElementState::Pressed) => {
+                        color_conversion = (color_conversion + 1) % color_conversions.len();
+                        s.set_color_conversion(color_conversions[color_conversion].clone());
+                        hud.notify(format!("Colour conversion: {}", s.color_conversion()));
+                    }
+                    (VirtualKeyCode::P, ElementState::Pressed) => {
+                        let target = match s.pursuit_target() {
+                            PursuitTarget::Nearest => PursuitTarget::Densest,
+                            PursuitTarget::Densest => PursuitTarget::Nearest,
+                        };
+                        s.set_pursuit_target(target);
+                        hud.notify(format!("Predators chase: {:?}", target));
+                    }
+                    (VirtualKeyCode::F5, ElementState::Pressed) => {
+                        match s.scenario().save(SCENARIO_FILE) {
+                            Ok(()) => hud.notify(format!("Scenario saved to {}", SCENARIO_FILE)),
+                            Err(err) => hud.notify(format!("Cannot save scenario: {:#}", err)),
+                        }
+                    }
+                    (VirtualKeyCode::F9, ElementState::Pressed)
+                    | (VirtualKeyCode::O, ElementState::Pressed)
+                    | (VirtualKeyCode::L, ElementState::Pressed) => {
+                        let path = match virtual_code {
+                            VirtualKeyCode::O => DEMO_SCENARIO_FILE,
+                            VirtualKeyCode::L => FOUNTAIN_SCENARIO_FILE,
+                            _ => SCENARIO_FILE,
+                        };
+                        match Scenario::load(path).and_then(|scenario| s.apply_scenario(scenario)) {
+                            Ok(()) => {
+                                renderer.set_species_colors(&s.species().colors());
+                                let mut message = format!("Scenario loaded from {}", path);
+                                // the GPU backend only handles plain random walks
+                                if let Some(backend) = &mut gpu {
+                                    match GpuSimulator::new(renderer.gl.clone(), &s) {
+                                        Ok(new_backend) => *backend = new_backend,
+                                        Err(err) => {
+                                            gpu = None;
+                                            message += &format!(
+                                                " (GPU backend unavailable, staying on CPU: {})",
+                                                err
+                                            );
+                                        }
+                                    }
+                                }
+                                hud.notify(message);
+                            }
+                            Err(err) => hud.notify(format!("Cannot load scenario: {:#}", err)),
+                        }
+                    }
+                    (VirtualKeyCode::N, ElementState::Pressed) => {
+                        match next_background(&background_path) {
+                            Ok(path) => swap_background(
+                                &mut s,
+                                &mut hud,
+                                &background_args,
+                                path,
+                                &mut background_path,
+                            ),
+                            Err(err) => hud.notify(format!("Cannot load background: {:#}", err)),
+                        }
+                    }
+                    (VirtualKeyCode::G, ElementState::Pressed) => match gpu.take() {
+                        Some(backend) => {
+                            sync_from_gpu(&backend, &mut s, &mut hud);
+                            hud.notify("CPU backend");
+                        }
+                        None => match GpuSimulator::new(renderer.gl.clone(), &s) {
+                            Ok(backend) => {
+                                gpu = Some(backend);
+                                hud.notify("GPU backend");
+                            }
+                            Err(err) => hud.notify(format!(
+                                "GPU backend unavailable, staying on CPU: {}",
+                                err
+                            )),
+                        },
+                    },
+                    (VirtualKeyCode::V, ElementState::Pressed) => {
+                        let report = match &mut gpu {
+                            Some(backend) => {
+                                backend.sync(&mut s).and_then(|()| backend.validate(&mut s))
+                            }
+                            None => GpuSimulator::new(renderer.gl.clone(), &s)
+                                .and_then(|mut backend| backend.validate(&mut s)),
+                        };
+                        match report {
+                            Ok(report) => hud.notify(report.to_string()),
+                            Err(err) => hud.notify(format!("GPU validation failed: {:#}", err)),
+                        }
                     }
                     _ => (),
                 },
                 _ => (),
             },
             Event::RedrawRequested(_) | Event::NewEvents(StartCause::Poll) => {
-                if let Ok(elapsed) = start_time.elapsed() {
-                    let PhysicalSize { width, height } = windowed_context.window().inner_size();
-                    let ratio = width as f32 / height as f32;
-
-                    s.update();
-                    let points = &s.points;
-
-                    renderer
-                        .render(
-                            elapsed.as_secs_f32(),
-                            ratio,
-                            [0.0, 0.0, 0.0, 0.0],
-                            &points,
-                            (width, height),
-                        )
-                        .unwrap();
-
-                    windowed_context.swap_buffers().unwrap();
+                let PhysicalSize { width, height } = windowed_context.window().inner_size();
+
+                let on_gpu = gpu.is_some();
+                match &mut gpu {
+                    Some(backend) => backend.update(&mut s),
+                    None => s.update(),
+                }
+                let record_due = recorder.as_ref().is_some_and(|r| r.is_due(s.step()));
+                let dump_due = trajectory.as_ref().is_some_and(|t| t.is_due(s.step()));
+                if let (true, Some(backend)) = (record_due || dump_due, &gpu) {
+                    // statistics and trajectories are taken from the CPU copy of the points
+                    sync_from_gpu(backend, &mut s, &mut hud);
+                }
+                if let (true, Some(t)) = (dump_due, &mut trajectory) {
+                    if let Err(err) = t.write(s.step(), s.time(), s.points()) {
+                        hud.notify(format!("Trajectory dump stopped: {:#}", err));
+                        trajectory = None;
+                    }
                 }
+                let statistics = if (analysis_enabled && !on_gpu) || record_due {
+                    Some(analyze(s.points(), s.space_size(), &analysis_config))
+                } else {
+                    None
+                };
+                let tracked_flocks = match (on_gpu, &mut flocks) {
+                    (false, Some(tracker)) => {
+                        // also updated as soon as the points change (e.g. after a reset)
+                        if s.step() % flock_interval == 0 || tracker.ids().len() != s.points().len()
+                        {
+                            tracker.update(s.points(), s.space_size(), s.step());
+                        }
+                        Some(&*tracker)
+                    }
+                    _ => None,
+                };
+                if let (true, Some(r), Some(statistics)) = (record_due, &mut recorder, &statistics)
+                {
+                    if let Err(err) = r.write(s.step(), s.time(), statistics) {
+                        hud.notify(format!("Recording stopped: {:#}", err));
+                        recorder = None;
+                    }
+                }
+                let flock_points =
+                    tracked_flocks.map(|tracker| color_by_flock(s.points(), tracker.ids()));
+                // species colours are those of the flocks while they are shown
+                match &flock_points {
+                    Some(_) => renderer.set_species_colors(&FLOCK_PALETTE),
+                    None => renderer.set_species_colors(&s.species().colors()),
+                }
+                let points = match (&gpu, &flock_points) {
+                    (Some(backend), _) => backend.points(),
+                    (None, Some(points)) => PointsSource::Host(points),
+                    (None, None) => PointsSource::Host(s.points()),
+                };
+
+                let mut overlay = OverlayBuilder::new();
+                draw_obstacles(s.obstacles(), s.space_size(), (width, height), &mut overlay);
+                draw_predators(s.predators(), s.space_size(), (width, height), &mut overlay);
+                let mut text = renderer.new_text_batch();
+                hud.build(
+                    &HudStats {
+                        fps,
+                        point_count: s.points().len(),
+                        step: s.step(),
+                        sim_time: s.time(),
+                        backend: if on_gpu { "GPU" } else { "CPU" },
+                        species: &s.species().name,
+                        upload_mode: match renderer.upload_mode() {
+                            UploadMode::Reallocate => "realloc",
+                            UploadMode::Orphan => "orphan",
+                        },
+                        submit_time: renderer.submit_time(),
+                        statistics: statistics.as_ref(),
+                        flocks: tracked_flocks,
+                    },
+                    // CPU copy of the points is outdated when running on the GPU
+                    if on_gpu { &[] } else { s.points() },
+                    &mut overlay,
+                    &mut text,
+                );
+
+                renderer
+                    .render(
+                        [0.0, 0.0, 0.0, 0.0],
+                        points,
+                        &overlay,
+                        &text,
+                        (width, height),
+                    )
+                    .unwrap();
+
+                if capture_requested {
+                    capture_requested = false;
+                    capture = start_capture(&renderer, (width, height), &capture_config, &mut hud);
+                }
+                // frames of another size cannot be added to the capture
+                match capture.take() {
+                    Some(current) if current.size() != (width, height) => {
+                        hud.notify("Window resized");
+                        stop_capture(current, &mut hud);
+                    }
+                    Some(mut current) => match current.capture() {
+                        Ok(()) => capture = Some(current),
+                        Err(err) => hud.notify(format!("Capture failed: {:#}", err)),
+                    },
+                    None => (),
+                }
+
+                windowed_context.swap_buffers().unwrap();
             }
             _ => (),
         }
@@ -169,7 +802,8 @@ fn main() -> Result<()> {
         accumulated_time += elapsed_time;
         count += 1;
         if accumulated_time > 1000.0 {
-            let title = format!("FPS: {:.2}", count as f64 / (accumulated_time * 0.001));
+            fps = (count as f64 / (accumulated_time * 0.001)) as f32;
+            let title = format!("FPS: {:.2}", fps);
             windowed_context.window().set_title(title.as_str());
             count = 0;
             accumulated_time = 0.0;
@@ -0,0 +1,382 @@
+//! Collective motion models from the literature, as behaviours with their canonical parameters.
+//! Lengths are in simulation space units (pixels) and times in simulation time units:
+//! the reference length of each model (interaction radius) is 10.
+
+use crate::behavior::{heading, random_state, Behavior, BehaviorPipeline, SteeringContext};
+use crate::point::Velocity;
+use crate::species::{Species, SpeciesSet};
+use crate::utils::{hashed_normals, hashed_random};
+use cgmath::{Angle, InnerSpace, Rad, Vector2, Zero};
+use rand::distributions::{IndependentSample, Range};
+use std::f32::consts::PI;
+
+/// Vicsek et al. (1995): constant speed, heading aligned with the mean heading of the neighbours
+/// within `radius` (the point included) plus a uniform angular noise in [-η/2, η/2].
+/// At fixed density, order is lost as η grows.
+pub struct Vicsek {
+    pub radius: f32,
+    pub speed: f32,
+    /// η (rad, in [0, 2π])
+    pub noise: f32,
+    /// stream of random values (cf. [random_state])
+    pub seed: u32,
+}
+
+impl Default for Vicsek {
+    /// Canonical ratios of the 1995 paper: a step of `0.03 r` and η = 2
+    /// (ordered at a density of 4 points per r²)
+    fn default() -> Self {
+        Vicsek {
+            radius: 10.0,
+            speed: 1.5,
+            noise: 2.0,
+            seed: 2,
+        }
+    }
+}
+
+impl Behavior for Vicsek {
+    fn name(&self) -> &str {
+        "vicsek"
+    }
+
+    fn steer(&self, context: &SteeringContext, index: usize) -> Velocity {
+        let p = &context.points[index];
+        let grid = match context.grid {
+            Some(grid) => grid,
+            None => return p.velocity,
+        };
+        let mut mean = Vector2::zero();
+        grid.for_each_neighbour(p.position, self.radius, |other, neighbour, _| {
+            mean += heading(neighbour, other);
+        });
+        let noise =
+            (hashed_random(&mut random_state(context, self.seed, index)) - 0.5) * self.noise;
+        let angle = mean.y.atan2(mean.x) + noise;
+        Vector2::new(angle.cos(), angle.sin()) * self.speed
+    }
+
+    fn perception_radius(&self) -> Option<f32> {
+        Some(self.radius)
+    }
+}
+
+/// Couzin et al. (2002) zone model: neighbours within `repulsion` are avoided (and then the
+/// only ones considered); otherwise the point aligns with neighbours of the orientation zone
+/// and moves toward those of the attraction zone. Neighbours in the blind angle behind the
+/// point are ignored, headings turn at most by `turning_rate` and get a gaussian noise.
+/// Growing the orientation zone goes from a swarm to a torus and then to parallel groups.
+pub struct Couzin {
+    pub speed: f32,
+    /// radius of the repulsion zone (r_r)
+    pub repulsion: f32,
+    /// width of the orientation zone (Δr_o)
+    pub orientation_width: f32,
+    /// width of the attraction zone (Δr_a)
+    pub attraction_width: f32,
+    /// field of perception (α, rad): the blind angle is 2π - α
+    pub field_of_perception: f32,
+    /// largest heading change (θ, rad per time unit)
+    pub turning_rate: f32,
+    /// standard deviation of the heading noise (σ, rad)
+    pub noise: f32,
+    /// stream of random values (cf. [random_state])
+    pub seed: u32,
+}
+
+impl Default for Couzin {
+    /// Parameters of the 2002 paper (r_r = 1, s = 3, α = 270°, θ = 40°, σ = 0.05,
+    /// Δr_o = 2, Δr_a = 14: dynamic parallel group), lengths scaled by 10
+    fn default() -> Self {
+        Couzin {
+            speed: 30.0,
+            repulsion: 10.0,
+            orientation_width: 20.0,
+            attraction_width: 140.0,
+            field_of_perception: 1.5 * PI,
+            turning_rate: 40f32.to_radians(),
+            noise: 0.05,
+            seed: 3,
+        }
+    }
+}
+
+impl Behavior for Couzin {
+    fn name(&self) -> &str {
+        "couzin"
+    }
+
+    fn steer(&self, context: &SteeringContext, index: usize) -> Velocity {
+        let p = &context.points[index];
+        let grid = match context.grid {
+            Some(grid) => grid,
+            None => return p.velocity,
+        };
+        let own_heading = heading(p, index);
+        let orientation_radius = self.repulsion + self.orientation_width;
+        let attraction_radius = orientation_radius + self.attraction_width;
+        let blind_cos = (self.field_of_perception / 2.0).cos();
+
+        let (mut repulsion, mut orientation, mut attraction) =
+            (Vector2::zero(), Vector2::zero(), Vector2::zero());
+        let (mut repelled, mut oriented, mut attracted) = (false, false, false);
+        grid.for_each_neighbour(p.position, attraction_radius, |other, neighbour, offset| {
+            let distance = offset.magnitude();
+            if other == index || distance == 0.0 {
+                return;
+            }
+            let direction = offset / distance;
+            if direction.dot(own_heading) < blind_cos {
+                return;
+            }
+            if distance < self.repulsion {
+                repulsion -= direction;
+                repelled = true;
+            } else if distance < orientation_radius {
+                orientation += heading(neighbour, other);
+                oriented = true;
+            } else {
+                attraction += direction;
+                attracted = true;
+            }
+        });
+
+        let normalized = |v: Vector2<f32>| {
+            if v.magnitude2() > 0.0 {
+                v.normalize()
+            } else {
+                v
+            }
+        };
+        let desired = if repelled {
+            repulsion
+        } else {
+            // the point itself belongs to the orientation zone
+            let orientation = normalized(orientation + own_heading);
+            match (oriented, attracted) {
+                (true, true) => (orientation + normalized(attraction)) / 2.0,
+                (false, true) => attraction,
+                _ => orientation,
+            }
+        };
+        let desired = if desired.magnitude2() > 0.0 {
+            desired
+        } else {
+            own_heading
+        };
+
+        let noise = hashed_normals(&mut random_state(context, self.seed, index)).0 * self.noise;
+        let turn = own_heading.angle(desired).0 + noise;
+        let max_turn = self.turning_rate * context.time_step;
+        let angle = own_heading.y.atan2(own_heading.x) + turn.max(-max_turn).min(max_turn);
+        Vector2::new(angle.cos(), angle.sin()) * self.speed
+    }
+
+    fn perception_radius(&self) -> Option<f32> {
+        Some(self.repulsion + self.orientation_width + self.attraction_width)
+    }
+}
+
+/// Cucker and Smale (2007): velocities relax toward those of the others, weighted by
+/// `ψ(r) = 1 / (1 + (r / scale)²)^β` and normalized by the number of points:
+/// `dv_i/dt = K / N Σ_j ψ(|x_j - x_i|) (v_j - v_i)`.
+/// Flocking is unconditional for β < 1/2 and depends on the initial state beyond.
+/// Points farther than `cutoff` are ignored (all-to-all if it covers the space).
+pub struct CuckerSmale {
+    /// K
+    pub coupling: f32,
+    pub scale: f32,
+    /// β
+    pub beta: f32,
+    pub cutoff: f32,
+}
+
+impl Default for CuckerSmale {
+    fn default() -> Self {
+        CuckerSmale {
+            coupling: 20.0,
+            scale: 10.0,
+            beta: 0.25,
+            cutoff: 200.0,
+        }
+    }
+}
+
+impl Behavior for CuckerSmale {
+    fn name(&self) -> &str {
+        "cucker_smale"
+    }
+
+    fn steer(&self, context: &SteeringContext, index: usize) -> Velocity {
+        let p = &context.points[index];
+        let grid = match context.grid {
+            Some(grid) => grid,
+            None => return p.velocity,
+        };
+        let mut acceleration = Vector2::zero();
+        grid.for_each_neighbour(p.position, self.cutoff, |_, neighbour, offset| {
+            let weight = (1.0 + offset.magnitude2() / (self.scale * self.scale)).powf(-self.beta);
+            acceleration += (neighbour.velocity - p.velocity) * weight;
+        });
+        let count = context.points.len() as f32;
+        p.velocity + acceleration * (self.coupling / count * context.time_step)
+    }
+
+    fn perception_radius(&self) -> Option<f32> {
+        Some(self.cutoff)
+    }
+}
+
+/// A model ready to be applied to a simulator (cf. [crate::points_simulator::PointsSimulator::apply_model])
+#[derive(Clone)]
+pub struct ModelPreset {
+    pub name: String,
+    /// a single species steered by the behaviours only
+    pub species: SpeciesSet,
+    pub behaviors: BehaviorPipeline,
+    /// models are usually studied with far fewer points than the viewer default
+    pub point_count: usize,
+    /// points start in random directions at this speed
+    pub initial_speed: f32,
+}
+
+impl ModelPreset {
+    fn new(
+        name: &str,
+        color: [f32; 3],
+        behavior: impl Behavior + 'static,
+        point_count: usize,
+        initial_speed: f32,
+    ) -> Self {
+        ModelPreset {
+            name: name.into(),
+            species: SpeciesSet::new(name, vec![Species::behaviors_only(name, color)], vec![0.0])
+                .unwrap(),
+            behaviors: BehaviorPipeline::new().with(1.0, behavior),
+            point_count,
+            initial_speed,
+        }
+    }
+
+    pub fn vicsek() -> Self {
+        let vicsek = Vicsek::default();
+        let speed = vicsek.speed;
+        Self::new("Vicsek", [0.4, 0.8, 1.0], vicsek, 20_000, speed)
+    }
+
+    pub fn couzin() -> Self {
+        let couzin = Couzin::default();
+        let speed = couzin.speed;
+        Self::new("Couzin", [1.0, 0.8, 0.3], couzin, 2_000, speed)
+    }
+
+    pub fn cucker_smale() -> Self {
+        Self::new(
+            "Cucker-Smale",
+            [0.6, 1.0, 0.5],
+            CuckerSmale::default(),
+            5_000,
+            5.0,
+        )
+    }
+
+    pub fn presets() -> Vec<ModelPreset> {
+        vec![Self::vicsek(), Self::couzin(), Self::cucker_smale()]
+    }
+}
+
+/// Unit vector in a random direction
+pub(crate) fn random_direction() -> Vector2<f32> {
+    let angle = Range::new(-PI, PI).ind_sample(&mut rand::thread_rng());
+    let (sin, cos) = Rad(angle).sin_cos();
+    Vector2::new(cos, sin)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::analysis::polarization;
+    use crate::point::Point;
+    use crate::points_simulator::PointsSimulator;
+
+    fn run(
+        space_size: (u32, u32),
+        behavior: impl Behavior + 'static,
+        point_count: usize,
+        initial_speed: f32,
+        steps: usize,
+    ) -> PointsSimulator {
+        let mut s = PointsSimulator::new(space_size).unwrap();
+        s.apply_model(&ModelPreset::new(
+            "test",
+            [1.0; 3],
+            behavior,
+            point_count,
+            initial_speed,
+        ));
+        for _ in 0..steps {
+            s.update();
+        }
+        s
+    }
+
+    /// Spread of the velocities around their mean
+    fn velocity_spread(points: &[Point]) -> f32 {
+        let count = points.len() as f32;
+        let mean = points
+            .iter()
+            .fold(Vector2::zero(), |sum, p| sum + p.velocity)
+            / count;
+        (points
+            .iter()
+            .map(|p| (p.velocity - mean).magnitude2())
+            .sum::<f32>()
+            / count)
+            .sqrt()
+    }
+
+    #[test]
+    fn vicsek_should_lose_order_as_noise_grows() {
+        // density of 4 points per r², faster than the canonical speed to order quickly
+        let vicsek = |noise| Vicsek {
+            radius: 10.0,
+            speed: 15.0,
+            noise,
+            ..Default::default()
+        };
+        let ordered = run((100, 100), vicsek(0.5), 400, 15.0, 400);
+        let disordered = run((100, 100), vicsek(5.5), 400, 15.0, 400);
+        assert!(polarization(ordered.points()) > 0.8);
+        assert!(polarization(disordered.points()) < 0.3);
+    }
+
+    #[test]
+    fn couzin_should_go_from_swarm_to_parallel_group() {
+        let couzin = |orientation_width, attraction_width| Couzin {
+            orientation_width,
+            attraction_width,
+            ..Default::default()
+        };
+        let swarm = run((300, 300), couzin(0.0, 140.0), 100, 30.0, 300);
+        let parallel = run((300, 300), couzin(120.0, 20.0), 100, 30.0, 300);
+        assert!(polarization(swarm.points()) < 0.3);
+        // sometimes still two groups about to merge
+        assert!(polarization(parallel.points()) > 0.7);
+    }
+
+    #[test]
+    fn cucker_smale_should_flock_unconditionally_only_for_small_beta() {
+        let cucker_smale = |beta| CuckerSmale {
+            beta,
+            // all-to-all interactions
+            cutoff: 300.0,
+            ..Default::default()
+        };
+        let initial_spread = 5.0;
+        let flock = run((400, 400), cucker_smale(0.25), 200, initial_spread, 100);
+        let scattered = run((400, 400), cucker_smale(3.0), 200, initial_spread, 100);
+        assert!(velocity_spread(flock.points()) < 0.05 * initial_spread);
+        // only close points align: velocities stay spread
+        assert!(velocity_spread(scattered.points()) > 0.2 * initial_spread);
+    }
+}
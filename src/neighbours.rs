@@ -0,0 +1,164 @@
+use crate::point::{Point, Position};
+use cgmath::Vector2;
+
+/// Uniform grid over the periodic simulation space used to find the neighbours of a point:
+/// points are copied sorted by cell so that each cell is a contiguous (cache friendly) range.
+pub struct SpatialGrid {
+    width: f32,
+    height: f32,
+    columns: usize,
+    rows: usize,
+    cell_start: Vec<u32>, // cell c holds points[cell_start[c]..cell_start[c + 1]]
+    indices: Vec<u32>,    // index of the sorted points in the original slice
+    points: Vec<Point>,
+}
+
+impl SpatialGrid {
+    /// `cell_size` is a lower bound: cells are enlarged to tile the space exactly
+    pub fn new(points: &[Point], space_size: (f32, f32), cell_size: f32) -> Self {
+        let (width, height) = space_size;
+        let columns = ((width / cell_size) as usize).max(1);
+        let rows = ((height / cell_size) as usize).max(1);
+        let mut grid = SpatialGrid {
+            width,
+            height,
+            columns,
+            rows,
+            cell_start: vec![0; columns * rows + 1],
+            indices: vec![0; points.len()],
+            points: vec![Point::default(); points.len()],
+        };
+
+        // Counting sort of the points by cell
+        let cells: Vec<usize> = points.iter().map(|p| grid.cell_of(p.position)).collect();
+        for &cell in cells.iter() {
+            grid.cell_start[cell + 1] += 1;
+        }
+        for cell in 0..columns * rows {
+            grid.cell_start[cell + 1] += grid.cell_start[cell];
+        }
+        let mut next = grid.cell_start.clone();
+        for (index, &cell) in cells.iter().enumerate() {
+            grid.indices[next[cell] as usize] = index as u32;
+            grid.points[next[cell] as usize] = points[index];
+            next[cell] += 1;
+        }
+        grid
+    }
+
+    fn cell_coordinates(&self, position: Position) -> (usize, usize) {
+        let column = (position.x / self.width * self.columns as f32).max(0.0) as usize;
+        let row = (position.y / self.height * self.rows as f32).max(0.0) as usize;
+        (column.min(self.columns - 1), row.min(self.rows - 1))
+    }
+
+    fn cell_of(&self, position: Position) -> usize {
+        let (column, row) = self.cell_coordinates(position);
+        row * self.columns + column
+    }
+
+    /// Shortest offset from `from` to `to` in the periodic space
+    pub fn offset(&self, from: Position, to: Position) -> Vector2<f32> {
+        let wrap = |d: f32, size: f32| d - size * (d / size).round();
+        Vector2::new(
+            wrap(to.x - from.x, self.width),
+            wrap(to.y - from.y, self.height),
+        )
+    }
+
+    /// Calls `f(index, neighbour, offset)` for every point (including the one at `position` if any)
+    /// closer than `radius`, `offset` going from `position` to the neighbour
+    pub fn for_each_neighbour<F>(&self, position: Position, radius: f32, mut f: F)
+    where
+        F: FnMut(usize, &Point, Vector2<f32>),
+    {
+        let cell_width = self.width / self.columns as f32;
+        let cell_height = self.height / self.rows as f32;
+        // Number of cells to scan around the point in each direction (never twice the same cell)
+        let span = |cell_size: f32, cells: usize| {
+            ((radius / cell_size).ceil() as usize * 2 + 1).min(cells)
+        };
+        let (span_x, span_y) = (span(cell_width, self.columns), span(cell_height, self.rows));
+
+        let (column, row) = self.cell_coordinates(position);
+        let first_column = column + self.columns - span_x / 2;
+        let first_row = row + self.rows - span_y / 2;
+        // Periodic image of a scanned cell: when only a window of cells is scanned, its shift is
+        // known from its position in the window (cheaper than wrapping each offset)
+        let shift = |first: usize, d: usize, cells: usize, span: usize, size: f32| {
+            if span == cells {
+                None
+            } else if first + d < cells {
+                Some(-size)
+            } else if first + d >= 2 * cells {
+                Some(size)
+            } else {
+                Some(0.0)
+            }
+        };
+        let radius_squared = radius * radius;
+        for dy in 0..span_y {
+            let cell_row = (first_row + dy) % self.rows;
+            let shift_y = shift(first_row, dy, self.rows, span_y, self.height);
+            for dx in 0..span_x {
+                let cell = cell_row * self.columns + (first_column + dx) % self.columns;
+                let shift_x = shift(first_column, dx, self.columns, span_x, self.width);
+                let range = self.cell_start[cell] as usize..self.cell_start[cell + 1] as usize;
+                for (&index, neighbour) in
+                    self.indices[range.clone()].iter().zip(&self.points[range])
+                {
+                    let offset = match (shift_x, shift_y) {
+                        (Some(shift_x), Some(shift_y)) => Vector2::new(
+                            neighbour.position.x + shift_x - position.x,
+                            neighbour.position.y + shift_y - position.y,
+                        ),
+                        _ => self.offset(position, neighbour.position),
+                    };
+                    if offset.x * offset.x + offset.y * offset.y < radius_squared {
+                        f(index as usize, neighbour, offset);
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::point::Velocity;
+    use rand::Rng;
+
+    #[test]
+    fn grid_should_find_same_neighbours_as_brute_force() {
+        let space_size = (100.0, 60.0);
+        let mut rng = rand::thread_rng();
+        let points: Vec<Point> = (0..500)
+            .map(|_| Point {
+                position: Position::new(
+                    rng.gen::<f32>() * space_size.0,
+                    rng.gen::<f32>() * space_size.1,
+                ),
+                velocity: Velocity::new(0.0, 0.0),
+                species: 0,
+            })
+            .collect();
+        let grid = SpatialGrid::new(&points, space_size, 7.0);
+
+        for radius in [3.0, 7.0, 12.0, 40.0].iter() {
+            for p in points.iter().take(50) {
+                let mut found = Vec::new();
+                grid.for_each_neighbour(p.position, *radius, |index, _, _| found.push(index));
+                found.sort_unstable();
+
+                let expected: Vec<usize> = (0..points.len())
+                    .filter(|&index| {
+                        let offset = grid.offset(p.position, points[index].position);
+                        offset.x * offset.x + offset.y * offset.y < radius * radius
+                    })
+                    .collect();
+                assert_eq!(found, expected);
+            }
+        }
+    }
+}
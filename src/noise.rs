@@ -0,0 +1,93 @@
+use crate::utils::hash_u32;
+
+/// Edges of a cube: gradients of the 3D noise (Perlin's improved noise)
+const GRADIENTS: [[f32; 3]; 12] = [
+    [1.0, 1.0, 0.0],
+    [-1.0, 1.0, 0.0],
+    [1.0, -1.0, 0.0],
+    [-1.0, -1.0, 0.0],
+    [1.0, 0.0, 1.0],
+    [-1.0, 0.0, 1.0],
+    [1.0, 0.0, -1.0],
+    [-1.0, 0.0, -1.0],
+    [0.0, 1.0, 1.0],
+    [0.0, -1.0, 1.0],
+    [0.0, 1.0, -1.0],
+    [0.0, -1.0, -1.0],
+];
+
+/// Quintic smoothstep: continuous first and second derivatives at lattice points
+fn fade(t: f32) -> f32 {
+    t * t * t * (t * (t * 6.0 - 15.0) + 10.0)
+}
+
+fn lerp(a: f32, b: f32, t: f32) -> f32 {
+    a + (b - a) * t
+}
+
+fn lattice_hash(seed: u32, coordinates: &[i32]) -> u32 {
+    coordinates
+        .iter()
+        .fold(hash_u32(seed), |hash, &c| hash_u32(hash ^ c as u32))
+}
+
+/// 1D gradient noise in about [-1, 1], smooth at the scale of 1 (0 on integers)
+pub fn perlin1(seed: u32, t: f32) -> f32 {
+    let base = t.floor();
+    let f = t - base;
+    let gradient = |i: i32| {
+        let hash = lattice_hash(seed, &[i]);
+        (hash >> 8) as f32 / 8_388_608.0 - 1.0
+    };
+    let i = base as i32;
+    // a gradient of 1 reaches at most 0.5 between two lattice points
+    2.0 * lerp(gradient(i) * f, gradient(i + 1) * (f - 1.0), fade(f))
+}
+
+/// 3D gradient (Perlin) noise in about [-1, 1], smooth at the scale of 1
+pub fn perlin3(seed: u32, x: f32, y: f32, z: f32) -> f32 {
+    let (bx, by, bz) = (x.floor(), y.floor(), z.floor());
+    let (fx, fy, fz) = (x - bx, y - by, z - bz);
+    let (ix, iy, iz) = (bx as i32, by as i32, bz as i32);
+    let corner = |dx: i32, dy: i32, dz: i32| {
+        let g = GRADIENTS[(lattice_hash(seed, &[ix + dx, iy + dy, iz + dz]) % 12) as usize];
+        g[0] * (fx - dx as f32) + g[1] * (fy - dy as f32) + g[2] * (fz - dz as f32)
+    };
+    let (u, v, w) = (fade(fx), fade(fy), fade(fz));
+    lerp(
+        lerp(
+            lerp(corner(0, 0, 0), corner(1, 0, 0), u),
+            lerp(corner(0, 1, 0), corner(1, 1, 0), u),
+            v,
+        ),
+        lerp(
+            lerp(corner(0, 0, 1), corner(1, 0, 1), u),
+            lerp(corner(0, 1, 1), corner(1, 1, 1), u),
+            v,
+        ),
+        w,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn noise_should_be_bounded_and_continuous() {
+        let step = 1e-3;
+        for i in 0..5000 {
+            let t = i as f32 * 0.0137 - 30.0;
+            let (a, b) = (perlin1(7, t), perlin1(7, t + step));
+            assert!(a.abs() <= 1.0);
+            assert!((a - b).abs() < 0.01);
+
+            let (x, y, z) = (t, 0.37 * t + 3.0, -0.61 * t);
+            let (a, b) = (perlin3(7, x, y, z), perlin3(7, x + step, y, z + step));
+            assert!(a.abs() <= 1.0);
+            assert!((a - b).abs() < 0.01);
+        }
+        // different seeds give different noises
+        assert_ne!(perlin3(1, 0.5, 0.5, 0.5), perlin3(2, 0.5, 0.5, 0.5));
+    }
+}
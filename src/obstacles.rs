@@ -0,0 +1,614 @@
+use crate::point::{Position, Velocity};
+use crate::utils::{calculate_relative_brightness, load_image};
+use anyhow::{ensure, Context, Result};
+use cgmath::{InnerSpace, MetricSpace, Vector2, Zero};
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+
+// Look-ahead avoidance: probes are placed along the velocity up to this distance
+const LOOK_AHEAD_DISTANCE: f32 = 20.0;
+const LOOK_AHEAD_PROBES: usize = 3;
+const AVOIDANCE_STRENGTH: f32 = 10.0;
+
+/// Obstacle shape in simulation space coordinates
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Shape {
+    Circle {
+        center: Position,
+        radius: f32,
+    },
+    /// simple polygon (convex or not), vertices in order
+    Polygon {
+        vertices: Vec<Position>,
+    },
+}
+
+/// Image whose dark pixels are obstacles (stretched over the simulation space)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MaskSource {
+    pub path: PathBuf,
+    /// pixels darker than this relative brightness are solid
+    pub threshold: f32,
+}
+
+/// Serialisable description of the obstacles (cf. [Obstacles::new])
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ObstaclesConfig {
+    #[serde(default)]
+    pub shapes: Vec<Shape>,
+    #[serde(default)]
+    pub mask: Option<MaskSource>,
+}
+
+impl ObstaclesConfig {
+    /// Checks the shapes (cf. [Shape::check])
+    pub fn check(&self) -> Result<()> {
+        for (index, shape) in self.shapes.iter().enumerate() {
+            shape
+                .check()
+                .with_context(|| format!("Invalid obstacle {}", index))?;
+        }
+        Ok(())
+    }
+
+    /// Same obstacles in a simulation space scaled by `scale`
+    /// (the mask is always stretched over the whole space)
+    pub fn scaled(&self, scale: (f32, f32)) -> ObstaclesConfig {
+        ObstaclesConfig {
+            shapes: self.shapes.iter().map(|s| s.scaled(scale)).collect(),
+            mask: self.mask.clone(),
+        }
+    }
+}
+
+struct ObstacleMask {
+    width: usize,
+    height: usize,
+    solid: Vec<bool>,                 // row-major
+    scale: (f32, f32),                // pixels per simulation space unit
+    rects: Vec<(f32, f32, f32, f32)>, // cf. ObstacleMask::runs
+}
+
+/// Static obstacles: boids steer away from them and never enter them
+pub struct Obstacles {
+    config: ObstaclesConfig,
+    mask: Option<ObstacleMask>,
+}
+
+impl Shape {
+    /// Checks what deserialization does not: a positive radius, at least 3 polygon vertices
+    pub fn check(&self) -> Result<()> {
+        match self {
+            Shape::Circle { radius, .. } => {
+                ensure!(
+                    radius.is_finite() && *radius > 0.0,
+                    "Circle radius must be positive: {}",
+                    radius
+                );
+            }
+            Shape::Polygon { vertices } => {
+                ensure!(
+                    vertices.len() >= 3,
+                    "Polygon needs at least 3 vertices: {} given",
+                    vertices.len()
+                );
+            }
+        }
+        Ok(())
+    }
+
+    pub fn contains(&self, p: Position) -> bool {
+        match self {
+            Shape::Circle { center, radius } => p.distance2(*center) < radius * radius,
+            Shape::Polygon { vertices } => {
+                // even-odd rule
+                let mut inside = false;
+                for (a, b) in edges(vertices) {
+                    if (a.y > p.y) != (b.y > p.y)
+                        && p.x < a.x + (p.y - a.y) / (b.y - a.y) * (b.x - a.x)
+                    {
+                        inside = !inside;
+                    }
+                }
+                inside
+            }
+        }
+    }
+
+    /// Direction toward the closest boundary (outward for a point inside)
+    fn escape_direction(&self, p: Position) -> Vector2<f32> {
+        let direction = match self {
+            Shape::Circle { center, .. } => p - *center,
+            Shape::Polygon { vertices } => {
+                let closest = edges(vertices)
+                    .map(|(a, b)| closest_on_segment(p, a, b))
+                    .min_by(|u, v| p.distance2(*u).partial_cmp(&p.distance2(*v)).unwrap())
+                    .unwrap_or(p);
+                closest - p
+            }
+        };
+        normalize_or_zero(direction)
+    }
+
+    /// First crossing of the boundary by the segment from `from` (outside) to `to`:
+    /// fraction of the segment and outward normal there
+    fn first_hit(&self, from: Position, to: Position) -> Option<(f32, Vector2<f32>)> {
+        let d = to - from;
+        match self {
+            Shape::Circle { center, radius } => {
+                // |from + t d - center| = radius
+                let offset = from - *center;
+                let a = d.magnitude2();
+                let b = offset.dot(d);
+                let c = offset.magnitude2() - radius * radius;
+                let discriminant = b * b - a * c;
+                if a == 0.0 || discriminant < 0.0 {
+                    return None;
+                }
+                let t = (-b - discriminant.sqrt()) / a;
+                (0.0..=1.0)
+                    .contains(&t)
+                    .then(|| (t, normalize_or_zero(offset + d * t)))
+            }
+            Shape::Polygon { vertices } => edges(vertices)
+                .filter_map(|(a, b)| {
+                    let edge = b - a;
+                    let denominator = d.perp_dot(edge);
+                    if denominator == 0.0 {
+                        return None;
+                    }
+                    let t = (a - from).perp_dot(edge) / denominator;
+                    let u = (a - from).perp_dot(d) / denominator;
+                    if !(0.0..=1.0).contains(&t) || !(0.0..=1.0).contains(&u) {
+                        return None;
+                    }
+                    let normal = normalize_or_zero(Vector2::new(edge.y, -edge.x));
+                    Some((t, if normal.dot(d) > 0.0 { -normal } else { normal }))
+                })
+                .min_by(|u, v| u.0.partial_cmp(&v.0).unwrap()),
+        }
+    }
+
+    /// Same shape in a simulation space scaled by `scale`
+    pub fn scaled(&self, scale: (f32, f32)) -> Shape {
+        let scale_point = |p: &Position| Position::new(p.x * scale.0, p.y * scale.1);
+        match self {
+            Shape::Circle { center, radius } => Shape::Circle {
+                center: scale_point(center),
+                radius: radius * (scale.0 * scale.1).sqrt(),
+            },
+            Shape::Polygon { vertices } => Shape::Polygon {
+                vertices: vertices.iter().map(scale_point).collect(),
+            },
+        }
+    }
+}
+
+fn edges(vertices: &[Position]) -> impl Iterator<Item = (Position, Position)> + '_ {
+    vertices
+        .iter()
+        .zip(vertices.iter().cycle().skip(1))
+        .map(|(a, b)| (*a, *b))
+}
+
+fn closest_on_segment(p: Position, a: Position, b: Position) -> Position {
+    let ab = b - a;
+    let length2 = ab.magnitude2();
+    if length2 == 0.0 {
+        return a;
+    }
+    let t = ((p - a).dot(ab) / length2).clamp(0.0, 1.0);
+    a + ab * t
+}
+
+fn normalize_or_zero(v: Vector2<f32>) -> Vector2<f32> {
+    if v.magnitude2() > 0.0 {
+        v.normalize()
+    } else {
+        Vector2::zero()
+    }
+}
+
+impl ObstacleMask {
+    fn load(source: &MaskSource, space_size: (f32, f32)) -> Result<Self> {
+        let img = load_image(&source.path)?;
+        let (width, height) = img.dimensions();
+        let solid = img
+            .pixels()
+            .map(|pixel| {
+                let v = pixel.0;
+                calculate_relative_brightness(v[0], v[1], v[2]) < source.threshold
+            })
+            .collect();
+        let mut mask = ObstacleMask {
+            width: width as usize,
+            height: height as usize,
+            solid,
+            scale: (width as f32 / space_size.0, height as f32 / space_size.1),
+            rects: Vec::new(),
+        };
+        mask.rects = mask.runs();
+        Ok(mask)
+    }
+
+    fn is_solid(&self, x: isize, y: isize) -> bool {
+        x >= 0
+            && y >= 0
+            && (x as usize) < self.width
+            && (y as usize) < self.height
+            && self.solid[y as usize * self.width + x as usize]
+    }
+
+    fn pixel_of(&self, p: Position) -> (isize, isize) {
+        (
+            (p.x * self.scale.0).floor() as isize,
+            (p.y * self.scale.1).floor() as isize,
+        )
+    }
+
+    fn contains(&self, p: Position) -> bool {
+        let (x, y) = self.pixel_of(p);
+        self.is_solid(x, y)
+    }
+
+    /// Away from the solid pixels around `p`
+    fn escape_direction(&self, p: Position) -> Vector2<f32> {
+        const RADIUS: isize = 3;
+        let (x, y) = self.pixel_of(p);
+        let mut direction = Vector2::zero();
+        for dy in -RADIUS..=RADIUS {
+            for dx in -RADIUS..=RADIUS {
+                if self.is_solid(x + dx, y + dy) {
+                    direction -= Vector2::new(dx as f32, dy as f32);
+                }
+            }
+        }
+        normalize_or_zero(direction)
+    }
+
+    /// First solid pixel crossed by the segment from `from` (free) to `to`, walking the pixels
+    /// along the segment: fraction of the segment and outward normal of the pixel side crossed
+    fn first_hit(&self, from: Position, to: Position) -> Option<(f32, Vector2<f32>)> {
+        let (sx, sy) = self.scale;
+        let start = (from.x * sx, from.y * sy);
+        let d = ((to.x - from.x) * sx, (to.y - from.y) * sy);
+        let (mut x, mut y) = self.pixel_of(from);
+        let end = self.pixel_of(to);
+        // per axis: pixel step, fraction of the segment at the next pixel side and between sides
+        let axis = |start: f32, d: f32, pixel: isize| {
+            if d == 0.0 {
+                (0, f32::INFINITY, f32::INFINITY)
+            } else if d > 0.0 {
+                (1, ((pixel + 1) as f32 - start) / d, 1.0 / d)
+            } else {
+                (-1, (pixel as f32 - start) / d, -1.0 / d)
+            }
+        };
+        let (step_x, mut next_x, delta_x) = axis(start.0, d.0, x);
+        let (step_y, mut next_y, delta_y) = axis(start.1, d.1, y);
+        while (x, y) != end {
+            let (t, normal) = if next_x < next_y {
+                x += step_x;
+                next_x += delta_x;
+                (next_x - delta_x, Vector2::new(-step_x as f32, 0.0))
+            } else {
+                y += step_y;
+                next_y += delta_y;
+                (next_y - delta_y, Vector2::new(0.0, -step_y as f32))
+            };
+            if t > 1.0 {
+                break; // rounding
+            }
+            if self.is_solid(x, y) {
+                return Some((t, normal));
+            }
+        }
+        None
+    }
+
+    /// Horizontal runs of solid pixels as rectangles (x, y, width, height) in simulation space
+    fn runs(&self) -> Vec<(f32, f32, f32, f32)> {
+        let mut runs = Vec::new();
+        let (sx, sy) = self.scale;
+        for y in 0..self.height {
+            let row = &self.solid[y * self.width..(y + 1) * self.width];
+            let mut x = 0;
+            while x < self.width {
+                if row[x] {
+                    let start = x;
+                    while x < self.width && row[x] {
+                        x += 1;
+                    }
+                    runs.push((
+                        start as f32 / sx,
+                        y as f32 / sy,
+                        (x - start) as f32 / sx,
+                        1.0 / sy,
+                    ));
+                } else {
+                    x += 1;
+                }
+            }
+        }
+        runs
+    }
+}
+
+impl Obstacles {
+    pub fn empty() -> Self {
+        Obstacles {
+            config: ObstaclesConfig::default(),
+            mask: None,
+        }
+    }
+
+    /// Loads the mask image (if any) scaled to the simulation space
+    pub fn new(config: ObstaclesConfig, space_size: (f32, f32)) -> Result<Self> {
+        let mask = match &config.mask {
+            Some(source) => Some(ObstacleMask::load(source, space_size)?),
+            None => None,
+        };
+        Ok(Obstacles { config, mask })
+    }
+
+    pub fn config(&self) -> &ObstaclesConfig {
+        &self.config
+    }
+
+    pub fn shapes(&self) -> &[Shape] {
+        &self.config.shapes
+    }
+
+    /// Solid pixels of the mask as rectangles (x, y, width, height) in simulation space
+    pub fn mask_rects(&self) -> &[(f32, f32, f32, f32)] {
+        match &self.mask {
+            Some(mask) => &mask.rects,
+            None => &[],
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.config.shapes.is_empty() && self.mask.is_none()
+    }
+
+    pub fn contains(&self, p: Position) -> bool {
+        self.config.shapes.iter().any(|shape| shape.contains(p))
+            || self.mask.as_ref().is_some_and(|mask| mask.contains(p))
+    }
+
+    /// Direction out of the obstacle containing `p` (zero if `p` is free)
+    fn escape_direction(&self, p: Position) -> Vector2<f32> {
+        if let Some(shape) = self.config.shapes.iter().find(|shape| shape.contains(p)) {
+            return shape.escape_direction(p);
+        }
+        match &self.mask {
+            Some(mask) if mask.contains(p) => mask.escape_direction(p),
+            _ => Vector2::zero(),
+        }
+    }
+
+    /// First obstacle crossed by the segment from `from` (free) to `to`: fraction of the segment
+    /// and outward normal there
+    fn first_hit(&self, from: Position, to: Position) -> Option<(f32, Vector2<f32>)> {
+        let hit = self
+            .config
+            .shapes
+            .iter()
+            .filter_map(|shape| shape.first_hit(from, to))
+            .chain(self.mask.as_ref().and_then(|mask| mask.first_hit(from, to)))
+            .min_by(|u, v| u.0.partial_cmp(&v.0).unwrap());
+        // a boundary grazed (rounding) while the segment ends inside
+        hit.or_else(|| self.contains(to).then(|| (1.0, self.escape_direction(to))))
+    }
+
+    /// Look-ahead avoidance: steering away from the first obstacle met along `velocity`,
+    /// stronger when the obstacle is close
+    pub fn avoidance(&self, position: Position, velocity: Velocity) -> Vector2<f32> {
+        let heading = normalize_or_zero(velocity);
+        if heading == Vector2::zero() {
+            return Vector2::zero();
+        }
+        for probe in 1..=LOOK_AHEAD_PROBES {
+            let fraction = probe as f32 / LOOK_AHEAD_PROBES as f32;
+            let ahead = position + heading * (LOOK_AHEAD_DISTANCE * fraction);
+            if self.contains(ahead) {
+                let away = self.escape_direction(ahead);
+                // steer sideways when the obstacle is hit head-on
+                let away = if away.dot(heading) < -0.99 {
+                    Vector2::new(-heading.y, heading.x)
+                } else {
+                    away
+                };
+                return away * (AVOIDANCE_STRENGTH * (1.0 + 1.0 / fraction));
+            }
+        }
+        Vector2::zero()
+    }
+
+    /// Moves a point from `from` to `to` without entering obstacles (the whole segment is tested,
+    /// so that fast points do not jump through thin ones): the move is cancelled and the velocity
+    /// loses its inward component on collision
+    pub fn resolve_move(&self, from: Position, to: Position, velocity: &mut Velocity) -> Position {
+        if self.contains(from) {
+            if !self.contains(to) {
+                return to;
+            }
+            // already stuck inside (e.g. obstacles added over points): move out
+            let outward = self.escape_direction(from);
+            remove_inward_speed(velocity, outward);
+            return from + outward * (to - from).magnitude().max(1.0);
+        }
+        match self.first_hit(from, to) {
+            Some((_, outward)) => {
+                remove_inward_speed(velocity, outward);
+                from
+            }
+            None => to,
+        }
+    }
+}
+
+fn remove_inward_speed(velocity: &mut Velocity, outward: Vector2<f32>) {
+    let inward_speed = velocity.dot(outward);
+    if inward_speed < 0.0 {
+        *velocity -= outward * inward_speed;
+    }
+}
+
+/// Triangles covering a simple polygon (ear clipping), to draw it
+pub fn triangulate(vertices: &[Position]) -> Vec<[Position; 3]> {
+    let mut triangles = Vec::new();
+    let mut remaining: Vec<Position> = vertices.to_vec();
+    // orientation of the polygon (counter-clockwise if positive)
+    let area: f32 = edges(vertices).map(|(a, b)| a.x * b.y - b.x * a.y).sum();
+    let convex = |a: Position, b: Position, c: Position| {
+        let cross = (b - a).perp_dot(c - b);
+        cross * area > 0.0
+    };
+
+    while remaining.len() > 3 {
+        let n = remaining.len();
+        let ear = (0..n).find(|&i| {
+            let (a, b, c) = (
+                remaining[(i + n - 1) % n],
+                remaining[i],
+                remaining[(i + 1) % n],
+            );
+            convex(a, b, c)
+                && !remaining
+                    .iter()
+                    .any(|&p| p != a && p != b && p != c && inside_triangle(p, a, b, c))
+        });
+        match ear {
+            Some(i) => {
+                triangles.push([
+                    remaining[(i + n - 1) % n],
+                    remaining[i],
+                    remaining[(i + 1) % n],
+                ]);
+                remaining.remove(i);
+            }
+            None => break, // degenerate polygon: draw what has been found
+        }
+    }
+    if remaining.len() == 3 {
+        triangles.push([remaining[0], remaining[1], remaining[2]]);
+    }
+    triangles
+}
+
+fn inside_triangle(p: Position, a: Position, b: Position, c: Position) -> bool {
+    let d1 = (b - a).perp_dot(p - a);
+    let d2 = (c - b).perp_dot(p - b);
+    let d3 = (a - c).perp_dot(p - c);
+    let has_negative = d1 < 0.0 || d2 < 0.0 || d3 < 0.0;
+    let has_positive = d1 > 0.0 || d2 > 0.0 || d3 > 0.0;
+    !(has_negative && has_positive)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn l_shape() -> Shape {
+        let v = |x, y| Position::new(x, y);
+        Shape::Polygon {
+            vertices: vec![
+                v(0., 0.),
+                v(20., 0.),
+                v(20., 10.),
+                v(10., 10.),
+                v(10., 20.),
+                v(0., 20.),
+            ],
+        }
+    }
+
+    #[test]
+    fn moves_into_obstacles_should_be_cancelled() {
+        let obstacles = Obstacles::new(
+            ObstaclesConfig {
+                shapes: vec![
+                    Shape::Circle {
+                        center: Position::new(50.0, 50.0),
+                        radius: 10.0,
+                    },
+                    l_shape(),
+                ],
+                mask: None,
+            },
+            (100.0, 100.0),
+        )
+        .unwrap();
+
+        let mut velocity = Velocity::new(5.0, 0.0);
+        let from = Position::new(38.0, 50.0);
+        let to = obstacles.resolve_move(from, Position::new(43.0, 50.0), &mut velocity);
+        assert_eq!(to, from);
+        assert!(velocity.x.abs() < 1e-6);
+
+        // inside the notch of the L shape is free
+        assert!(!obstacles.contains(Position::new(15.0, 15.0)));
+        assert!(obstacles.contains(Position::new(5.0, 15.0)));
+    }
+
+    #[test]
+    fn fast_moves_should_not_jump_through_thin_obstacles() {
+        let v = |x, y| Position::new(x, y);
+        let wall = Shape::Polygon {
+            vertices: vec![v(40.0, 0.0), v(40.5, 0.0), v(40.5, 100.0), v(40.0, 100.0)],
+        };
+        let mut solid = vec![false; 100];
+        solid[50..60].iter_mut().for_each(|pixel| *pixel = true); // 6th row
+        let obstacles = Obstacles {
+            config: ObstaclesConfig {
+                shapes: vec![wall],
+                mask: None,
+            },
+            mask: Some(ObstacleMask {
+                width: 10,
+                height: 10,
+                solid,
+                scale: (0.1, 0.1),
+                rects: Vec::new(),
+            }),
+        };
+
+        // both ends free, across the wall
+        let mut velocity = Velocity::new(50.0, 10.0);
+        let from = v(20.0, 20.0);
+        assert_eq!(
+            obstacles.resolve_move(from, v(70.0, 30.0), &mut velocity),
+            from
+        );
+        assert!(velocity.x.abs() < 1e-6 && velocity.y == 10.0);
+
+        // across the one pixel row of the mask (y in [50, 60[)
+        let mut velocity = Velocity::new(5.0, 30.0);
+        let from = v(70.0, 35.0);
+        assert_eq!(
+            obstacles.resolve_move(from, v(75.0, 65.0), &mut velocity),
+            from
+        );
+        assert!(velocity.y.abs() < 1e-6 && velocity.x == 5.0);
+
+        // free moves are kept
+        let mut velocity = Velocity::new(5.0, 5.0);
+        let to = v(75.0, 40.0);
+        assert_eq!(obstacles.resolve_move(v(70.0, 35.0), to, &mut velocity), to);
+    }
+
+    #[test]
+    fn triangulation_should_cover_concave_polygon_area() {
+        if let Shape::Polygon { vertices } = l_shape() {
+            let triangles = triangulate(&vertices);
+            let area: f32 = triangles
+                .iter()
+                .map(|[a, b, c]| (b - a).perp_dot(c - a).abs() / 2.0)
+                .sum();
+            assert_eq!(triangles.len(), 4);
+            assert!((area - 300.0).abs() < 1e-3);
+        }
+    }
+}
@@ -0,0 +1,23 @@
+use cgmath::{Point2, Vector2};
+
+pub type Position = Point2<f32>;
+pub type Velocity = Vector2<f32>;
+
+/// A simulated point, laid out as the vertex attributes of the points shaders
+#[repr(C)]
+#[derive(Copy, Clone)]
+pub struct Point {
+    pub position: Position,
+    pub velocity: Velocity,
+    pub species: u32,
+}
+
+impl Default for Point {
+    fn default() -> Self {
+        Point {
+            position: Position { x: 0.0, y: 0.0 },
+            velocity: Velocity { x: 0.0, y: 0.0 },
+            species: 0,
+        }
+    }
+}
@@ -1,18 +1,129 @@
-use crate::glx::WindowSizeInfo;
-use crate::shader_programs::points::{Point, Position, Velocity};
-use crate::utils::calculate_relative_brightness;
-use anyhow::{Context, Result};
-use cgmath::{Basis2, Rad, Rotation, Rotation2, Vector2};
-use image::io::Reader as ImageReader;
+use crate::behavior::{random_walk_velocity, BehaviorPipeline, SteeringContext};
+use crate::color::ColorConversion;
+use crate::field::{
+    FieldEffects, ImageScalarField, ScalarField, BRIGHTNESS_SPEED_GAIN, BRIGHTNESS_SPEED_OFFSET,
+};
+use crate::image_sequence::{FramePosition, ImageSequence};
+use crate::lifecycle::{Lifecycle, LifecycleConfig, SpawnContext};
+use crate::models::{random_direction, ModelPreset};
+use crate::neighbours::SpatialGrid;
+use crate::obstacles::Obstacles;
+use crate::parallel::*;
+use crate::point::{Point, Position, Velocity};
+use crate::predators::{Predator, PredatorParams, PursuitTarget};
+use crate::scenario::Scenario;
+use crate::soa::PointsSoA;
+use crate::species::{NeighbourForces, SpeciesSet};
+use crate::utils::{hash_u32, hashed_random};
+use anyhow::Result;
+use cgmath::{InnerSpace, MetricSpace, Vector2, Zero};
 use image::RgbImage;
-use rand::distributions::{IndependentSample, Range};
 use rand::Rng;
-use rayon::prelude::*;
+use std::sync::Arc;
+
+// Simulation time elapsed at each update step
+pub const TIME_STEP: f32 = 0.2;
+
+pub const DEFAULT_BACKGROUND: &str = "./assets/pugs.jpg";
+
+// Brightness of the uniform background used when images cannot be decoded
+#[cfg(not(feature = "images"))]
+const UNIFORM_BACKGROUND: image::Rgb<u8> = image::Rgb([128, 128, 128]);
+
+/// [DEFAULT_BACKGROUND] image
+#[cfg(feature = "images")]
+fn default_background(_space_size: (u32, u32)) -> Result<RgbImage> {
+    use anyhow::Context;
+    crate::utils::load_image(DEFAULT_BACKGROUND).context("Failed to load background image")
+}
+
+/// Uniform background of the space size (built without the `images` feature)
+#[cfg(not(feature = "images"))]
+fn default_background(space_size: (u32, u32)) -> Result<RgbImage> {
+    Ok(RgbImage::from_pixel(
+        space_size.0.max(1),
+        space_size.1.max(1),
+        UNIFORM_BACKGROUND,
+    ))
+}
+
+// Random positions drawn to find a free place for a point (obstacles)
+const MAX_PLACEMENT_ATTEMPTS: usize = 100;
 
 pub struct PointsSimulator {
-    pub points: Vec<Point>,
-    img: RgbImage,
+    points: Vec<Point>,
+    // structure of arrays copy of the points used by the random walk kernels
+    // (None when the points changed since)
+    soa: Option<PointsSoA>,
+    sequence: ImageSequence,
+    frame: FramePosition,
+    img: RgbImage, // current background image (cf. [ImageSequence::image])
+    background_generation: u64,
+    conversion: ColorConversion,
+    background: Arc<ImageScalarField>, // background image converted to scalars
+    fields: FieldEffects,
+    field_preset: usize,
     space_size: SimulationSpace,
+    species: SpeciesSet,
+    predators: Vec<Predator>,
+    predator_params: PredatorParams,
+    obstacles: Obstacles,
+    lifecycle: Lifecycle,
+    behaviors: BehaviorPipeline,
+    step: u64,
+}
+
+/// Neighbour rules of the point `index`: flocking with its own species,
+/// separation from everyone and attraction/avoidance given by the interaction matrix
+fn neighbour_forces(
+    grid: &SpatialGrid,
+    species: &SpeciesSet,
+    points: &[Point],
+    index: usize,
+) -> NeighbourForces {
+    let point = &points[index];
+    let own = species.get(point.species);
+    let mut forces = NeighbourForces::default();
+    if !own.weights.uses_neighbours() {
+        return forces;
+    }
+
+    let mut mean_velocity = Velocity::zero();
+    let mut mean_offset = Vector2::zero();
+    let (mut flockmates, mut neighbours) = (0, 0);
+    grid.for_each_neighbour(
+        point.position,
+        own.perception_radius,
+        |other, neighbour, offset| {
+            if other == index {
+                return;
+            }
+            let distance = offset.magnitude();
+            neighbours += 1;
+            if neighbour.species == point.species {
+                flockmates += 1;
+                mean_velocity += neighbour.velocity;
+                mean_offset += offset;
+            }
+            if distance > 0.0 {
+                let direction = offset / distance;
+                if distance < own.separation_radius {
+                    forces.separation -= direction * (1.0 - distance / own.separation_radius);
+                }
+                forces.interaction +=
+                    direction * species.interaction(point.species, neighbour.species);
+            }
+        },
+    );
+
+    if flockmates > 0 {
+        forces.alignment = mean_velocity / flockmates as f32 - point.velocity;
+        forces.cohesion = mean_offset / flockmates as f32;
+    }
+    if neighbours > 0 {
+        forces.interaction /= neighbours as f32;
+    }
+    forces
 }
 
 #[derive(Copy, Clone)]
@@ -22,60 +133,482 @@ struct SimulationSpace {
 }
 
 impl PointsSimulator {
-    pub fn new(window_info: WindowSizeInfo) -> Result<Self> {
-        // let img = ImageReader::open("./assets/rustacean-happy.png")
-        let img = ImageReader::open("./assets/pugs.jpg")
-            .context("Failed to open background image")?
-            .decode()
-            .context("Failed to decode background image")?;
-        let img = img.to_rgb8();
+    /// Simulator of a `space_size` space (in pixels of the viewer window)
+    pub fn new(space_size: (u32, u32)) -> Result<Self> {
+        let img = default_background(space_size)?;
+        let sequence = ImageSequence::still(img.clone());
         let space_size = SimulationSpace {
-            width: window_info.width as f32,
-            height: window_info.height as f32,
+            width: space_size.0 as f32,
+            height: space_size.1 as f32,
         };
 
-        let mut points = Vec::<Point>::with_capacity(200_000);
-        points.resize(points.capacity(), Point::default());
-        Self::internal_init_points(space_size, &mut points);
+        let conversion = ColorConversion::default();
+        let background = Arc::new(ImageScalarField::from_image(
+            &img,
+            (space_size.width, space_size.height),
+            &conversion,
+        ));
+        let fields = FieldEffects::brightness_speed(background.clone());
+
+        let species = SpeciesSet::single();
+        let mut points = Vec::<Point>::new();
+        let mut lifecycle = Lifecycle::new(LifecycleConfig::default());
+        lifecycle.reset(&mut points);
+        let obstacles = Obstacles::empty();
+        Self::internal_init_points(space_size, &species, &obstacles, &mut points);
 
         Ok(PointsSimulator {
             points,
+            soa: None,
+            frame: sequence.position(0.0),
+            sequence,
             img,
+            background_generation: 0,
+            conversion,
+            background,
+            fields,
+            field_preset: 0,
             space_size,
+            species,
+            predators: Vec::new(),
+            predator_params: PredatorParams::default(),
+            obstacles,
+            lifecycle,
+            behaviors: BehaviorPipeline::random_walk(),
+            step: 0,
         })
     }
 
+    pub fn points(&self) -> &[Point] {
+        &self.points
+    }
+
+    /// Replaces the points (e.g. by the ones computed on the GPU)
+    pub fn set_points(&mut self, points: Vec<Point>) {
+        self.points = points;
+        self.soa = None;
+    }
+
     pub fn init_points(&mut self) {
-        Self::internal_init_points(self.space_size, &mut self.points);
+        self.soa = None;
+        self.lifecycle.reset(&mut self.points);
+        Self::internal_init_points(
+            self.space_size,
+            &self.species,
+            &self.obstacles,
+            &mut self.points,
+        );
+        self.step = 0;
+        self.animate_background();
+    }
+
+    /// Grows or shrinks the population at runtime: existing points are kept
+    /// and new ones are placed as done at initialization
+    pub fn set_point_count(&mut self, count: usize) {
+        self.soa = None;
+        let first = self.lifecycle.resize(&mut self.points, count);
+        Self::internal_init_points(
+            self.space_size,
+            &self.species,
+            &self.obstacles,
+            &mut self.points[first..],
+        );
+    }
+
+    pub fn obstacles(&self) -> &Obstacles {
+        &self.obstacles
+    }
+
+    pub fn lifecycle(&self) -> &LifecycleConfig {
+        self.lifecycle.config()
+    }
+
+    /// Current configuration (species, predators, obstacles) as a scenario
+    pub fn scenario(&self) -> Scenario {
+        Scenario {
+            space_size: self.space_size(),
+            species: self.species.clone(),
+            predators: self.predators.clone(),
+            predator_params: self.predator_params,
+            obstacles: self.obstacles.config().clone(),
+            lifecycle: self.lifecycle.config().clone(),
+        }
+    }
+
+    /// Replaces the configuration by the scenario one (rescaled to this simulation space)
+    /// and reinitializes the points
+    pub fn apply_scenario(&mut self, scenario: Scenario) -> Result<()> {
+        scenario.check()?;
+        let (width, height) = self.space_size();
+        let scale = (
+            width / scenario.space_size.0,
+            height / scenario.space_size.1,
+        );
+        self.obstacles = Obstacles::new(scenario.obstacles.scaled(scale), (width, height))?;
+        self.predators = scenario
+            .predators
+            .iter()
+            .map(|predator| Predator {
+                position: Position::new(
+                    predator.position.x * scale.0,
+                    predator.position.y * scale.1,
+                ),
+                velocity: predator.velocity,
+            })
+            .collect();
+        self.predator_params = scenario.predator_params;
+        self.lifecycle = Lifecycle::new(scenario.lifecycle.scaled(scale));
+        self.set_species(scenario.species);
+        Ok(())
+    }
+
+    pub fn species(&self) -> &SpeciesSet {
+        &self.species
+    }
+
+    /// Replaces the species and reinitializes the points
+    pub fn set_species(&mut self, species: SpeciesSet) {
+        self.species = species;
+        self.init_points();
+    }
+
+    pub fn behaviors(&self) -> &BehaviorPipeline {
+        &self.behaviors
+    }
+
+    /// Steering behaviours (the points are kept: weights can be tuned while running)
+    pub fn behaviors_mut(&mut self) -> &mut BehaviorPipeline {
+        &mut self.behaviors
+    }
+
+    pub fn set_behaviors(&mut self, behaviors: BehaviorPipeline) {
+        self.behaviors = behaviors;
+    }
+
+    /// Replaces species, behaviours and point count by those of a collective motion model;
+    /// points start at random places in random directions
+    pub fn apply_model(&mut self, model: &ModelPreset) {
+        self.behaviors = model.behaviors.clone();
+        self.species = model.species.clone();
+        self.init_points();
+        self.set_point_count(model.point_count);
+        for p in self.points.iter_mut() {
+            p.velocity = random_direction() * model.initial_speed;
+        }
+    }
+
+    pub fn predators(&self) -> &[Predator] {
+        &self.predators
+    }
+
+    pub fn add_predator(&mut self, position: Position) {
+        self.predators.push(Predator::new(position));
+    }
+
+    /// Removes the predator closest to `position` if within `radius` (returns true if removed)
+    pub fn remove_predator_near(&mut self, position: Position, radius: f32) -> bool {
+        let closest = self
+            .predators
+            .iter()
+            .enumerate()
+            .map(|(index, predator)| (index, predator.position.distance2(position)))
+            .filter(|(_, distance2)| *distance2 < radius * radius)
+            .min_by(|a, b| a.1.partial_cmp(&b.1).unwrap());
+        match closest {
+            Some((index, _)) => {
+                self.predators.swap_remove(index);
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn pursuit_target(&self) -> PursuitTarget {
+        self.predator_params.target
+    }
+
+    pub fn set_pursuit_target(&mut self, target: PursuitTarget) {
+        self.predator_params.target = target;
+    }
+
+    /// Number of update steps since the last (re)initialization
+    pub fn step(&self) -> u64 {
+        self.step
+    }
+
+    /// Simulation time since the last (re)initialization
+    pub fn time(&self) -> f32 {
+        self.step as f32 * TIME_STEP
+    }
+
+    pub fn space_size(&self) -> (f32, f32) {
+        (self.space_size.width, self.space_size.height)
+    }
+
+    /// Scalar value of each pixel of the background image (row-major),
+    /// brightness by default (cf. [PointsSimulator::set_color_conversion])
+    pub fn brightness_map(&self) -> (u32, u32, Vec<f32>) {
+        let (width, height, values) = self.background.values();
+        (width, height, values.to_vec())
+    }
+
+    pub fn field_effects(&self) -> &FieldEffects {
+        &self.fields
+    }
+
+    /// Selects one of the demo field couplings built on the background image
+    /// (cf. [FieldEffects::preset]); `index` wraps around
+    pub fn set_field_preset(&mut self, index: usize) {
+        self.field_preset = index % FieldEffects::PRESET_COUNT;
+        self.fields = FieldEffects::preset(
+            self.field_preset,
+            &self.background,
+            &self.img,
+            self.space_size(),
+        );
+    }
+
+    pub fn field_preset(&self) -> usize {
+        self.field_preset
+    }
+
+    pub fn color_conversion(&self) -> &ColorConversion {
+        &self.conversion
+    }
+
+    /// Changes how the background image is converted to scalars (fields are rebuilt)
+    pub fn set_color_conversion(&mut self, conversion: ColorConversion) {
+        self.conversion = conversion;
+        self.rebuild_background();
+    }
+
+    /// Replaces the background by an image sequence, animated along the simulation time
+    pub fn set_background(&mut self, sequence: ImageSequence) {
+        self.sequence = sequence;
+        self.frame = self.sequence.position(self.time());
+        self.img = self.sequence.image(self.frame);
+        self.rebuild_background();
+    }
+
+    /// Incremented each time the background (and the fields built on it) changes
+    pub fn background_generation(&self) -> u64 {
+        self.background_generation
+    }
+
+    /// Shows the frame of the background sequence at the current time (if it changed)
+    fn animate_background(&mut self) {
+        let frame = self.sequence.position(self.time());
+        if frame != self.frame {
+            self.frame = frame;
+            self.img = self.sequence.image(frame);
+            self.rebuild_background();
+        }
+    }
+
+    fn rebuild_background(&mut self) {
+        self.background = Arc::new(ImageScalarField::from_image(
+            &self.img,
+            self.space_size(),
+            &self.conversion,
+        ));
+        self.set_field_preset(self.field_preset);
+        self.background_generation += 1;
+    }
+
+    /// True when fields only apply the historical brightness speed modulation
+    /// (the only coupling supported by the GPU backend)
+    pub fn fields_are_brightness_speed(&self) -> bool {
+        self.fields.is_brightness_speed(&self.background)
+    }
+
+    /// Accounts for a step computed outside of this simulator (e.g. on the GPU)
+    pub(crate) fn advance_step(&mut self) {
+        self.step += 1;
+        self.animate_background();
+    }
+
+    /// True when only the random walk with the brightness speed modulation applies: the rules of
+    /// [PointsSimulator::update_random_walk] (and of the GPU backend)
+    pub fn is_plain_random_walk(&self) -> bool {
+        self.species.random_walk_only()
+            && self.behaviors.is_random_walk_only()
+            && self.predators.is_empty()
+            && self.obstacles.is_empty()
+            && self.lifecycle.config().is_static()
+            && self.fields_are_brightness_speed()
     }
 
     pub fn update(&mut self) {
-        let get_pixel_brightness = Self::get_pixel_brightness(&self.img, self.space_size);
-        let periodize_point = Self::periodize_point(self.space_size);
+        if self.is_plain_random_walk() {
+            self.update_random_walk(self.step as u32);
+            return;
+        }
+        self.soa = None;
+        let periodize_point = Self::periodize_point(self.space_size());
 
-        let vel_space = Range::new(0., 10.0);
-        // let ang_space = Range::new(0., 6.28);
-        let ang_space = Range::new(-1.0, 1.0);
-        self.points.par_iter_mut().for_each(|p| {
-            let mut rng = rand::thread_rng();
-            let a = ang_space.ind_sample(&mut rng);
-            let m = vel_space.ind_sample(&mut rng);
-            p.velocity = Basis2::from_angle(Rad(a)).rotate_vector(Vector2::new(0., m))
-                * (1.2 - get_pixel_brightness(p.position.x, p.position.y));
-            p.position = periodize_point(p.position + p.velocity / 5.0);
+        let fields = &self.fields;
+        let species = &self.species;
+        let behaviors = &self.behaviors;
+        let predators = &self.predators;
+        let predator_params = &self.predator_params;
+        let obstacles = &self.obstacles;
+        // points leaving the space die (cf. [Lifecycle::step]) instead of wrapping around
+        let wrap = !self.lifecycle.config().death.leaving_domain;
+        let behaviors_radius = behaviors.perception_radius();
+        let grid =
+            if species.uses_neighbours() || behaviors_radius.is_some() || !predators.is_empty() {
+                Some(SpatialGrid::new(
+                    &self.points,
+                    self.space_size(),
+                    species
+                        .min_perception_radius()
+                        .min(predator_params.fear_radius)
+                        .min(behaviors_radius.unwrap_or(f32::INFINITY)),
+                ))
+            } else {
+                None
+            };
+
+        // New velocities are computed from the previous state of all points before being applied
+        let points = &self.points;
+        let context = SteeringContext {
+            points,
+            grid: grid.as_ref(),
+            species,
+            fields,
+            step: self.step,
+            time: self.step as f32 * TIME_STEP,
+            time_step: TIME_STEP,
+        };
+        let velocities: Vec<Velocity> = points
+            .par_iter()
+            .enumerate()
+            .map(|(index, p)| {
+                let steering = behaviors.steer(&context, index);
+                let mut forces = match &grid {
+                    Some(grid) => {
+                        let mut forces = neighbour_forces(grid, species, points, index);
+                        for predator in predators.iter() {
+                            let offset = grid.offset(predator.position, p.position);
+                            forces.flee += predator.flee_force(offset, predator_params);
+                        }
+                        forces
+                    }
+                    None => NeighbourForces::default(),
+                };
+                if !obstacles.is_empty() {
+                    forces.avoidance = obstacles.avoidance(p.position, p.velocity);
+                }
+                forces.field = fields.force(p.position);
+                let velocity = species.get(p.species).steer(p.velocity, steering, &forces);
+                fields.bias_heading(p.position, velocity)
+            })
+            .collect();
+
+        self.points
+            .par_iter_mut()
+            .zip(velocities.par_iter())
+            .for_each(|(p, velocity)| {
+                p.velocity = *velocity;
+                p.position = Self::move_point(
+                    obstacles,
+                    &periodize_point,
+                    p.position,
+                    &mut p.velocity,
+                    wrap,
+                );
+            });
+
+        if let Some(grid) = &grid {
+            for predator in self.predators.iter_mut() {
+                predator.steer(grid, &self.predator_params, TIME_STEP);
+                predator.position = Self::move_point(
+                    obstacles,
+                    &periodize_point,
+                    predator.position,
+                    &mut predator.velocity,
+                    true,
+                );
+            }
+        }
+        self.lifecycle.step(
+            &mut self.points,
+            TIME_STEP,
+            &SpawnContext {
+                space_size: (self.space_size.width, self.space_size.height),
+                species: &self.species,
+                background: &self.background,
+                background_generation: self.background_generation,
+            },
+        );
+        self.advance_step();
+    }
+
+    /// Random walk step on a structure of arrays copy of the points (cf. [PointsSoA]), with random
+    /// values derived from `seed` and the point indices as done by the GPU backend (used to
+    /// validate it). Only the random walk is applied (cf. [SpeciesSet::random_walk_only]) with
+    /// the brightness speed modulation (cf. [PointsSimulator::fields_are_brightness_speed]).
+    pub fn update_random_walk(&mut self, seed: u32) {
+        let points = &self.points;
+        let soa = self
+            .soa
+            .get_or_insert_with(|| PointsSoA::from_points(points));
+        // statically dispatched equivalent of `self.fields.speed_factor` (cf. is_plain_random_walk)
+        let background = &*self.background;
+        soa.random_walk(seed, |x, y| {
+            BRIGHTNESS_SPEED_OFFSET + BRIGHTNESS_SPEED_GAIN * background.sample(Position::new(x, y))
         });
+        soa.integrate(TIME_STEP, (self.space_size.width, self.space_size.height));
+        soa.write_points(&mut self.points);
+        self.advance_step();
     }
 
-    fn internal_init_points(space_size: SimulationSpace, points: &mut Vec<Point>) {
-        // Random position initialization
-        points.par_iter_mut().for_each(|p| {
+    /// Array of structs version of [PointsSimulator::update_random_walk], the reference of its
+    /// tests and benchmarks
+    pub fn update_random_walk_aos(&mut self, seed: u32) {
+        let periodize_point = Self::periodize_point(self.space_size());
+        let fields = &self.fields;
+
+        let seed = hash_u32(seed);
+        self.points
+            .par_iter_mut()
+            .enumerate()
+            .for_each(|(index, p)| {
+                let mut state = hash_u32(index as u32 ^ seed);
+                let a = -1.0 + 2.0 * hashed_random(&mut state);
+                let m = 10.0 * hashed_random(&mut state);
+                p.velocity = random_walk_velocity(a, m, fields.speed_factor(p.position));
+                p.position = periodize_point(p.position + p.velocity * TIME_STEP);
+            });
+        self.soa = None;
+        self.advance_step();
+    }
+
+    fn internal_init_points(
+        space_size: SimulationSpace,
+        species: &SpeciesSet,
+        obstacles: &Obstacles,
+        points: &mut [Point],
+    ) {
+        // Random position initialization (outside of obstacles)
+        let count = points.len();
+        points.par_iter_mut().enumerate().for_each(|(index, p)| {
             let mut rng = rand::thread_rng();
+            let mut random_position = || Position {
+                x: rng.gen::<f32>() * space_size.width,
+                y: rng.gen::<f32>() * space_size.height,
+            };
+            let mut position = random_position();
+            for _ in 0..MAX_PLACEMENT_ATTEMPTS {
+                if !obstacles.contains(position) {
+                    break;
+                }
+                position = random_position();
+            }
             *p = Point {
-                position: Position {
-                    x: rng.gen::<f32>() * space_size.width,
-                    y: rng.gen::<f32>() * space_size.height,
-                },
+                position,
                 velocity: Velocity { x: 0.0, y: 0.0 },
+                species: species.species_for(index, count),
             }
         });
 
@@ -95,45 +628,91 @@ impl PointsSimulator {
         // });
     }
 
-    fn get_pixel_brightness<'a>(
-        img: &'a RgbImage,
-        space_size: SimulationSpace,
-    ) -> impl Fn(f32, f32) -> f32 + 'a {
-        let (img_width, img_height) = img.dimensions();
-        move |x: f32, y: f32| {
-            let pixel = img.get_pixel(
-                u32::clamp(
-                    (x / space_size.width * img_width as f32) as u32,
-                    0,
-                    img_width - 1,
-                ),
-                u32::clamp(
-                    (y / space_size.height * img_height as f32) as u32,
-                    0,
-                    img_height - 1,
-                ),
-            );
-            let v = pixel.0;
-            calculate_relative_brightness(v[0], v[1], v[2])
+    /// Move along `velocity` during a time step, stopped by the obstacles
+    /// (cf. [Obstacles::resolve_move]); with `wrap`, the end of a move leaving the space
+    /// is tested on the other side
+    fn move_point(
+        obstacles: &Obstacles,
+        periodize_point: &impl Fn(Position) -> Position,
+        from: Position,
+        velocity: &mut Velocity,
+        wrap: bool,
+    ) -> Position {
+        let to = from + *velocity * TIME_STEP;
+        let position = obstacles.resolve_move(from, to, velocity);
+        if !wrap || position != to {
+            return position;
+        }
+        let wrapped = periodize_point(to);
+        if wrapped == to
+            || obstacles.resolve_move(wrapped - (to - from), wrapped, velocity) == wrapped
+        {
+            wrapped
+        } else {
+            from
         }
     }
 
-    fn periodize_point(space_size: SimulationSpace) -> impl Fn(Position) -> Position {
+    /// Brings positions back into the periodic simulation space
+    pub fn periodize_point(space_size: (f32, f32)) -> impl Fn(Position) -> Position {
+        let (width, height) = space_size;
         move |mut p: Position| -> Position {
-            // 'while' version, seems to be faster than 'if' version
+            // 'while' version: a single 'if' is faster (cf. periodize benchmark) but misses
+            // points moving by more than the space size in a step (e.g. unbounded model speeds)
             while p.x < 0.0 {
-                p.x += space_size.width
+                p.x += width
             }
-            while p.x > space_size.width {
-                p.x -= space_size.width
+            while p.x > width {
+                p.x -= width
             }
             while p.y < 0.0 {
-                p.y += space_size.height
+                p.y += height
             }
-            while p.y > space_size.height {
-                p.y -= space_size.height
+            while p.y > height {
+                p.y -= height
             }
             p
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn simulator() -> PointsSimulator {
+        PointsSimulator::new((1920, 1080)).unwrap()
+    }
+
+    #[test]
+    fn periodize_should_wrap_moves_longer_than_the_space() {
+        let periodize_point = PointsSimulator::periodize_point((100.0, 50.0));
+        let p = periodize_point(Position::new(-250.0, 170.0));
+        assert!((p.x - 50.0).abs() < 1e-4 && (p.y - 20.0).abs() < 1e-4);
+    }
+
+    #[test]
+    fn soa_random_walk_should_match_the_aos_implementation() {
+        let mut soa = simulator();
+        let mut aos = simulator();
+        aos.set_points(soa.points().to_vec());
+        // a point on an edge may be wrapped in one layout only (rounding): its speed factor is
+        // then sampled on the opposite edge of the background and both walks drift apart
+        let mut wrapped = vec![false; soa.points().len()];
+        for seed in 0..3 {
+            soa.update_random_walk(seed);
+            aos.update_random_walk_aos(seed);
+            let points = soa.points().iter().zip(aos.points().iter());
+            for ((a, b), wrapped) in points.zip(wrapped.iter_mut()) {
+                *wrapped |= (a.position - b.position).magnitude() > 1.0;
+                if !*wrapped {
+                    assert!((a.position - b.position).magnitude() < 1e-3);
+                    // both layouts round differently
+                    let tolerance = 1e-4 * b.velocity.magnitude().max(1.0);
+                    assert!((a.velocity - b.velocity).magnitude() < tolerance);
+                }
+            }
+        }
+        assert!(wrapped.iter().filter(|&&w| w).count() * 100 < wrapped.len());
+    }
+}
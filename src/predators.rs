@@ -0,0 +1,219 @@
+use crate::neighbours::SpatialGrid;
+use crate::point::{Position, Velocity};
+use cgmath::{Basis2, InnerSpace, Rad, Rotation, Rotation2, Vector2, Zero};
+use serde::{Deserialize, Serialize};
+use std::convert::TryFrom;
+use thiserror::Error;
+
+/// What a predator chases among the boids it perceives
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum PursuitTarget {
+    /// the closest boid
+    Nearest,
+    /// the most crowded part of the perceived boids
+    Densest,
+}
+
+#[derive(Error, Debug, PartialEq)]
+pub enum PredatorError {
+    #[error("Invalid predator parameters: {0}")]
+    InvalidParameters(&'static str),
+}
+
+#[derive(Debug, Copy, Clone, Serialize, Deserialize)]
+#[serde(try_from = "PredatorParamsDescription")]
+pub struct PredatorParams {
+    pub max_speed: f32,
+    /// maximum heading change per time unit (rad)
+    pub max_turn_rate: f32,
+    /// boids farther than this radius are not seen by predators
+    pub perception_radius: f32,
+    /// boids closer than this radius to a predator flee
+    pub fear_radius: f32,
+    /// magnitude of the flee steering applied to boids
+    pub flee_strength: f32,
+    pub target: PursuitTarget,
+}
+
+impl Default for PredatorParams {
+    fn default() -> Self {
+        PredatorParams {
+            max_speed: 14.0,
+            max_turn_rate: 1.5,
+            perception_radius: 150.0,
+            fear_radius: 40.0,
+            flee_strength: 20.0,
+            target: PursuitTarget::Nearest,
+        }
+    }
+}
+
+// Deserialized fields, checked by PredatorParams::check
+#[derive(Deserialize)]
+struct PredatorParamsDescription {
+    max_speed: f32,
+    max_turn_rate: f32,
+    perception_radius: f32,
+    fear_radius: f32,
+    flee_strength: f32,
+    target: PursuitTarget,
+}
+
+impl TryFrom<PredatorParamsDescription> for PredatorParams {
+    type Error = PredatorError;
+
+    fn try_from(description: PredatorParamsDescription) -> Result<Self, Self::Error> {
+        let params = PredatorParams {
+            max_speed: description.max_speed,
+            max_turn_rate: description.max_turn_rate,
+            perception_radius: description.perception_radius,
+            fear_radius: description.fear_radius,
+            flee_strength: description.flee_strength,
+            target: description.target,
+        };
+        params.check()?;
+        Ok(params)
+    }
+}
+
+impl PredatorParams {
+    /// Speeds and radii must be positive (radii give the cell size of the neighbour grids)
+    pub fn check(&self) -> Result<(), PredatorError> {
+        let positive = |value: f32| value.is_finite() && value > 0.0;
+        let invalid = |reason| Err(PredatorError::InvalidParameters(reason));
+        if !positive(self.max_speed) {
+            invalid("max_speed must be positive")
+        } else if self.max_turn_rate.is_nan() || self.max_turn_rate < 0.0 {
+            invalid("max_turn_rate must not be negative")
+        } else if !positive(self.perception_radius) {
+            invalid("perception_radius must be positive")
+        } else if !positive(self.fear_radius) {
+            invalid("fear_radius must be positive")
+        } else {
+            Ok(())
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, Serialize, Deserialize)]
+pub struct Predator {
+    pub position: Position,
+    pub velocity: Velocity,
+}
+
+// Side of the local histogram used to find the densest group (in bins)
+const DENSITY_BINS: usize = 4;
+
+impl Predator {
+    pub fn new(position: Position) -> Self {
+        Predator {
+            position,
+            velocity: Velocity::zero(),
+        }
+    }
+
+    /// Offset toward the boid(s) to chase, if any is perceived
+    fn target_offset(&self, grid: &SpatialGrid, params: &PredatorParams) -> Option<Vector2<f32>> {
+        let radius = params.perception_radius;
+        match params.target {
+            PursuitTarget::Nearest => {
+                let mut nearest: Option<Vector2<f32>> = None;
+                grid.for_each_neighbour(self.position, radius, |_, _, offset| {
+                    if nearest.map_or(true, |n| offset.magnitude2() < n.magnitude2()) {
+                        nearest = Some(offset);
+                    }
+                });
+                nearest
+            }
+            PursuitTarget::Densest => {
+                // boids are binned over the perception square; the fullest bin is chased
+                let mut bins = [(0usize, Vector2::<f32>::zero()); DENSITY_BINS * DENSITY_BINS];
+                let bin_of = |d: f32| {
+                    (((d + radius) / (2.0 * radius) * DENSITY_BINS as f32) as usize)
+                        .min(DENSITY_BINS - 1)
+                };
+                grid.for_each_neighbour(self.position, radius, |_, _, offset| {
+                    let bin = &mut bins[bin_of(offset.y) * DENSITY_BINS + bin_of(offset.x)];
+                    bin.0 += 1;
+                    bin.1 += offset;
+                });
+                bins.iter()
+                    .filter(|(count, _)| *count > 0)
+                    .max_by_key(|(count, _)| *count)
+                    .map(|(count, sum)| sum / *count as f32)
+            }
+        }
+    }
+
+    /// Turns toward the chased boids (within the turning limit) at full speed
+    pub fn steer(&mut self, grid: &SpatialGrid, params: &PredatorParams, time_step: f32) {
+        let heading = match self.target_offset(grid, params) {
+            Some(offset) if offset.magnitude2() > 0.0 => offset.normalize(),
+            _ if self.velocity.magnitude2() > 0.0 => self.velocity.normalize(),
+            _ => Vector2::new(0.0, 1.0),
+        };
+
+        let direction = if self.velocity.magnitude2() > 0.0 {
+            let current = self.velocity.normalize();
+            let angle: Rad<f32> = current.angle(heading);
+            let max_turn = params.max_turn_rate * time_step;
+            let turn = angle.0.clamp(-max_turn, max_turn);
+            Basis2::from_angle(Rad(turn)).rotate_vector(current)
+        } else {
+            heading
+        };
+        self.velocity = direction * params.max_speed;
+    }
+
+    /// Flee steering of a boid at `offset` from this predator (zero outside the fear radius)
+    pub fn flee_force(&self, offset: Vector2<f32>, params: &PredatorParams) -> Vector2<f32> {
+        let distance = offset.magnitude();
+        if distance >= params.fear_radius || distance == 0.0 {
+            Vector2::zero()
+        } else {
+            offset / distance * (params.flee_strength * (1.0 - distance / params.fear_radius))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::point::Point;
+
+    #[test]
+    fn predator_should_turn_toward_nearest_boid_within_turning_limit() {
+        let boid = Point {
+            position: Position::new(60.0, 50.0),
+            velocity: Velocity::zero(),
+            species: 0,
+        };
+        let grid = SpatialGrid::new(&[boid], (100.0, 100.0), 10.0);
+        let params = PredatorParams::default();
+        let mut predator = Predator {
+            position: Position::new(50.0, 50.0),
+            velocity: Velocity::new(0.0, params.max_speed), // heading orthogonal to the boid
+        };
+
+        predator.steer(&grid, &params, 0.2);
+
+        let turn = Velocity::new(0.0, 1.0).angle(predator.velocity);
+        assert!((turn.0.abs() - params.max_turn_rate * 0.2).abs() < 1e-5);
+        assert!(predator.velocity.x > 0.0);
+        assert!((predator.velocity.magnitude() - params.max_speed).abs() < 1e-4);
+    }
+
+    #[test]
+    fn non_positive_radii_should_be_rejected() {
+        let params = PredatorParams {
+            fear_radius: 0.0,
+            ..PredatorParams::default()
+        };
+        assert!(params.check().is_err());
+
+        let mut json = serde_json::to_value(PredatorParams::default()).unwrap();
+        assert!(serde_json::from_value::<PredatorParams>(json.clone()).is_ok());
+        json["perception_radius"] = serde_json::json!(-1.0);
+        assert!(serde_json::from_value::<PredatorParams>(json).is_err());
+    }
+}
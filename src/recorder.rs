@@ -0,0 +1,355 @@
+//! Time series of run statistics (cf. [crate::analysis]) appended to a CSV or JSON Lines file
+//! every few steps, e.g. to be loaded with `pandas.read_csv` / `pandas.read_json(lines=True)`
+
+use crate::analysis::{analyze, AnalysisConfig, StepStatistics};
+use crate::points_simulator::PointsSimulator;
+use anyhow::{anyhow, Context, Result};
+use serde::ser::{SerializeMap, Serializer};
+use serde::Serialize;
+use std::fmt;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+use std::str::FromStr;
+use std::time::Instant;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RecordFormat {
+    /// header line then one comma separated line per record
+    Csv,
+    /// one JSON object per line
+    JsonLines,
+}
+
+impl RecordFormat {
+    /// `.csv`, `.jsonl` or `.ndjson`
+    pub fn from_path<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let path = path.as_ref();
+        match path.extension().and_then(|e| e.to_str()) {
+            Some(e) if e.eq_ignore_ascii_case("csv") => Ok(RecordFormat::Csv),
+            Some(e) if e.eq_ignore_ascii_case("jsonl") || e.eq_ignore_ascii_case("ndjson") => {
+                Ok(RecordFormat::JsonLines)
+            }
+            _ => Err(anyhow!(
+                "Unknown record format for {:?} (expected .csv, .jsonl or .ndjson)",
+                path
+            )),
+        }
+    }
+}
+
+/// Statistic recorded as a column
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Metric {
+    PointCount,
+    Polarization,
+    Milling,
+    MeanSpeed,
+    SpeedStd,
+    NearestNeighbour,
+    ClusterCount,
+    LargestCluster,
+    DensityMean,
+    DensityVariance,
+}
+
+impl Metric {
+    pub const ALL: [Metric; 10] = [
+        Metric::PointCount,
+        Metric::Polarization,
+        Metric::Milling,
+        Metric::MeanSpeed,
+        Metric::SpeedStd,
+        Metric::NearestNeighbour,
+        Metric::ClusterCount,
+        Metric::LargestCluster,
+        Metric::DensityMean,
+        Metric::DensityVariance,
+    ];
+
+    /// Column name
+    pub fn name(self) -> &'static str {
+        match self {
+            Metric::PointCount => "point_count",
+            Metric::Polarization => "polarization",
+            Metric::Milling => "milling",
+            Metric::MeanSpeed => "mean_speed",
+            Metric::SpeedStd => "speed_std",
+            Metric::NearestNeighbour => "nearest_neighbour",
+            Metric::ClusterCount => "cluster_count",
+            Metric::LargestCluster => "largest_cluster",
+            Metric::DensityMean => "density_mean",
+            Metric::DensityVariance => "density_variance",
+        }
+    }
+
+    fn value(self, statistics: &StepStatistics) -> Value {
+        match self {
+            Metric::PointCount => Value::Integer(statistics.point_count as u64),
+            Metric::Polarization => Value::Real(statistics.polarization),
+            Metric::Milling => Value::Real(statistics.milling),
+            Metric::MeanSpeed => Value::Real(statistics.mean_speed),
+            Metric::SpeedStd => Value::Real(statistics.speed_std),
+            Metric::NearestNeighbour => Value::Real(statistics.nearest_neighbour),
+            Metric::ClusterCount => Value::Integer(statistics.cluster_count as u64),
+            Metric::LargestCluster => {
+                Value::Integer(statistics.cluster_sizes.first().copied().unwrap_or(0) as u64)
+            }
+            Metric::DensityMean => Value::Real(statistics.density_mean),
+            Metric::DensityVariance => Value::Real(statistics.density_variance),
+        }
+    }
+
+    /// Comma separated metric names, e.g. `polarization,milling`
+    pub fn parse_list(list: &str) -> Result<Vec<Metric>> {
+        list.split(',').map(|name| name.trim().parse()).collect()
+    }
+}
+
+impl FromStr for Metric {
+    type Err = anyhow::Error;
+
+    fn from_str(name: &str) -> Result<Self> {
+        Metric::ALL
+            .iter()
+            .copied()
+            .find(|metric| metric.name() == name)
+            .ok_or_else(|| {
+                let names: Vec<_> = Metric::ALL.iter().map(|metric| metric.name()).collect();
+                anyhow!("Unknown metric {:?} (one of {})", name, names.join(", "))
+            })
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct RecorderConfig {
+    /// a record every `interval` steps (steps multiple of it)
+    pub interval: u64,
+    /// columns after `step`, `time` (simulation time) and `wall_time` (seconds since the recorder creation)
+    pub metrics: Vec<Metric>,
+    pub analysis: AnalysisConfig,
+}
+
+impl Default for RecorderConfig {
+    fn default() -> Self {
+        RecorderConfig {
+            interval: 10,
+            metrics: Metric::ALL.to_vec(),
+            analysis: AnalysisConfig::default(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Serialize)]
+#[serde(untagged)]
+enum Value {
+    Integer(u64),
+    Real(f32),
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Value::Integer(value) => write!(f, "{}", value),
+            Value::Real(value) => write!(f, "{}", value),
+        }
+    }
+}
+
+/// Named values serialized as a JSON object (in column order)
+struct Row<'a>(&'a [(&'static str, Value)]);
+
+impl Serialize for Row<'_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut map = serializer.serialize_map(Some(self.0.len()))?;
+        for (name, value) in self.0 {
+            map.serialize_entry(name, value)?;
+        }
+        map.end()
+    }
+}
+
+/// Appends a record every [RecorderConfig::interval] steps; buffered output is flushed
+/// by [Recorder::flush] and when the recorder is dropped.
+///
+/// A winit event loop never returns nor drops its closure: flush on `Event::LoopDestroyed`.
+pub struct Recorder<W: Write = BufWriter<File>> {
+    writer: W,
+    format: RecordFormat,
+    config: RecorderConfig,
+    start: Instant,
+    last_step: Option<u64>,
+}
+
+impl Recorder {
+    /// Creates (truncates) `path`, the format being given by its extension
+    pub fn create<P: AsRef<Path>>(path: P, config: RecorderConfig) -> Result<Self> {
+        let path = path.as_ref();
+        let format = RecordFormat::from_path(path)?;
+        let file =
+            File::create(path).with_context(|| format!("Failed to create record {:?}", path))?;
+        Recorder::new(BufWriter::new(file), format, config)
+    }
+}
+
+impl<W: Write> Recorder<W> {
+    pub fn new(writer: W, format: RecordFormat, config: RecorderConfig) -> Result<Self> {
+        let mut recorder = Recorder {
+            writer,
+            format,
+            config: RecorderConfig {
+                interval: config.interval.max(1),
+                ..config
+            },
+            start: Instant::now(),
+            last_step: None,
+        };
+        if format == RecordFormat::Csv {
+            let mut header = vec!["step", "time", "wall_time"];
+            header.extend(recorder.config.metrics.iter().map(|metric| metric.name()));
+            writeln!(recorder.writer, "{}", header.join(","))
+                .context("Failed to write record header")?;
+        }
+        Ok(recorder)
+    }
+
+    pub fn config(&self) -> &RecorderConfig {
+        &self.config
+    }
+
+    /// Whether `step` is to be recorded (and has not been yet)
+    pub fn is_due(&self, step: u64) -> bool {
+        step % self.config.interval == 0 && self.last_step != Some(step)
+    }
+
+    /// Records the current step of `simulator` if due (analyzing its points); returns whether it did
+    pub fn record(&mut self, simulator: &PointsSimulator) -> Result<bool> {
+        if !self.is_due(simulator.step()) {
+            return Ok(false);
+        }
+        let statistics = analyze(
+            simulator.points(),
+            simulator.space_size(),
+            &self.config.analysis,
+        );
+        self.write(simulator.step(), simulator.time(), &statistics)?;
+        Ok(true)
+    }
+
+    /// Writes a record regardless of the interval (e.g. with statistics already computed for display)
+    pub fn write(&mut self, step: u64, time: f32, statistics: &StepStatistics) -> Result<()> {
+        let mut row = vec![
+            ("step", Value::Integer(step)),
+            ("time", Value::Real(time)),
+            ("wall_time", Value::Real(self.start.elapsed().as_secs_f32())),
+        ];
+        row.extend(
+            self.config
+                .metrics
+                .iter()
+                .map(|&metric| (metric.name(), metric.value(statistics))),
+        );
+        match self.format {
+            RecordFormat::Csv => {
+                let values: Vec<String> = row.iter().map(|(_, value)| value.to_string()).collect();
+                writeln!(self.writer, "{}", values.join(","))?;
+            }
+            RecordFormat::JsonLines => {
+                serde_json::to_writer(&mut self.writer, &Row(&row))?;
+                writeln!(self.writer)?;
+            }
+        }
+        self.last_step = Some(step);
+        Ok(())
+    }
+
+    pub fn flush(&mut self) -> Result<()> {
+        self.writer.flush().context("Failed to flush record")
+    }
+}
+
+impl<W: Write> Drop for Recorder<W> {
+    fn drop(&mut self) {
+        // errors cannot be reported here: call flush() to handle them
+        let _ = self.writer.flush();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record(format: RecordFormat, metrics: Vec<Metric>) -> String {
+        let mut simulator = PointsSimulator::new((400, 300)).unwrap();
+        simulator.set_point_count(1000);
+        let mut output = Vec::new();
+        {
+            let config = RecorderConfig {
+                interval: 5,
+                metrics,
+                ..RecorderConfig::default()
+            };
+            let mut recorder = Recorder::new(&mut output, format, config).unwrap();
+            assert!(recorder.record(&simulator).unwrap());
+            assert!(!recorder.record(&simulator).unwrap());
+            for _ in 0..12 {
+                simulator.update();
+                recorder.record(&simulator).unwrap();
+            }
+        }
+        String::from_utf8(output).unwrap()
+    }
+
+    #[test]
+    fn csv_should_have_a_header_and_a_line_per_interval() {
+        let output = record(
+            RecordFormat::Csv,
+            vec![Metric::Polarization, Metric::PointCount],
+        );
+        let lines: Vec<&str> = output.lines().collect();
+        assert_eq!(lines[0], "step,time,wall_time,polarization,point_count");
+        let steps: Vec<&str> = lines[1..]
+            .iter()
+            .map(|line| line.split(',').next().unwrap())
+            .collect();
+        assert_eq!(steps, ["0", "5", "10"]);
+        let columns: Vec<&str> = lines[2].split(',').collect();
+        assert_eq!(columns.len(), 5);
+        assert_eq!(columns[1], "1");
+        assert_eq!(columns[4], "1000");
+        assert!((0.0..=1.0).contains(&columns[3].parse::<f32>().unwrap()));
+    }
+
+    #[test]
+    fn json_lines_should_have_an_object_per_interval() {
+        let output = record(RecordFormat::JsonLines, Metric::ALL.to_vec());
+        let records: Vec<serde_json::Value> = output
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(records.len(), 3);
+        assert_eq!(records[2]["step"], 10);
+        assert_eq!(records[2]["point_count"], 1000);
+        for metric in &Metric::ALL {
+            assert!(records[0].get(metric.name()).is_some(), "{:?}", metric);
+        }
+    }
+
+    #[test]
+    fn format_and_metrics_should_be_parsed() {
+        assert_eq!(
+            RecordFormat::from_path("a/b.csv").unwrap(),
+            RecordFormat::Csv
+        );
+        assert_eq!(
+            RecordFormat::from_path("b.jsonl").unwrap(),
+            RecordFormat::JsonLines
+        );
+        assert!(RecordFormat::from_path("b.txt").is_err());
+        assert_eq!(
+            Metric::parse_list("polarization, cluster_count").unwrap(),
+            [Metric::Polarization, Metric::ClusterCount]
+        );
+        assert!(Metric::parse_list("polarisation").is_err());
+    }
+}
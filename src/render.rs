@@ -1,46 +1,186 @@
-use crate::glx::{self, gl, WindowSizeInfo};
-use crate::shader_programs::lines::LinesRenderProgram;
-use crate::shader_programs::points::{Point, PointsRenderProgram};
-use crate::shader_programs::triangle::TriangleRenderProgram;
+//! OpenGL rendering of the simulation (windowing, shaders, HUD, GPU backend)
+
+#[macro_use]
+pub mod glx;
+pub mod capture;
+pub mod fps;
+pub mod gpu_simulator;
+pub mod hud;
+pub mod shader_programs;
+
+use self::glx::{gl, Buffer, UploadMode, WindowSizeInfo};
+use self::shader_programs::lines::LinesRenderProgram;
+use self::shader_programs::overlay::{OverlayBuilder, OverlayColor, OverlayRenderProgram};
+use self::shader_programs::points::PointsRenderProgram;
+use self::shader_programs::text::{TextBatch, TextRenderProgram};
+use crate::obstacles::{triangulate, Obstacles, Shape};
+use crate::point::Point;
+use crate::predators::Predator;
 use anyhow::Result;
 use std::rc::Rc;
 
+/// Where the points to draw are stored
+pub enum PointsSource<'a> {
+    /// in main memory (uploaded at each frame)
+    Host(&'a [Point]),
+    /// in a GPU buffer (`count` first points)
+    Device(&'a Buffer, usize),
+}
+
+const PREDATOR_COLOR: OverlayColor = [1.0, 0.1, 0.1, 0.9];
+const PREDATOR_SIZE: f32 = 12.0; // pixels
+
+/// Adds predators to the overlay as arrows pointing in their direction of motion
+/// (`space_size` is the simulation space drawn on a `size` screen)
+pub fn draw_predators(
+    predators: &[Predator],
+    space_size: (f32, f32),
+    size: (u32, u32),
+    overlay: &mut OverlayBuilder,
+) {
+    let scale = (size.0 as f32 / space_size.0, size.1 as f32 / space_size.1);
+    for predator in predators {
+        let speed = (predator.velocity.x.powi(2) + predator.velocity.y.powi(2)).sqrt();
+        let (dx, dy) = if speed > 0.0 {
+            (predator.velocity.x / speed, predator.velocity.y / speed)
+        } else {
+            (0.0, 1.0)
+        };
+        let (x, y) = (predator.position.x * scale.0, predator.position.y * scale.1);
+        let (length, width) = (PREDATOR_SIZE, PREDATOR_SIZE / 2.0);
+        overlay.triangle(
+            [
+                [x + dx * length, y + dy * length],
+                [
+                    x - dx * length / 2.0 - dy * width,
+                    y - dy * length / 2.0 + dx * width,
+                ],
+                [
+                    x - dx * length / 2.0 + dy * width,
+                    y - dy * length / 2.0 - dx * width,
+                ],
+            ],
+            PREDATOR_COLOR,
+        );
+    }
+}
+
+const OBSTACLE_COLOR: OverlayColor = [0.45, 0.45, 0.55, 0.85];
+const CIRCLE_SEGMENTS: usize = 32;
+
+/// Adds obstacles to the overlay (same coordinates conversion as [draw_predators])
+pub fn draw_obstacles(
+    obstacles: &Obstacles,
+    space_size: (f32, f32),
+    size: (u32, u32),
+    overlay: &mut OverlayBuilder,
+) {
+    let scale = (size.0 as f32 / space_size.0, size.1 as f32 / space_size.1);
+    let to_screen = |x: f32, y: f32| [x * scale.0, y * scale.1];
+    for shape in obstacles.shapes() {
+        match shape {
+            Shape::Circle { center, radius } => {
+                let corner = |i: usize| {
+                    let angle = i as f32 / CIRCLE_SEGMENTS as f32 * std::f32::consts::TAU;
+                    to_screen(
+                        center.x + radius * angle.cos(),
+                        center.y + radius * angle.sin(),
+                    )
+                };
+                for i in 0..CIRCLE_SEGMENTS {
+                    overlay.triangle(
+                        [to_screen(center.x, center.y), corner(i), corner(i + 1)],
+                        OBSTACLE_COLOR,
+                    );
+                }
+            }
+            Shape::Polygon { vertices } => {
+                for [a, b, c] in triangulate(vertices) {
+                    overlay.triangle(
+                        [
+                            to_screen(a.x, a.y),
+                            to_screen(b.x, b.y),
+                            to_screen(c.x, c.y),
+                        ],
+                        OBSTACLE_COLOR,
+                    );
+                }
+            }
+        }
+    }
+    for &(x, y, width, height) in obstacles.mask_rects() {
+        overlay.rect(
+            x * scale.0,
+            y * scale.1,
+            width * scale.0,
+            height * scale.1,
+            OBSTACLE_COLOR,
+        );
+    }
+}
+
 #[derive(Debug)]
 pub struct RendererConfig {
     pub size: WindowSizeInfo,
 }
 
 pub struct Renderer {
-    pub gl: Rc<crate::glx::gl::Gl>,
-    triangle_program: TriangleRenderProgram,
+    pub gl: Rc<gl::Gl>,
     points_program: PointsRenderProgram,
     lines_program: LinesRenderProgram,
+    overlay_program: OverlayRenderProgram,
+    text_program: TextRenderProgram,
 }
 
 impl Renderer {
     pub fn new(gl: gl::Gl, config: RendererConfig) -> Result<Renderer> {
         let gl = Rc::new(gl);
         Ok(Renderer {
-            triangle_program: TriangleRenderProgram::new(gl.clone())?,
             points_program: PointsRenderProgram::new(gl.clone(), config.size)?,
             lines_program: LinesRenderProgram::new(gl.clone())?,
+            overlay_program: OverlayRenderProgram::new(gl.clone())?,
+            text_program: TextRenderProgram::new(gl.clone(), config.size)?,
             gl,
         })
     }
 
     pub fn initialize(&mut self) -> Result<()> {
         self.points_program.initialize()?;
-        self.triangle_program.initialize()?;
         self.lines_program.initialize()?;
+        self.overlay_program.initialize()?;
+        self.text_program.initialize()?;
         Ok(())
     }
 
+    pub fn upload_mode(&self) -> UploadMode {
+        self.points_program.upload_mode
+    }
+
+    pub fn set_upload_mode(&mut self, mode: UploadMode) {
+        self.points_program.upload_mode = mode;
+    }
+
+    /// Colour of each species (indexed by species id)
+    pub fn set_species_colors(&mut self, colors: &[[f32; 3]]) {
+        self.points_program.set_species_colors(colors);
+    }
+
+    /// Smoothed CPU time spent submitting the points upload (in ms)
+    pub fn submit_time(&self) -> f32 {
+        self.points_program.submit_time()
+    }
+
+    /// Creates an empty text batch to be filled before calling [Renderer::render]
+    pub fn new_text_batch(&self) -> TextBatch {
+        self.text_program.new_batch()
+    }
+
     pub fn render(
         &self,
-        t: f32,
-        ratio: f32,
         color: [f32; 4],
-        points: &[Point],
+        points: PointsSource,
+        overlay: &OverlayBuilder,
+        text: &TextBatch,
         size: (u32, u32),
     ) -> Result<()> {
         glx::clear_screen(&self.gl, color);
@@ -49,11 +189,21 @@ impl Renderer {
         unsafe {
             self.gl.UseProgram(0);
         };
-        self.triangle_program.render(t, ratio)?;
+        match points {
+            PointsSource::Host(points) => self.points_program.render(points)?,
+            PointsSource::Device(buffer, count) => {
+                self.points_program.render_buffer(buffer, count)?
+            }
+        }
+        unsafe {
+            self.gl.UseProgram(0);
+        };
+        // overlay and text are drawn last to stay on top of the simulation
+        self.overlay_program.render(overlay.vertices(), size)?;
         unsafe {
             self.gl.UseProgram(0);
         };
-        self.points_program.render(points)?;
+        self.text_program.render(text, size)?;
         unsafe {
             self.gl.UseProgram(0);
         };
@@ -0,0 +1,376 @@
+//! Capture of the rendered frames without stalling the render loop: pixels are read into a ring
+//! of pixel buffer objects and mapped a few frames later (once the GPU is done with them),
+//! then a background thread writes them as numbered PNG images or as a Y4M stream
+//! (e.g. `ffmpeg -i capture.y4m capture.mp4`)
+
+use crate::render::glx::{gl, image_from_gl_pixels, Buffer};
+use anyhow::{anyhow, Context, Result};
+use image::RgbImage;
+use std::collections::VecDeque;
+use std::fmt;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+use std::sync::mpsc::{sync_channel, SyncSender, TrySendError};
+use std::thread::JoinHandle;
+
+/// Pixel buffer objects in flight: a frame is mapped once `PBO_COUNT` more frames have been captured
+const PBO_COUNT: usize = 3;
+/// Frames waiting for the writer thread; further frames are dropped
+const QUEUE_SIZE: usize = 8;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CaptureFormat {
+    /// `frame_000000.png`, `frame_000001.png`... in a directory
+    Png,
+    /// a single YUV 4:4:4 stream
+    Y4m,
+}
+
+impl CaptureFormat {
+    /// Y4M for a `.y4m` path, PNG images in the `path` directory otherwise
+    pub fn from_path<P: AsRef<Path>>(path: P) -> Self {
+        match path.as_ref().extension().and_then(|e| e.to_str()) {
+            Some(e) if e.eq_ignore_ascii_case("y4m") => CaptureFormat::Y4m,
+            _ => CaptureFormat::Png,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct CaptureConfig {
+    /// directory of the PNG images or `.y4m` file
+    pub path: PathBuf,
+    /// one frame out of `every` is captured
+    pub every: u32,
+    /// frame rate written in the Y4M header
+    pub fps: u32,
+}
+
+impl Default for CaptureConfig {
+    fn default() -> Self {
+        CaptureConfig {
+            path: PathBuf::from("capture"),
+            every: 1,
+            fps: 60,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct CaptureReport {
+    pub written: usize,
+    /// frames dropped because the writer thread was late (gaps in the PNG numbering)
+    pub dropped: usize,
+}
+
+impl fmt::Display for CaptureReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} frame(s) written, {} dropped",
+            self.written, self.dropped
+        )
+    }
+}
+
+/// BT.601 (studio range) YCbCr of an sRGB colour
+fn ycbcr([r, g, b]: [u8; 3]) -> [u8; 3] {
+    let (r, g, b) = (r as f32, g as f32, b as f32);
+    [
+        16.0 + (65.738 * r + 129.057 * g + 25.064 * b) / 256.0,
+        128.0 + (-37.945 * r - 74.494 * g + 112.439 * b) / 256.0,
+        128.0 + (112.439 * r - 94.154 * g - 18.285 * b) / 256.0,
+    ]
+    .map(|c| c.round().clamp(0.0, 255.0) as u8)
+}
+
+/// Destination of the frames (owned by the writer thread)
+enum FrameSink {
+    Png(PathBuf),
+    Y4m {
+        writer: BufWriter<File>,
+        fps: u32,
+        header_written: bool,
+    },
+}
+
+impl FrameSink {
+    fn create(config: &CaptureConfig) -> Result<Self> {
+        let path = &config.path;
+        Ok(match CaptureFormat::from_path(path) {
+            CaptureFormat::Png => {
+                std::fs::create_dir_all(path)
+                    .with_context(|| format!("Failed to create capture directory {:?}", path))?;
+                FrameSink::Png(path.clone())
+            }
+            CaptureFormat::Y4m => {
+                let file = File::create(path)
+                    .with_context(|| format!("Failed to create capture {:?}", path))?;
+                FrameSink::Y4m {
+                    writer: BufWriter::new(file),
+                    fps: config.fps.max(1),
+                    header_written: false,
+                }
+            }
+        })
+    }
+
+    fn write(&mut self, index: usize, image: &RgbImage) -> Result<()> {
+        match self {
+            FrameSink::Png(dir) => {
+                let path = dir.join(format!("frame_{:06}.png", index));
+                image
+                    .save(&path)
+                    .with_context(|| format!("Failed to save frame {:?}", path))?;
+            }
+            FrameSink::Y4m {
+                writer,
+                fps,
+                header_written,
+            } => {
+                if !*header_written {
+                    writeln!(
+                        writer,
+                        "YUV4MPEG2 W{} H{} F{}:1 Ip A1:1 C444",
+                        image.width(),
+                        image.height(),
+                        fps
+                    )?;
+                    *header_written = true;
+                }
+                let pixels: Vec<[u8; 3]> = image.pixels().map(|p| ycbcr(p.0)).collect();
+                writer.write_all(b"FRAME\n")?;
+                for plane in 0..3 {
+                    let bytes: Vec<u8> = pixels.iter().map(|p| p[plane]).collect();
+                    writer.write_all(&bytes)?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn finish(&mut self) -> Result<()> {
+        if let FrameSink::Y4m { writer, .. } = self {
+            writer.flush().context("Failed to flush capture")?;
+        }
+        Ok(())
+    }
+}
+
+/// Pixels as read by `ReadPixels` (cf. [image_from_gl_pixels])
+struct RawFrame {
+    index: usize,
+    pixels: Vec<u8>,
+}
+
+/// Captures one frame out of [CaptureConfig::every] from the back buffer of a window
+/// of constant size; [FrameCapture::finish] writes the last frames
+pub struct FrameCapture {
+    buffers: Vec<Buffer>,
+    gl: Rc<gl::Gl>,
+    size: (u32, u32),
+    stride: u32,
+    every: u32,
+    frame: u64,
+    /// (buffer, capture index) of the frames being read, oldest first
+    pending: VecDeque<(usize, usize)>,
+    next_buffer: usize,
+    captured: usize,
+    dropped: usize,
+    sender: Option<SyncSender<RawFrame>>,
+    writer: Option<JoinHandle<Result<usize>>>,
+}
+
+impl FrameCapture {
+    pub fn start(gl: Rc<gl::Gl>, size: (u32, u32), config: &CaptureConfig) -> Result<Self> {
+        let mut sink = FrameSink::create(config)?;
+        let (width, height) = size;
+        // rows of `ReadPixels` are aligned on 4 bytes (cf. save_image)
+        let stride = (3 * width).div_ceil(4) * 4;
+        let (sender, receiver) = sync_channel::<RawFrame>(QUEUE_SIZE);
+        let writer = std::thread::Builder::new()
+            .name("frame capture".into())
+            .spawn(move || -> Result<usize> {
+                let mut written = 0;
+                for frame in receiver {
+                    let image = image_from_gl_pixels(&frame.pixels, width, height, stride);
+                    sink.write(frame.index, &image)?;
+                    written += 1;
+                }
+                sink.finish()?;
+                Ok(written)
+            })?;
+
+        let buffers = (0..PBO_COUNT)
+            .map(|_| {
+                let buffer = Buffer::new(gl.clone());
+                buffer.allocate(
+                    gl::PIXEL_PACK_BUFFER,
+                    (stride * height) as usize,
+                    gl::STREAM_READ,
+                );
+                buffer
+            })
+            .collect();
+        unsafe {
+            gl.BindBuffer(gl::PIXEL_PACK_BUFFER, 0);
+        }
+        Ok(FrameCapture {
+            buffers,
+            gl,
+            size,
+            stride,
+            every: config.every.max(1),
+            frame: 0,
+            pending: VecDeque::new(),
+            next_buffer: 0,
+            captured: 0,
+            dropped: 0,
+            sender: Some(sender),
+            writer: Some(writer),
+        })
+    }
+
+    /// Window size of the captured frames
+    pub fn size(&self) -> (u32, u32) {
+        self.size
+    }
+
+    /// To be called once per frame, after rendering and before swapping buffers
+    pub fn capture(&mut self) -> Result<()> {
+        let frame = self.frame;
+        self.frame += 1;
+        if frame % self.every as u64 != 0 {
+            return Ok(());
+        }
+        if self.pending.len() == PBO_COUNT {
+            let (buffer, index) = self.pending.pop_front().unwrap();
+            self.send(buffer, index, false)?;
+        }
+
+        let buffer = self.next_buffer;
+        self.next_buffer = (buffer + 1) % PBO_COUNT;
+        self.buffers[buffer].bind(gl::PIXEL_PACK_BUFFER);
+        let gl = &self.gl;
+        unsafe {
+            gl.PixelStorei(gl::PACK_ALIGNMENT, 4);
+            gl.ReadBuffer(gl::BACK);
+            // asynchronous: the pixels are copied into the bound buffer
+            gl.ReadPixels(
+                0,
+                0,
+                self.size.0 as i32,
+                self.size.1 as i32,
+                gl::RGB,
+                gl::UNSIGNED_BYTE,
+                std::ptr::null_mut(),
+            );
+            gl.BindBuffer(gl::PIXEL_PACK_BUFFER, 0);
+        }
+        self.pending.push_back((buffer, self.captured));
+        self.captured += 1;
+        Ok(())
+    }
+
+    /// Maps `buffer` and hands its pixels to the writer thread (dropped if it is late, unless `wait`)
+    fn send(&mut self, buffer: usize, index: usize, wait: bool) -> Result<()> {
+        let size = (self.stride * self.size.1) as usize;
+        let pixels = self.buffers[buffer].read::<u8>(gl::PIXEL_PACK_BUFFER, size);
+        unsafe {
+            self.gl.BindBuffer(gl::PIXEL_PACK_BUFFER, 0);
+        }
+        let pixels = pixels.with_context(|| format!("Failed to read back frame {}", index))?;
+        let frame = RawFrame { index, pixels };
+        let sender = self.sender.as_ref().expect("capture already finished");
+        let sent = if wait {
+            sender.send(frame).is_ok()
+        } else {
+            match sender.try_send(frame) {
+                Ok(()) => true,
+                Err(TrySendError::Full(_)) => {
+                    self.dropped += 1;
+                    true
+                }
+                Err(TrySendError::Disconnected(_)) => false,
+            }
+        };
+        if sent {
+            Ok(())
+        } else {
+            // the writer thread stopped on an error
+            self.join().and(Err(anyhow!("Frame writer stopped")))
+        }
+    }
+
+    fn join(&mut self) -> Result<usize> {
+        self.sender = None; // ends the writer loop
+        match self.writer.take() {
+            Some(writer) => writer
+                .join()
+                .map_err(|_| anyhow!("Frame writer panicked"))?,
+            None => Ok(0),
+        }
+    }
+
+    /// Writes the frames still in flight and waits for the writer thread
+    pub fn finish(mut self) -> Result<CaptureReport> {
+        while let Some((buffer, index)) = self.pending.pop_front() {
+            self.send(buffer, index, true)?;
+        }
+        let written = self.join()?;
+        Ok(CaptureReport {
+            written,
+            dropped: self.dropped,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn y4m_frames_should_hold_the_three_planes() {
+        let path = std::env::temp_dir().join("life_of_boids_capture_test.y4m");
+        let config = CaptureConfig {
+            path: path.clone(),
+            fps: 30,
+            ..CaptureConfig::default()
+        };
+        assert_eq!(CaptureFormat::from_path(&path), CaptureFormat::Y4m);
+        let mut image = RgbImage::new(2, 1);
+        image.put_pixel(0, 0, image::Rgb([255, 255, 255]));
+        {
+            let mut sink = FrameSink::create(&config).unwrap();
+            sink.write(0, &image).unwrap();
+            sink.write(1, &image).unwrap();
+            sink.finish().unwrap();
+        }
+        let bytes = std::fs::read(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        let header = b"YUV4MPEG2 W2 H1 F30:1 Ip A1:1 C444\n";
+        assert_eq!(&bytes[..header.len()], header);
+        // white then black pixel: Y, Cb and Cr planes
+        let frame = [b"FRAME\n".as_ref(), &[235, 16, 128, 128, 128, 128]].concat();
+        assert_eq!(&bytes[header.len()..], [frame.clone(), frame].concat());
+    }
+
+    #[test]
+    fn png_frames_should_be_numbered() {
+        let dir = std::env::temp_dir().join("life_of_boids_capture_test");
+        let config = CaptureConfig {
+            path: dir.clone(),
+            ..CaptureConfig::default()
+        };
+        assert_eq!(CaptureFormat::from_path(&dir), CaptureFormat::Png);
+        let mut sink = FrameSink::create(&config).unwrap();
+        sink.write(12, &RgbImage::new(3, 2)).unwrap();
+        let frame = dir.join("frame_000012.png");
+        let loaded = image::open(&frame).unwrap().to_rgb8();
+        std::fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(loaded.dimensions(), (3, 2));
+    }
+}
@@ -42,6 +42,12 @@ pub struct FpsCounter {
     rolling_dt_sum: Duration,
 }
 
+impl Default for FpsCounter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl FpsCounter {
     // TODO: Would be more accurate to store instances
     // See: https://github.com/PistonDevelopers/fps_counter
@@ -2,17 +2,24 @@
 
 // private sub-modules
 mod buffer;
+mod font;
 mod image;
 mod program_unit;
 mod shader_program;
 mod support;
+mod texture;
 mod vertex_array;
 mod window;
 
 // re-export
-pub use self::image::save_image;
+pub use self::image::{image_from_gl_pixels, save_image};
+pub use buffer::{Buffer, UploadMode};
+pub use font::{FontAtlas, GLYPH_SIZE};
 pub use program_unit::ProgramUnit;
+pub use shader_program::ShaderProgram;
 pub use support::gl;
+pub use texture::Texture;
+pub use vertex_array::VertexArray;
 pub use window::clear_screen;
 pub use window::get_window_size_info;
 pub use window::gl_init;
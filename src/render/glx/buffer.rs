@@ -0,0 +1,138 @@
+use super::support::gl;
+use anyhow::{bail, Result};
+use std::cell::Cell;
+use std::rc::Rc;
+
+/// How data is sent to a buffer updated at each frame
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum UploadMode {
+    /// `BufferData` with the new data: storage is reallocated at each upload
+    Reallocate,
+    /// storage is kept while large enough: it is orphaned (`BufferData` with a null pointer,
+    /// so that the driver doesn't wait for pending draws) then filled using `BufferSubData`
+    Orphan,
+}
+
+// https://learnopengl.com/Getting-started/Hello-Triangle
+// https://github.com/bwasty/learn-opengl-rs/tree/master/src/_1_getting_started (warning out-of-date)
+// https://www.khronos.org/opengl/wiki/Buffer_Object_Streaming
+pub struct Buffer {
+    buffer_id: gl::types::GLuint,
+    gl: Rc<gl::Gl>,
+    capacity: Cell<usize>, // allocated size (in bytes)
+}
+
+impl Buffer {
+    pub fn new(gl: Rc<gl::Gl>) -> Buffer {
+        let mut buffer_id = 0;
+        unsafe {
+            gl.GenBuffers(1, &mut buffer_id);
+        }
+        Buffer {
+            buffer_id,
+            gl,
+            capacity: Cell::new(0),
+        }
+    }
+
+    pub fn bind(&self, target: gl::types::GLenum) {
+        unsafe {
+            self.gl.BindBuffer(target, self.buffer_id);
+        }
+    }
+
+    /// Binds the buffer to an indexed target (e.g. transform feedback output)
+    pub fn bind_base(&self, target: gl::types::GLenum, index: gl::types::GLuint) {
+        unsafe {
+            self.gl.BindBufferBase(target, index, self.buffer_id);
+        }
+    }
+
+    /// (Re)allocates the buffer with `data` using the given usage hint
+    pub fn set_data<T>(&self, target: gl::types::GLenum, data: &[T], usage: gl::types::GLenum) {
+        let size = std::mem::size_of_val(data);
+        self.bind(target);
+        unsafe {
+            self.gl.BufferData(
+                target,
+                size as gl::types::GLsizeiptr,
+                data.as_ptr() as *const _,
+                usage,
+            );
+        }
+        self.capacity.set(size);
+    }
+
+    /// (Re)allocates `size` bytes of uninitialized storage (e.g. for pixels read back by the GPU)
+    pub fn allocate(&self, target: gl::types::GLenum, size: usize, usage: gl::types::GLenum) {
+        self.bind(target);
+        unsafe {
+            self.gl.BufferData(
+                target,
+                size as gl::types::GLsizeiptr,
+                std::ptr::null(),
+                usage,
+            );
+        }
+        self.capacity.set(size);
+    }
+
+    /// Reads back the `count` first elements of the buffer
+    pub fn read<T: Copy>(&self, target: gl::types::GLenum, count: usize) -> Result<Vec<T>> {
+        let size = count * std::mem::size_of::<T>();
+        assert!(size <= self.capacity.get());
+        let mut data = Vec::<T>::with_capacity(count);
+        self.bind(target);
+        unsafe {
+            let ptr =
+                self.gl
+                    .MapBufferRange(target, 0, size as gl::types::GLsizeiptr, gl::MAP_READ_BIT)
+                    as *const T;
+            if ptr.is_null() {
+                bail!("Failed to map {} bytes of buffer {}", size, self.buffer_id);
+            }
+            std::ptr::copy_nonoverlapping(ptr, data.as_mut_ptr(), count);
+            data.set_len(count);
+            self.gl.UnmapBuffer(target);
+        }
+        Ok(data)
+    }
+
+    /// Uploads `data` (frequently updated) into the buffer bound to `target`
+    pub fn stream<T>(&self, target: gl::types::GLenum, data: &[T], mode: UploadMode) {
+        let size = std::mem::size_of_val(data);
+        self.bind(target);
+        unsafe {
+            if mode == UploadMode::Reallocate || size > self.capacity.get() {
+                self.gl.BufferData(
+                    target,
+                    size as gl::types::GLsizeiptr,
+                    data.as_ptr() as *const _,
+                    gl::STREAM_DRAW,
+                );
+                self.capacity.set(size);
+            } else {
+                self.gl.BufferData(
+                    target,
+                    self.capacity.get() as gl::types::GLsizeiptr,
+                    std::ptr::null(),
+                    gl::STREAM_DRAW,
+                );
+                self.gl.BufferSubData(
+                    target,
+                    0,
+                    size as gl::types::GLsizeiptr,
+                    data.as_ptr() as *const _,
+                );
+            }
+        }
+    }
+}
+
+impl Drop for Buffer {
+    fn drop(&mut self) {
+        unsafe {
+            self.gl.DeleteBuffers(1, &self.buffer_id);
+        }
+    }
+}
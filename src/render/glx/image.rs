@@ -1,7 +1,8 @@
-use crate::glx::gl;
+use crate::render::glx::gl;
 use anyhow::Result;
 use glutin::dpi::PhysicalSize;
 use glutin::window::Window;
+use image::RgbImage;
 use std::path::PathBuf;
 use std::rc::Rc;
 
@@ -11,8 +12,7 @@ pub fn save_image(gl: Rc<gl::Gl>, filepath: &PathBuf, window: &Window) -> Result
     let mut stride = nr_channels * width;
     stride += if stride % 4 != 0 { 4 - stride % 4 } else { 0 };
     let buffer_size = stride * height;
-    let mut data = Vec::<u8>::with_capacity(buffer_size as usize);
-    data.resize(data.capacity(), 0);
+    let data = vec![0; buffer_size as usize];
 
     unsafe {
         gl.PixelStorei(gl::PACK_ALIGNMENT, 4);
@@ -28,7 +28,12 @@ pub fn save_image(gl: Rc<gl::Gl>, filepath: &PathBuf, window: &Window) -> Result
         );
     }
 
-    let mut img = image::RgbImage::new(width, height);
+    Ok(image_from_gl_pixels(&data, width, height, stride).save(filepath)?)
+}
+
+/// Image from RGB rows read by `ReadPixels` (bottom row first, rows padded to `stride` bytes)
+pub fn image_from_gl_pixels(data: &[u8], width: u32, height: u32, stride: u32) -> RgbImage {
+    let mut img = RgbImage::new(width, height);
     for y in 0..height {
         let mut pos = (y * stride) as usize;
         for x in 0..width {
@@ -41,6 +46,5 @@ pub fn save_image(gl: Rc<gl::Gl>, filepath: &PathBuf, window: &Window) -> Result
             *img.get_pixel_mut(x, height - 1 - y) = image::Rgb([red, green, blue]);
         }
     }
-
-    Ok(img.save(filepath)?)
+    img
 }
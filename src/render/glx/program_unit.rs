@@ -1,7 +1,7 @@
-use super::buffer::Buffer;
+use super::buffer::{Buffer, UploadMode};
 use super::support::gl;
 use super::vertex_array::VertexArray;
-use crate::glx::shader_program::ShaderProgram;
+use crate::render::glx::shader_program::ShaderProgram;
 use anyhow::{anyhow, Result};
 use std::collections::HashMap;
 use std::rc::Rc;
@@ -21,7 +21,7 @@ impl ProgramUnit {
         fragment_shader_src: &'static [u8],
     ) -> Result<Self> {
         Ok(ProgramUnit {
-            program: ShaderProgram::new(&gl, vertex_shader_src, fragment_shader_src)?,
+            program: ShaderProgram::new(gl, vertex_shader_src, fragment_shader_src)?,
             vbo: Buffer::new(gl.clone()),
             vao: VertexArray::new(gl.clone()),
             uniforms: HashMap::default(),
@@ -48,6 +48,11 @@ impl ProgramUnit {
         }
     }
 
+    /// Uploads the vertices to draw into the vertex buffer of the program
+    pub fn stream_vertices<T>(&self, vertices: &[T], mode: UploadMode) {
+        self.vbo.stream(gl::ARRAY_BUFFER, vertices, mode);
+    }
+
     pub fn add_uniform(&mut self, name: &'static str) -> Result<gl::types::GLint> {
         let id = self.program.get_uniform_location(name)?;
         self.uniforms.insert(name, id);
@@ -72,7 +77,6 @@ impl ProgramUnit {
         }
     }
 
-    #[allow(dead_code)]
     pub fn get_attribute(&self, name: &'static str) -> Result<gl::types::GLint> {
         match self.attributes.get(name) {
             Some(id) => Ok(*id),
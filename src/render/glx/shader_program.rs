@@ -24,11 +24,22 @@ impl ShaderProgram {
         gl: &Rc<gl::Gl>,
         vertex_shader_src: &'static [u8],
         fragment_shader_src: &'static [u8],
+    ) -> Result<ShaderProgram> {
+        Self::with_feedback_varyings(gl, vertex_shader_src, fragment_shader_src, &[])
+    }
+
+    /// Creates a program whose vertex shader outputs `varyings` are captured
+    /// (interleaved, in this order) when transform feedback is active
+    pub fn with_feedback_varyings(
+        gl: &Rc<gl::Gl>,
+        vertex_shader_src: &'static [u8],
+        fragment_shader_src: &'static [u8],
+        varyings: &[&str],
     ) -> Result<ShaderProgram> {
         unsafe {
-            let vertex_shader = compile_shader(&gl, vertex_shader_src, gl::VERTEX_SHADER)?;
-            let fragment_shader = compile_shader(&gl, fragment_shader_src, gl::FRAGMENT_SHADER)?;
-            let program = link_program(&gl, vertex_shader, fragment_shader)?;
+            let vertex_shader = compile_shader(gl, vertex_shader_src, gl::VERTEX_SHADER)?;
+            let fragment_shader = compile_shader(gl, fragment_shader_src, gl::FRAGMENT_SHADER)?;
+            let program = link_program(gl, vertex_shader, fragment_shader, varyings)?;
             gl.DeleteShader(vertex_shader);
             gl.DeleteShader(fragment_shader);
             Ok(ShaderProgram {
@@ -44,7 +55,7 @@ impl ShaderProgram {
         }
     }
 
-    pub(super) fn get_attrib_location(&self, name: &str) -> Result<gl::types::GLint> {
+    pub fn get_attrib_location(&self, name: &str) -> Result<gl::types::GLint> {
         let c_name = CString::new(name).unwrap();
         unsafe {
             let location = self.gl.GetAttribLocation(self.program_id, c_name.as_ptr());
@@ -59,7 +70,7 @@ impl ShaderProgram {
         }
     }
 
-    pub(super) fn get_uniform_location(&self, name: &str) -> Result<gl::types::GLint> {
+    pub fn get_uniform_location(&self, name: &str) -> Result<gl::types::GLint> {
         let c_name = CString::new(name).unwrap();
         unsafe {
             let location = self.gl.GetUniformLocation(self.program_id, c_name.as_ptr());
@@ -78,7 +89,7 @@ impl ShaderProgram {
 impl Drop for ShaderProgram {
     fn drop(&mut self) {
         unsafe {
-            self.gl.DeleteShader(self.program_id);
+            self.gl.DeleteProgram(self.program_id);
         }
     }
 }
@@ -101,8 +112,7 @@ unsafe fn compile_shader(
     if success != i32::from(gl::TRUE) {
         let mut len = 0;
         gl.GetShaderiv(shader, gl::INFO_LOG_LENGTH, &mut len);
-        let mut info_log = Vec::with_capacity(len as usize);
-        info_log.set_len(len as usize - 1); // -1 to skip trialing null character
+        let mut info_log: Vec<gl::types::GLchar> = vec![0; len as usize]; // includes trailing null character
         gl.GetShaderInfoLog(
             shader,
             len,
@@ -122,10 +132,22 @@ unsafe fn link_program(
     gl: &gl::Gl,
     vertex_shader: gl::types::GLuint,
     fragment_shader: gl::types::GLuint,
+    varyings: &[&str],
 ) -> Result<gl::types::GLuint, ShaderError> {
     let program = gl.CreateProgram();
     gl.AttachShader(program, vertex_shader);
     gl.AttachShader(program, fragment_shader);
+    if !varyings.is_empty() {
+        // must be declared before linking
+        let c_varyings: Vec<CString> = varyings.iter().map(|v| CString::new(*v).unwrap()).collect();
+        let c_ptrs: Vec<*const gl::types::GLchar> = c_varyings.iter().map(|v| v.as_ptr()).collect();
+        gl.TransformFeedbackVaryings(
+            program,
+            c_ptrs.len() as gl::types::GLsizei,
+            c_ptrs.as_ptr(),
+            gl::INTERLEAVED_ATTRIBS,
+        );
+    }
     gl.LinkProgram(program);
 
     // Check link errors
@@ -134,8 +156,7 @@ unsafe fn link_program(
     if success != i32::from(gl::TRUE) {
         let mut len = 0;
         gl.GetProgramiv(program, gl::INFO_LOG_LENGTH, &mut len);
-        let mut info_log = Vec::with_capacity(len as usize);
-        info_log.set_len(len as usize - 1); // -1 to skip trialing null character
+        let mut info_log: Vec<gl::types::GLchar> = vec![0; len as usize]; // includes trailing null character
         gl.GetProgramInfoLog(
             program,
             len,
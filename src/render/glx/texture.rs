@@ -0,0 +1,93 @@
+use super::support::gl;
+use std::rc::Rc;
+
+pub struct Texture {
+    texture_id: gl::types::GLuint,
+    gl: Rc<gl::Gl>,
+}
+
+impl Texture {
+    pub fn new(gl: Rc<gl::Gl>) -> Texture {
+        let mut texture_id = 0;
+        unsafe {
+            gl.GenTextures(1, &mut texture_id);
+        }
+        Texture { texture_id, gl }
+    }
+
+    /// Binds the texture to the given texture unit
+    pub fn bind(&self, unit: gl::types::GLuint) {
+        unsafe {
+            self.gl.ActiveTexture(gl::TEXTURE0 + unit);
+            self.gl.BindTexture(gl::TEXTURE_2D, self.texture_id);
+        }
+    }
+
+    /// Uploads a single channel texture (one byte per pixel, rows are tightly packed)
+    pub fn upload_r8(&self, width: u32, height: u32, data: &[u8]) {
+        assert_eq!(data.len(), (width * height) as usize);
+        self.upload(
+            width,
+            height,
+            gl::R8,
+            gl::UNSIGNED_BYTE,
+            data.as_ptr() as *const _,
+        );
+    }
+
+    /// Uploads a single channel float texture (rows are tightly packed)
+    pub fn upload_r32f(&self, width: u32, height: u32, data: &[f32]) {
+        assert_eq!(data.len(), (width * height) as usize);
+        self.upload(
+            width,
+            height,
+            gl::R32F,
+            gl::FLOAT,
+            data.as_ptr() as *const _,
+        );
+    }
+
+    fn upload(
+        &self,
+        width: u32,
+        height: u32,
+        internal_format: gl::types::GLenum,
+        data_type: gl::types::GLenum,
+        data: *const gl::types::GLvoid,
+    ) {
+        self.bind(0);
+        unsafe {
+            self.gl.PixelStorei(gl::UNPACK_ALIGNMENT, 1);
+            self.gl.TexImage2D(
+                gl::TEXTURE_2D,
+                0,
+                internal_format as gl::types::GLint,
+                width as gl::types::GLsizei,
+                height as gl::types::GLsizei,
+                0,
+                gl::RED,
+                data_type,
+                data,
+            );
+            // pixel-exact sampling
+            let parameters = [
+                (gl::TEXTURE_MIN_FILTER, gl::NEAREST),
+                (gl::TEXTURE_MAG_FILTER, gl::NEAREST),
+                (gl::TEXTURE_WRAP_S, gl::CLAMP_TO_EDGE),
+                (gl::TEXTURE_WRAP_T, gl::CLAMP_TO_EDGE),
+            ];
+            for (name, value) in parameters.iter() {
+                self.gl
+                    .TexParameteri(gl::TEXTURE_2D, *name, *value as gl::types::GLint);
+            }
+        }
+    }
+}
+
+impl Drop for Texture {
+    fn drop(&mut self) {
+        unsafe {
+            self.gl.DeleteTextures(1, &self.texture_id);
+        }
+    }
+}
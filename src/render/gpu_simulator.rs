@@ -0,0 +1,149 @@
+use crate::points_simulator::{PointsSimulator, TIME_STEP};
+use crate::render::glx::gl;
+use crate::render::shader_programs::step_points::StepPointsProgram;
+use crate::render::PointsSource;
+use anyhow::{anyhow, Result};
+use std::fmt;
+use std::rc::Rc;
+
+// Maximum difference accepted between CPU and GPU steps
+const POSITION_TOLERANCE: f32 = 1e-2;
+const VELOCITY_TOLERANCE: f32 = 1e-2;
+
+/// GPU backend: points stay resident on the GPU and are advanced by a transform feedback
+/// shader following the same rules as [PointsSimulator::update_random_walk].
+/// The CPU simulator keeps track of the step count; its points are only updated on [GpuSimulator::sync].
+pub struct GpuSimulator {
+    program: StepPointsProgram,
+    background_generation: u64, // of the uploaded background values
+}
+
+#[derive(Debug)]
+pub struct ValidationReport {
+    pub points: usize,
+    pub mismatches: usize,
+    pub max_position_error: f32,
+    pub max_velocity_error: f32,
+}
+
+impl fmt::Display for ValidationReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "GPU validation: {}/{} mismatches (max position error {:.2e}, max velocity error {:.2e})",
+            self.mismatches, self.points, self.max_position_error, self.max_velocity_error
+        )
+    }
+}
+
+impl GpuSimulator {
+    /// Fails when transform feedback is not available or when species (or behaviours, predators, obstacles, fields, lifecycle) use rules
+    /// not implemented on the GPU (the CPU backend should be used instead)
+    pub fn new(gl: Rc<gl::Gl>, simulator: &PointsSimulator) -> Result<Self> {
+        if !simulator.species().random_walk_only() {
+            return Err(anyhow!(
+                "species '{}' use neighbour rules, only the random walk runs on the GPU",
+                simulator.species().name
+            ));
+        }
+        if !simulator.behaviors().is_random_walk_only() {
+            return Err(anyhow!(
+                "behaviours '{}' are not supported by the GPU backend",
+                simulator.behaviors().names()
+            ));
+        }
+        if !simulator.predators().is_empty() || !simulator.obstacles().is_empty() {
+            return Err(anyhow!(
+                "predators and obstacles are not supported by the GPU backend"
+            ));
+        }
+        if !simulator.lifecycle().is_static() {
+            return Err(anyhow!(
+                "emitters and death conditions are not supported by the GPU backend"
+            ));
+        }
+        if !simulator.fields_are_brightness_speed() {
+            return Err(anyhow!(
+                "fields '{}' are not supported by the GPU backend",
+                simulator.field_effects().name
+            ));
+        }
+        let mut program = StepPointsProgram::new(gl, simulator.space_size())?;
+        program.upload_points(simulator.points());
+        let (width, height, brightness) = simulator.brightness_map();
+        program.upload_brightness(width, height, &brightness);
+        Ok(GpuSimulator {
+            program,
+            background_generation: simulator.background_generation(),
+        })
+    }
+
+    /// Restarts from the current CPU points (e.g. after a reset)
+    pub fn reset(&mut self, simulator: &PointsSimulator) {
+        self.program.upload_points(simulator.points());
+    }
+
+    /// Uploads the background values again when they changed (colour conversion, animation)
+    fn sync_background(&mut self, simulator: &PointsSimulator) {
+        if self.background_generation != simulator.background_generation() {
+            let (width, height, brightness) = simulator.brightness_map();
+            self.program.upload_brightness(width, height, &brightness);
+            self.background_generation = simulator.background_generation();
+        }
+    }
+
+    pub fn update(&mut self, simulator: &mut PointsSimulator) {
+        self.sync_background(simulator);
+        self.program.step(simulator.step() as u32, TIME_STEP);
+        simulator.advance_step();
+    }
+
+    /// Copies the GPU points back to the CPU simulator
+    pub fn sync(&self, simulator: &mut PointsSimulator) -> Result<()> {
+        simulator.set_points(self.program.download_points()?);
+        Ok(())
+    }
+
+    pub fn points(&self) -> PointsSource<'_> {
+        PointsSource::Device(self.program.points_buffer(), self.program.count())
+    }
+
+    /// Runs one step on both backends from the same state and compares the results;
+    /// the simulator and this backend are left on the CPU result.
+    pub fn validate(&mut self, simulator: &mut PointsSimulator) -> Result<ValidationReport> {
+        let seed = simulator.step() as u32;
+        self.sync_background(simulator);
+        self.program.upload_points(simulator.points());
+        self.program.step(seed, TIME_STEP);
+        let gpu_points = self.program.download_points()?;
+        simulator.update_random_walk(seed);
+        self.program.upload_points(simulator.points());
+
+        let (width, height) = simulator.space_size();
+        // distance in a periodic space
+        let periodic = |d: f32, size: f32| {
+            let d = d.abs() % size;
+            d.min(size - d)
+        };
+
+        let mut report = ValidationReport {
+            points: simulator.points().len(),
+            mismatches: 0,
+            max_position_error: 0.0,
+            max_velocity_error: 0.0,
+        };
+        for (cpu, gpu) in simulator.points().iter().zip(gpu_points.iter()) {
+            let position_error = periodic(cpu.position.x - gpu.position.x, width)
+                .max(periodic(cpu.position.y - gpu.position.y, height));
+            let velocity_error = (cpu.velocity.x - gpu.velocity.x)
+                .abs()
+                .max((cpu.velocity.y - gpu.velocity.y).abs());
+            if position_error > POSITION_TOLERANCE || velocity_error > VELOCITY_TOLERANCE {
+                report.mismatches += 1;
+            }
+            report.max_position_error = report.max_position_error.max(position_error);
+            report.max_velocity_error = report.max_velocity_error.max(velocity_error);
+        }
+        Ok(report)
+    }
+}
@@ -0,0 +1,158 @@
+use crate::analysis::StepStatistics;
+use crate::flocks::FlockTracker;
+use crate::point::Point;
+use crate::render::shader_programs::overlay::{OverlayBuilder, OverlayColor};
+use crate::render::shader_programs::text::{TextBatch, TextColor, TextPosition};
+use std::time::{Duration, Instant};
+
+const MARGIN: f32 = 10.0;
+const PADDING: f32 = 8.0;
+const TEXT_SCALE: f32 = 2.0;
+const NOTIFICATION_DURATION: Duration = Duration::from_secs(3);
+
+const PANEL_COLOR: OverlayColor = [0.0, 0.0, 0.0, 0.6];
+const TEXT_COLOR: TextColor = [1.0, 1.0, 1.0, 0.9];
+const NOTIFICATION_COLOR: TextColor = [1.0, 0.8, 0.2, 1.0];
+const LABEL_COLOR: TextColor = [0.4, 1.0, 1.0, 1.0];
+const LABELLED_POINTS: usize = 16;
+
+#[derive(Debug, Default, Copy, Clone)]
+pub struct HudStats<'a> {
+    pub fps: f32,
+    pub point_count: usize,
+    pub step: u64,
+    pub sim_time: f32,
+    pub backend: &'static str,
+    pub species: &'a str,
+    pub upload_mode: &'static str,
+    pub submit_time: f32, // CPU time submitting the upload (ms)
+    /// shown when the analysis is enabled
+    pub statistics: Option<&'a StepStatistics>,
+    /// shown when the flocks are tracked
+    pub flocks: Option<&'a FlockTracker>,
+}
+
+/// Head-up display drawn on top of the simulation
+pub struct Hud {
+    pub visible: bool,
+    /// labels the first points with their index (to follow individual boids)
+    pub show_labels: bool,
+    notification: Option<(String, Instant)>,
+}
+
+impl Default for Hud {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Hud {
+    pub fn new() -> Self {
+        Hud {
+            visible: true,
+            show_labels: false,
+            notification: None,
+        }
+    }
+
+    pub fn toggle(&mut self) {
+        self.visible = !self.visible;
+    }
+
+    pub fn toggle_labels(&mut self) {
+        self.show_labels = !self.show_labels;
+    }
+
+    /// Displays a short message below the HUD panel for a few seconds
+    pub fn notify<S: Into<String>>(&mut self, message: S) {
+        let message = message.into();
+        println!("{}", message);
+        self.notification = Some((message, Instant::now()));
+    }
+
+    pub fn build(
+        &self,
+        stats: &HudStats,
+        points: &[Point],
+        overlay: &mut OverlayBuilder,
+        text: &mut TextBatch,
+    ) {
+        if self.show_labels {
+            for (index, p) in points.iter().take(LABELLED_POINTS).enumerate() {
+                text.add(
+                    &format!("#{}", index),
+                    TextPosition::World(p.position.x, p.position.y),
+                    1.0,
+                    LABEL_COLOR,
+                );
+            }
+        }
+
+        if !self.visible {
+            return;
+        }
+
+        let mut content = format!(
+            "FPS     {:.1}\npoints  {}\nstep    {}\ntime    {:.2}\nbackend {}\nspecies {}\nupload   {:.2} ms CPU ({})",
+            stats.fps,
+            stats.point_count,
+            stats.step,
+            stats.sim_time,
+            stats.backend,
+            stats.species,
+            stats.submit_time,
+            stats.upload_mode
+        );
+        if let Some(statistics) = stats.statistics {
+            content += &format!(
+                "\npolarization {:.3}\nmilling      {:.3}\nspeed        {:.2} +/- {:.2}\nnearest      {:.2}\nclusters     {} (largest {})\ndensity      {:.1} (var {:.1})",
+                statistics.polarization,
+                statistics.milling,
+                statistics.mean_speed,
+                statistics.speed_std,
+                statistics.nearest_neighbour,
+                statistics.cluster_count,
+                statistics.cluster_sizes.first().copied().unwrap_or(0),
+                statistics.density_mean,
+                statistics.density_variance,
+            );
+        }
+        if let Some(tracker) = stats.flocks {
+            let total = tracker.total_events();
+            content += &format!(
+                "\nflocks       {} (largest {})\nsplits       {} merges {}\nbirths       {} deaths {}",
+                tracker.flocks().len(),
+                tracker.flocks().first().map_or(0, |f| f.size),
+                total.splits,
+                total.merges,
+                total.births,
+                total.deaths,
+            );
+        }
+        let (width, height) = TextBatch::text_size(&content, TEXT_SCALE);
+        overlay.rect(
+            MARGIN,
+            MARGIN,
+            width + 2.0 * PADDING,
+            height + 2.0 * PADDING,
+            PANEL_COLOR,
+        );
+        text.add(
+            &content,
+            TextPosition::Screen(MARGIN + PADDING, MARGIN + PADDING),
+            TEXT_SCALE,
+            TEXT_COLOR,
+        );
+
+        if let Some((message, since)) = &self.notification {
+            if since.elapsed() < NOTIFICATION_DURATION {
+                text.add(
+                    message,
+                    TextPosition::Screen(MARGIN, 2.0 * (MARGIN + PADDING) + height),
+                    TEXT_SCALE,
+                    NOTIFICATION_COLOR,
+                );
+            }
+        }
+    }
+}
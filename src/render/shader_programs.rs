@@ -0,0 +1,5 @@
+pub mod lines;
+pub mod overlay;
+pub mod points;
+pub mod step_points;
+pub mod text;
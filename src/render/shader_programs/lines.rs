@@ -1,5 +1,5 @@
-use crate::glx::gl;
-use crate::glx::{vertex_transform_2d, ProgramUnit};
+use crate::render::glx::gl;
+use crate::render::glx::{vertex_transform_2d, ProgramUnit, UploadMode};
 use anyhow::Result;
 use cgmath::{Matrix, Matrix3};
 use std::rc::Rc;
@@ -13,8 +13,8 @@ impl LinesRenderProgram {
         Ok(LinesRenderProgram {
             program: ProgramUnit::new(
                 &gl,
-                &crate::shader_programs::lines::VS_SRC,
-                &crate::shader_programs::lines::FS_SRC,
+                crate::render::shader_programs::lines::VS_SRC,
+                crate::render::shader_programs::lines::FS_SRC,
             )?,
         })
     }
@@ -74,12 +74,8 @@ impl LinesRenderProgram {
                 gl::FALSE,
                 transform.as_ptr(),
             );
-            gl.BufferData(
-                gl::ARRAY_BUFFER,
-                (vertex_data.len() * std::mem::size_of::<f32>()) as gl::types::GLsizeiptr,
-                vertex_data.as_ptr() as *const _,
-                gl::STREAM_DRAW,
-            );
+            self.program
+                .stream_vertices(&vertex_data, UploadMode::Orphan);
             // https://docs.gl/gl3/glDrawArrays
             // https://www.khronos.org/opengl/wiki/Primitive (use gl::LINE_LOOP to make lines)
             gl.DrawArrays(gl::LINES, 0, (vertex_data.len() / 5) as i32);
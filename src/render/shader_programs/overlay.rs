@@ -0,0 +1,143 @@
+use crate::render::glx::gl;
+use crate::render::glx::{vertex_transform_2d, ProgramUnit, UploadMode};
+use anyhow::Result;
+use cgmath::{Matrix, Matrix3};
+use std::rc::Rc;
+
+pub type OverlayColor = [f32; 4];
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug)]
+pub struct OverlayVertex {
+    position: [f32; 2],
+    color: OverlayColor,
+}
+
+/// Collects the triangles of an overlay (panels, indicators) in screen coordinates
+/// (pixels, origin at top-left corner)
+#[derive(Default)]
+pub struct OverlayBuilder {
+    vertices: Vec<OverlayVertex>,
+}
+
+impl OverlayBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn vertices(&self) -> &[OverlayVertex] {
+        &self.vertices
+    }
+
+    pub fn triangle(&mut self, corners: [[f32; 2]; 3], color: OverlayColor) {
+        for &position in corners.iter() {
+            self.vertices.push(OverlayVertex { position, color });
+        }
+    }
+
+    pub fn rect(&mut self, x: f32, y: f32, width: f32, height: f32, color: OverlayColor) {
+        let corners = [
+            [x, y],
+            [x + width, y],
+            [x + width, y + height],
+            [x, y + height],
+        ];
+        for &i in &[0, 1, 2, 0, 2, 3] {
+            self.vertices.push(OverlayVertex {
+                position: corners[i],
+                color,
+            });
+        }
+    }
+}
+
+pub struct OverlayRenderProgram {
+    program: ProgramUnit,
+}
+
+impl OverlayRenderProgram {
+    pub fn new(gl: Rc<gl::Gl>) -> Result<Self> {
+        Ok(OverlayRenderProgram {
+            program: ProgramUnit::new(&gl, VS_SRC, FS_SRC)?,
+        })
+    }
+
+    pub fn initialize(&mut self) -> Result<()> {
+        let gl = self.program.gl();
+
+        self.program.prepare();
+        self.program.add_uniform("transform")?;
+        let pos_attrib = self.program.add_attribute("vPos")?;
+        let color_attrib = self.program.add_attribute("vCol")?;
+
+        unsafe {
+            gl.VertexAttribPointer(
+                pos_attrib as gl::types::GLuint,
+                2,
+                gl::FLOAT,
+                gl::FALSE,
+                std::mem::size_of::<OverlayVertex>() as gl::types::GLsizei,
+                memoffset::offset_of!(OverlayVertex, position) as *const gl::types::GLvoid,
+            );
+            gl.VertexAttribPointer(
+                color_attrib as gl::types::GLuint,
+                4,
+                gl::FLOAT,
+                gl::FALSE,
+                std::mem::size_of::<OverlayVertex>() as gl::types::GLsizei,
+                memoffset::offset_of!(OverlayVertex, color) as *const gl::types::GLvoid,
+            );
+        };
+        Ok(())
+    }
+
+    pub fn render(&self, vertices: &[OverlayVertex], size: (u32, u32)) -> Result<()> {
+        if vertices.is_empty() {
+            return Ok(());
+        }
+        let gl = self.program.gl();
+
+        let (width, height) = size;
+        let transform: Matrix3<f32> = vertex_transform_2d(width as f32, height as f32);
+
+        self.program.activate();
+        unsafe {
+            gl.Enable(gl::BLEND);
+            gl.BlendFunc(gl::SRC_ALPHA, gl::ONE_MINUS_SRC_ALPHA);
+            gl.UniformMatrix3fv(
+                self.program.get_uniform("transform")?,
+                1,
+                gl::FALSE,
+                transform.as_ptr(),
+            );
+            self.program.stream_vertices(vertices, UploadMode::Orphan);
+            gl.DrawArrays(gl::TRIANGLES, 0, vertices.len() as i32);
+            gl.Disable(gl::BLEND);
+        }
+        Ok(())
+    }
+}
+
+// Overlay Vertex Shader (screen coordinates)
+const VS_SRC: &[u8] = b"
+#version 330 core
+uniform mat3 transform;
+in vec2 vPos;
+in vec4 vCol;
+out vec4 color;
+void main()
+{
+    gl_Position = vec4(transform * vec3(vPos, 1.0), 1.0);
+    color = vCol;
+}
+\0";
+
+const FS_SRC: &[u8] = b"
+#version 330 core
+
+in vec4 color;
+out vec4 fragColor;
+void main() {
+    fragColor = color;
+}
+\0";
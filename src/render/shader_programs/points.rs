@@ -0,0 +1,209 @@
+use std::cell::Cell;
+use std::rc::Rc;
+use std::time::Instant;
+
+use anyhow::Result;
+use cgmath::{Matrix, Matrix3};
+
+use crate::point::Point;
+use crate::render::glx::gl;
+use crate::render::glx::{vertex_transform_2d, Buffer, ProgramUnit, UploadMode, WindowSizeInfo};
+use crate::species::MAX_SPECIES;
+
+pub struct PointsRenderProgram {
+    program: ProgramUnit,
+    transform: Matrix3<f32>,
+    point_size: f32,
+    pub max_speed: f32,
+    pub upload_mode: UploadMode,
+    species_colors: Vec<[f32; 3]>,
+    submit_time: Cell<f32>, // smoothed CPU duration of the upload submission (ms)
+}
+
+// Smoothing factor of the submit time measure (exponential moving average)
+const SUBMIT_TIME_SMOOTHING: f32 = 0.05;
+
+impl PointsRenderProgram {
+    pub fn new(gl: Rc<gl::Gl>, size: WindowSizeInfo) -> Result<Self> {
+        Ok(PointsRenderProgram {
+            program: ProgramUnit::new(&gl, VS_SRC, FS_SRC)?,
+            transform: vertex_transform_2d(size.width as f32, size.height as f32),
+            point_size: 1.0,
+            max_speed: 10.0,
+            upload_mode: UploadMode::Orphan,
+            species_colors: vec![[1.0, 1.0, 1.0]],
+            submit_time: Cell::new(0.0),
+        })
+    }
+
+    pub fn initialize(&mut self) -> Result<()> {
+        let gl = self.program.gl();
+
+        self.program.prepare();
+        self.program.add_uniform("transform")?;
+        self.program.add_uniform("pointSize")?;
+        self.program.add_uniform("maxSpeedSquared")?;
+        self.program.add_uniform("speciesColors")?;
+        self.program.add_attribute("position")?;
+        self.program.add_attribute("velocity")?;
+        self.program.add_attribute("species")?;
+        self.set_vertex_layout()?;
+        unsafe {
+            // Allow shader to specify point size
+            gl.Enable(gl::PROGRAM_POINT_SIZE);
+        }
+        Ok(())
+    }
+
+    // Specify the layout of the vertex data (read from the buffer bound to ARRAY_BUFFER)
+    fn set_vertex_layout(&self) -> Result<()> {
+        let gl = self.program.gl();
+        unsafe {
+            gl.VertexAttribPointer(
+                self.program.get_attribute("position")? as gl::types::GLuint,
+                2,
+                gl::FLOAT,
+                gl::FALSE,
+                std::mem::size_of::<Point>() as gl::types::GLsizei,
+                memoffset::offset_of!(Point, position) as *const gl::types::GLvoid,
+            );
+            gl.VertexAttribPointer(
+                self.program.get_attribute("velocity")? as gl::types::GLuint,
+                2,
+                gl::FLOAT,
+                gl::FALSE,
+                std::mem::size_of::<Point>() as gl::types::GLsizei,
+                memoffset::offset_of!(Point, velocity) as *const gl::types::GLvoid,
+            );
+            gl.VertexAttribIPointer(
+                self.program.get_attribute("species")? as gl::types::GLuint,
+                1,
+                gl::UNSIGNED_INT,
+                std::mem::size_of::<Point>() as gl::types::GLsizei,
+                memoffset::offset_of!(Point, species) as *const gl::types::GLvoid,
+            );
+        }
+        Ok(())
+    }
+
+    fn set_uniforms(&self) -> Result<()> {
+        let gl = self.program.gl();
+        unsafe {
+            gl.UniformMatrix3fv(
+                self.program.get_uniform("transform")?,
+                1,
+                gl::FALSE,
+                self.transform.as_ptr(),
+            );
+            gl.Uniform1f(
+                self.program.get_uniform("pointSize")?,
+                self.point_size as gl::types::GLfloat,
+            );
+            gl.Uniform1f(
+                self.program.get_uniform("maxSpeedSquared")?,
+                self.max_speed.powi(2) as gl::types::GLfloat,
+            );
+            gl.Uniform3fv(
+                self.program.get_uniform("speciesColors")?,
+                self.species_colors.len() as gl::types::GLsizei,
+                self.species_colors.as_ptr() as *const gl::types::GLfloat,
+            );
+        }
+        Ok(())
+    }
+
+    /// Colour of each species (at most [MAX_SPECIES])
+    pub fn set_species_colors(&mut self, colors: &[[f32; 3]]) {
+        self.species_colors = colors.iter().take(MAX_SPECIES).copied().collect();
+    }
+
+    /// Smoothed CPU time spent submitting the points upload to the driver (in ms): the copy
+    /// to the GPU is asynchronous and not included
+    pub fn submit_time(&self) -> f32 {
+        self.submit_time.get()
+    }
+
+    pub fn render(&self, points: &[Point]) -> Result<()> {
+        let gl = self.program.gl();
+
+        self.program.activate();
+        self.set_uniforms()?;
+
+        // time spent in the driver to enqueue the upload (GL calls return before the GPU copy)
+        let start = Instant::now();
+        self.program.stream_vertices(points, self.upload_mode);
+        let elapsed = start.elapsed().as_secs_f32() * 1000.0;
+        self.submit_time.set(
+            self.submit_time.get() + SUBMIT_TIME_SMOOTHING * (elapsed - self.submit_time.get()),
+        );
+
+        // the vertex layout may refer to another buffer (cf. render_buffer)
+        self.set_vertex_layout()?;
+        unsafe {
+            gl.DrawArrays(gl::POINTS, 0, points.len() as i32);
+        }
+        Ok(())
+    }
+
+    /// Draws `count` points already stored on the GPU
+    pub fn render_buffer(&self, buffer: &Buffer, count: usize) -> Result<()> {
+        let gl = self.program.gl();
+
+        self.program.activate();
+        self.set_uniforms()?;
+        buffer.bind(gl::ARRAY_BUFFER);
+        self.set_vertex_layout()?;
+        unsafe {
+            gl.DrawArrays(gl::POINTS, 0, count as i32);
+        }
+        Ok(())
+    }
+}
+
+// Shader sources
+const VS_SRC: &[u8] = b"
+    #version 330 core
+    layout (location = 0) in vec2 position;
+    layout (location = 1) in vec2 velocity;
+    layout (location = 2) in uint species;
+
+    uniform mat3 transform;
+    uniform float pointSize;
+    uniform float maxSpeedSquared;
+    uniform vec3 speciesColors[8]; // MAX_SPECIES
+
+    out vec4 pointColor;
+
+    float two_pi = 6.2831853072;
+
+    vec3 rgb_from_hsb(in vec3 c){
+        vec3 rgb = clamp(abs(mod(c.x*6.0+vec3(0.0,4.0,2.0),
+                                 6.0)-3.0)-1.0,
+                         0.0,
+                         1.0 );
+        rgb = rgb*rgb*(3.0-2.0*rgb);
+        return c.z * mix(vec3(1.0), rgb, c.y);
+    }
+
+    float mag_2 = pow(velocity.x, 2) + pow(velocity.y, 2);
+
+    float a = atan(velocity.y, velocity.x);
+    void main() {
+        // pointColor = vec4(rgb_from_hsb(vec3(a/two_pi, 1 - (mag_2 / maxSpeedSquared), 1.0)), 1.0);
+        // pointColor = vec4(mag_2 / maxSpeedSquared, mag_2 / maxSpeedSquared, mag_2 / maxSpeedSquared, 1.0);
+        // pointColor = vec4(1.0 - mag_2 / maxSpeedSquared, 1.0 - mag_2 / maxSpeedSquared, 1.0 - mag_2 / maxSpeedSquared, 1.0);
+        float slowness = clamp(1.0 - mag_2 / maxSpeedSquared, 0.0, 1.0);
+        pointColor = vec4(speciesColors[min(species, 7u)] * slowness, 1.0);
+        gl_PointSize = pointSize;
+        gl_Position = vec4(transform * vec3(position, 1.0), 1.0);
+    }\0";
+
+const FS_SRC: &[u8] = b"
+    #version 330 core
+    out vec4 frag_colour;
+
+    in vec4 pointColor;
+
+    void main() {
+        frag_colour = pointColor;
+    }\0";
@@ -0,0 +1,221 @@
+use std::rc::Rc;
+
+use anyhow::{anyhow, Result};
+
+use crate::point::Point;
+use crate::render::glx::gl;
+use crate::render::glx::{Buffer, ShaderProgram, Texture, VertexArray};
+
+/// Advances points on the GPU using transform feedback:
+/// points are read from one buffer and the vertex shader outputs are captured into the other one
+/// (buffers are swapped after each step).
+pub struct StepPointsProgram {
+    gl: Rc<gl::Gl>,
+    program: ShaderProgram,
+    buffers: [Buffer; 2],
+    vertex_arrays: [VertexArray; 2],
+    brightness: Texture,
+    current: usize,
+    count: usize,
+    space_size: (f32, f32),
+    brightness_uniform: gl::types::GLint,
+    space_size_uniform: gl::types::GLint,
+    time_step_uniform: gl::types::GLint,
+    seed_uniform: gl::types::GLint,
+}
+
+impl StepPointsProgram {
+    pub fn new(gl: Rc<gl::Gl>, space_size: (f32, f32)) -> Result<Self> {
+        if !gl.TransformFeedbackVaryings.is_loaded()
+            || !gl.BeginTransformFeedback.is_loaded()
+            || !gl.BindBufferBase.is_loaded()
+            || !gl.MapBufferRange.is_loaded()
+            || !gl.Uniform1ui.is_loaded()
+        {
+            return Err(anyhow!(
+                "transform feedback is not supported by this OpenGL context"
+            ));
+        }
+
+        let program = ShaderProgram::with_feedback_varyings(
+            &gl,
+            VS_SRC,
+            FS_SRC,
+            &["outPosition", "outVelocity", "outSpecies"],
+        )?;
+        let brightness_uniform = program.get_uniform_location("brightness")?;
+        let space_size_uniform = program.get_uniform_location("spaceSize")?;
+        let time_step_uniform = program.get_uniform_location("timeStep")?;
+        let seed_uniform = program.get_uniform_location("seed")?;
+
+        let step_program = StepPointsProgram {
+            program,
+            buffers: [Buffer::new(gl.clone()), Buffer::new(gl.clone())],
+            vertex_arrays: [VertexArray::new(gl.clone()), VertexArray::new(gl.clone())],
+            brightness: Texture::new(gl.clone()),
+            current: 0,
+            count: 0,
+            space_size,
+            brightness_uniform,
+            space_size_uniform,
+            time_step_uniform,
+            seed_uniform,
+            gl,
+        };
+
+        // Each vertex array reads the points from its buffer
+        for (vao, buffer) in step_program
+            .vertex_arrays
+            .iter()
+            .zip(step_program.buffers.iter())
+        {
+            vao.bind();
+            buffer.bind(gl::ARRAY_BUFFER);
+            unsafe {
+                for (location, offset) in [
+                    (0, memoffset::offset_of!(Point, position)),
+                    (1, memoffset::offset_of!(Point, velocity)),
+                ]
+                .iter()
+                {
+                    step_program.gl.EnableVertexAttribArray(*location);
+                    step_program.gl.VertexAttribPointer(
+                        *location,
+                        2,
+                        gl::FLOAT,
+                        gl::FALSE,
+                        std::mem::size_of::<Point>() as gl::types::GLsizei,
+                        *offset as *const gl::types::GLvoid,
+                    );
+                }
+                step_program.gl.EnableVertexAttribArray(2);
+                step_program.gl.VertexAttribIPointer(
+                    2,
+                    1,
+                    gl::UNSIGNED_INT,
+                    std::mem::size_of::<Point>() as gl::types::GLsizei,
+                    memoffset::offset_of!(Point, species) as *const gl::types::GLvoid,
+                );
+                step_program.gl.BindVertexArray(0);
+            }
+        }
+
+        Ok(step_program)
+    }
+
+    pub fn upload_points(&mut self, points: &[Point]) {
+        for buffer in self.buffers.iter() {
+            buffer.set_data(gl::ARRAY_BUFFER, points, gl::DYNAMIC_COPY);
+        }
+        self.current = 0;
+        self.count = points.len();
+    }
+
+    /// Brightness map (row-major) used to slow down points
+    pub fn upload_brightness(&self, width: u32, height: u32, values: &[f32]) {
+        self.brightness.upload_r32f(width, height, values);
+    }
+
+    pub fn download_points(&self) -> Result<Vec<Point>> {
+        self.buffers[self.current].read(gl::ARRAY_BUFFER, self.count)
+    }
+
+    /// Buffer holding the current points (to be drawn)
+    pub fn points_buffer(&self) -> &Buffer {
+        &self.buffers[self.current]
+    }
+
+    pub fn count(&self) -> usize {
+        self.count
+    }
+
+    pub fn step(&mut self, seed: u32, time_step: f32) {
+        let (source, target) = (self.current, 1 - self.current);
+        let gl = &self.gl;
+
+        self.program.activate();
+        self.brightness.bind(0);
+        self.vertex_arrays[source].bind();
+        self.buffers[target].bind_base(gl::TRANSFORM_FEEDBACK_BUFFER, 0);
+        unsafe {
+            gl.Uniform1i(self.brightness_uniform, 0);
+            gl.Uniform2f(
+                self.space_size_uniform,
+                self.space_size.0,
+                self.space_size.1,
+            );
+            gl.Uniform1f(self.time_step_uniform, time_step);
+            gl.Uniform1ui(self.seed_uniform, seed);
+
+            gl.Enable(gl::RASTERIZER_DISCARD);
+            gl.BeginTransformFeedback(gl::POINTS);
+            gl.DrawArrays(gl::POINTS, 0, self.count as i32);
+            gl.EndTransformFeedback();
+            gl.Disable(gl::RASTERIZER_DISCARD);
+
+            gl.BindBufferBase(gl::TRANSFORM_FEEDBACK_BUFFER, 0, 0);
+            gl.BindVertexArray(0);
+            gl.UseProgram(0);
+        }
+        self.current = target;
+    }
+}
+
+// Same rules as PointsSimulator::update_random_walk (hash functions are copies of utils ones)
+const VS_SRC: &[u8] = b"
+    #version 330 core
+    layout (location = 0) in vec2 position;
+    layout (location = 1) in vec2 velocity;
+    layout (location = 2) in uint species;
+
+    uniform sampler2D brightness;
+    uniform vec2 spaceSize;
+    uniform float timeStep;
+    uniform uint seed;
+
+    out vec2 outPosition;
+    out vec2 outVelocity;
+    flat out uint outSpecies;
+
+    uint hash(uint value) {
+        uint state = value * 747796405u + 2891336453u;
+        uint word = ((state >> ((state >> 28u) + 4u)) ^ state) * 277803737u;
+        return (word >> 22u) ^ word;
+    }
+
+    float random(inout uint state) {
+        state = hash(state);
+        return float(state >> 8u) / 16777216.0;
+    }
+
+    void main() {
+        uint state = hash(uint(gl_VertexID) ^ hash(seed));
+        float a = -1.0 + 2.0 * random(state);
+        float m = 10.0 * random(state);
+
+        // bilinear interpolation between pixel centres (same as field::Grid::bilinear)
+        ivec2 size = textureSize(brightness, 0);
+        vec2 uv = position / spaceSize * vec2(size) - 0.5;
+        vec2 base = floor(uv);
+        vec2 f = uv - base;
+        ivec2 p0 = clamp(ivec2(base), ivec2(0), size - 1);
+        ivec2 p1 = clamp(ivec2(base) + 1, ivec2(0), size - 1);
+        float b = mix(
+            mix(texelFetch(brightness, p0, 0).r, texelFetch(brightness, ivec2(p1.x, p0.y), 0).r, f.x),
+            mix(texelFetch(brightness, ivec2(p0.x, p1.y), 0).r, texelFetch(brightness, p1, 0).r, f.x),
+            f.y);
+
+        outVelocity = vec2(-sin(a) * m, cos(a) * m) * (1.2 - b);
+        vec2 p = position + outVelocity * timeStep;
+        outPosition = p - spaceSize * floor(p / spaceSize);
+        outSpecies = species;
+    }\0";
+
+// Never executed (rasterizer discard) but required to link a program
+const FS_SRC: &[u8] = b"
+    #version 330 core
+    out vec4 frag_colour;
+
+    void main() {
+        frag_colour = vec4(1.0);
+    }\0";
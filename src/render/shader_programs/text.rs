@@ -0,0 +1,228 @@
+use crate::render::glx::gl;
+use crate::render::glx::{
+    vertex_transform_2d, FontAtlas, ProgramUnit, Texture, UploadMode, WindowSizeInfo, GLYPH_SIZE,
+};
+use anyhow::Result;
+use cgmath::{Matrix, Matrix3};
+use std::rc::Rc;
+
+pub type TextColor = [f32; 4];
+
+/// Where a text is anchored (top-left corner of its first glyph)
+#[derive(Debug, Copy, Clone)]
+pub enum TextPosition {
+    /// window coordinates (pixels, origin at top-left corner)
+    Screen(f32, f32),
+    /// simulation coordinates (the text follows the simulation space, glyphs keep their pixel size)
+    World(f32, f32),
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug)]
+pub struct TextVertex {
+    anchor: [f32; 2],
+    offset: [f32; 2],
+    uv: [f32; 2],
+    color: TextColor,
+}
+
+/// Collects glyph quads to be drawn by [TextRenderProgram]
+pub struct TextBatch {
+    atlas: Rc<FontAtlas>,
+    screen_vertices: Vec<TextVertex>,
+    world_vertices: Vec<TextVertex>,
+}
+
+impl TextBatch {
+    pub fn new(atlas: Rc<FontAtlas>) -> Self {
+        TextBatch {
+            atlas,
+            screen_vertices: Vec::new(),
+            world_vertices: Vec::new(),
+        }
+    }
+
+    /// Size in pixels of a text (multi-line texts are supported)
+    pub fn text_size(text: &str, scale: f32) -> (f32, f32) {
+        let glyph_size = GLYPH_SIZE as f32 * scale;
+        let columns = text.lines().map(|l| l.chars().count()).max().unwrap_or(0);
+        let rows = text.lines().count();
+        (columns as f32 * glyph_size, rows as f32 * glyph_size)
+    }
+
+    /// Adds `text` at `position`; `scale` is the size of a glyph pixel (in screen pixels)
+    pub fn add(&mut self, text: &str, position: TextPosition, scale: f32, color: TextColor) {
+        let (anchor, vertices) = match position {
+            TextPosition::Screen(x, y) => ([x, y], &mut self.screen_vertices),
+            TextPosition::World(x, y) => ([x, y], &mut self.world_vertices),
+        };
+
+        let glyph_size = GLYPH_SIZE as f32 * scale;
+        for (row, line) in text.lines().enumerate() {
+            let y = row as f32 * glyph_size;
+            for (column, c) in line.chars().enumerate() {
+                if c == ' ' {
+                    continue;
+                }
+                let x = column as f32 * glyph_size;
+                let [u0, v0, u1, v1] = self.atlas.glyph_uv(c);
+                let corners = [
+                    ([x, y], [u0, v0]),
+                    ([x + glyph_size, y], [u1, v0]),
+                    ([x + glyph_size, y + glyph_size], [u1, v1]),
+                    ([x, y + glyph_size], [u0, v1]),
+                ];
+                for &i in &[0, 1, 2, 0, 2, 3] {
+                    let (offset, uv) = corners[i];
+                    vertices.push(TextVertex {
+                        anchor,
+                        offset,
+                        uv,
+                        color,
+                    });
+                }
+            }
+        }
+    }
+}
+
+pub struct TextRenderProgram {
+    program: ProgramUnit,
+    atlas: Rc<FontAtlas>,
+    texture: Texture,
+    world_transform: Matrix3<f32>,
+}
+
+impl TextRenderProgram {
+    pub fn new(gl: Rc<gl::Gl>, size: WindowSizeInfo) -> Result<Self> {
+        Ok(TextRenderProgram {
+            program: ProgramUnit::new(&gl, VS_SRC, FS_SRC)?,
+            atlas: Rc::new(FontAtlas::new()),
+            texture: Texture::new(gl),
+            world_transform: vertex_transform_2d(size.width as f32, size.height as f32),
+        })
+    }
+
+    /// Creates an empty batch using the font of this program
+    pub fn new_batch(&self) -> TextBatch {
+        TextBatch::new(self.atlas.clone())
+    }
+
+    pub fn initialize(&mut self) -> Result<()> {
+        let gl = self.program.gl();
+
+        self.program.prepare();
+        self.program.add_uniform("anchorTransform")?;
+        self.program.add_uniform("offsetTransform")?;
+        self.program.add_uniform("atlas")?;
+        let stride = std::mem::size_of::<TextVertex>() as gl::types::GLsizei;
+        let attributes = [
+            ("vAnchor", 2, memoffset::offset_of!(TextVertex, anchor)),
+            ("vOffset", 2, memoffset::offset_of!(TextVertex, offset)),
+            ("vUV", 2, memoffset::offset_of!(TextVertex, uv)),
+            ("vCol", 4, memoffset::offset_of!(TextVertex, color)),
+        ];
+        for (name, components, offset) in attributes.iter() {
+            let location = self.program.add_attribute(name)?;
+            unsafe {
+                gl.VertexAttribPointer(
+                    location as gl::types::GLuint,
+                    *components,
+                    gl::FLOAT,
+                    gl::FALSE,
+                    stride,
+                    *offset as *const gl::types::GLvoid,
+                );
+            }
+        }
+
+        self.texture
+            .upload_r8(self.atlas.width, self.atlas.height, &self.atlas.pixels);
+        Ok(())
+    }
+
+    pub fn render(&self, batch: &TextBatch, size: (u32, u32)) -> Result<()> {
+        if batch.screen_vertices.is_empty() && batch.world_vertices.is_empty() {
+            return Ok(());
+        }
+        let gl = self.program.gl();
+
+        let (width, height) = size;
+        let screen_transform = vertex_transform_2d(width as f32, height as f32);
+
+        self.program.activate();
+        self.texture.bind(0);
+        unsafe {
+            gl.Enable(gl::BLEND);
+            gl.BlendFunc(gl::SRC_ALPHA, gl::ONE_MINUS_SRC_ALPHA);
+            gl.Uniform1i(self.program.get_uniform("atlas")?, 0);
+            // offsets are pixel vectors (no translation part, cf. vertex shader)
+            gl.UniformMatrix3fv(
+                self.program.get_uniform("offsetTransform")?,
+                1,
+                gl::FALSE,
+                screen_transform.as_ptr(),
+            );
+        }
+        for (vertices, anchor_transform) in [
+            (&batch.screen_vertices, &screen_transform),
+            (&batch.world_vertices, &self.world_transform),
+        ]
+        .iter()
+        {
+            if vertices.is_empty() {
+                continue;
+            }
+            unsafe {
+                gl.UniformMatrix3fv(
+                    self.program.get_uniform("anchorTransform")?,
+                    1,
+                    gl::FALSE,
+                    anchor_transform.as_ptr(),
+                );
+                self.program.stream_vertices(vertices, UploadMode::Orphan);
+                gl.DrawArrays(gl::TRIANGLES, 0, vertices.len() as i32);
+            }
+        }
+        unsafe {
+            gl.Disable(gl::BLEND);
+        }
+        Ok(())
+    }
+}
+
+// Text Vertex Shader: anchor in screen or world coordinates + glyph offset in pixels
+const VS_SRC: &[u8] = b"
+#version 330 core
+uniform mat3 anchorTransform;
+uniform mat3 offsetTransform;
+in vec2 vAnchor;
+in vec2 vOffset;
+in vec2 vUV;
+in vec4 vCol;
+out vec2 uv;
+out vec4 color;
+void main()
+{
+    vec3 position = anchorTransform * vec3(vAnchor, 1.0) + offsetTransform * vec3(vOffset, 0.0);
+    gl_Position = vec4(position.xy, 0.0, 1.0);
+    uv = vUV;
+    color = vCol;
+}
+\0";
+
+const FS_SRC: &[u8] = b"
+#version 330 core
+
+uniform sampler2D atlas;
+in vec2 uv;
+in vec4 color;
+out vec4 fragColor;
+void main() {
+    float coverage = texture(atlas, uv).r;
+    if (coverage == 0.0) {
+        discard;
+    }
+    fragColor = vec4(color.rgb, color.a * coverage);
+}
+\0";
@@ -0,0 +1,151 @@
+use crate::lifecycle::LifecycleConfig;
+use crate::obstacles::ObstaclesConfig;
+use crate::predators::{Predator, PredatorParams};
+use crate::species::SpeciesSet;
+use anyhow::{ensure, Context, Result};
+use serde::{Deserialize, Serialize};
+use std::fs::File;
+use std::io::{BufReader, BufWriter};
+use std::path::Path;
+
+/// Everything needed to restart an experiment (points themselves are randomly reinitialized),
+/// stored as JSON
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Scenario {
+    /// simulation space the coordinates refer to (rescaled when loaded in another space)
+    pub space_size: (f32, f32),
+    pub species: SpeciesSet,
+    #[serde(default)]
+    pub predators: Vec<Predator>,
+    #[serde(default)]
+    pub predator_params: PredatorParams,
+    #[serde(default)]
+    pub obstacles: ObstaclesConfig,
+    #[serde(default)]
+    pub lifecycle: LifecycleConfig,
+}
+
+impl Scenario {
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let path = path.as_ref();
+        let file =
+            File::open(path).with_context(|| format!("Failed to open scenario {:?}", path))?;
+        let scenario: Scenario = serde_json::from_reader(BufReader::new(file))
+            .with_context(|| format!("Failed to read scenario {:?}", path))?;
+        scenario
+            .check()
+            .with_context(|| format!("Invalid scenario {:?}", path))?;
+        Ok(scenario)
+    }
+
+    /// Checks what deserialization does not: the space size (coordinates are rescaled by it),
+    /// the predator parameters, the obstacle shapes and the emitters
+    pub fn check(&self) -> Result<()> {
+        let (width, height) = self.space_size;
+        ensure!(
+            width.is_finite() && width > 0.0 && height.is_finite() && height > 0.0,
+            "Space size must be positive: {:?}",
+            self.space_size
+        );
+        self.predator_params.check()?;
+        self.obstacles.check()?;
+        self.lifecycle.check(self.species.count())?;
+        Ok(())
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        let path = path.as_ref();
+        let file =
+            File::create(path).with_context(|| format!("Failed to create scenario {:?}", path))?;
+        serde_json::to_writer_pretty(BufWriter::new(file), self)
+            .with_context(|| format!("Failed to write scenario {:?}", path))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lifecycle::Emitter;
+    use crate::obstacles::Shape;
+    use crate::point::Position;
+
+    #[test]
+    fn demo_scenario_should_survive_a_round_trip() {
+        let scenario = Scenario::load("./assets/scenarios/obstacles.json").unwrap();
+        assert_eq!(scenario.obstacles.shapes.len(), 3);
+
+        let json = serde_json::to_string(&scenario).unwrap();
+        let reloaded: Scenario = serde_json::from_str(&json).unwrap();
+        assert_eq!(reloaded.species.name, scenario.species.name);
+        assert_eq!(reloaded.obstacles.shapes.len(), 3);
+        assert!(reloaded.lifecycle.is_static());
+
+        let fountain = Scenario::load("./assets/scenarios/fountain.json").unwrap();
+        assert_eq!(fountain.lifecycle.emitters.len(), 2);
+        assert!(fountain.lifecycle.death.leaving_domain);
+    }
+
+    #[test]
+    fn invalid_species_should_be_rejected() {
+        let mut json = serde_json::to_value(Scenario {
+            space_size: (100.0, 100.0),
+            species: SpeciesSet::single(),
+            predators: Vec::new(),
+            predator_params: PredatorParams::default(),
+            obstacles: ObstaclesConfig::default(),
+            lifecycle: LifecycleConfig::default(),
+        })
+        .unwrap();
+        json["species"]["interactions"] = serde_json::json!([0.0, 1.0]);
+        let result: serde_json::Result<Scenario> = serde_json::from_value(json);
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn empty_space_should_be_rejected() {
+        let mut scenario = Scenario::load("./assets/scenarios/obstacles.json").unwrap();
+        assert!(scenario.check().is_ok());
+        scenario.space_size = (1920.0, 0.0);
+        assert!(scenario.check().is_err());
+    }
+
+    #[test]
+    fn invalid_emitters_and_shapes_should_be_rejected() {
+        let fountain = Scenario::load("./assets/scenarios/fountain.json").unwrap();
+        let species_count = fountain.species.count() as u32;
+        let invalid_emitters: [fn(&mut Emitter); 4] = [
+            |emitter| emitter.rate = -1.0,
+            |emitter| emitter.rate = f32::NAN,
+            |emitter| emitter.lifetime = (120.0, 40.0),
+            |emitter| emitter.lifetime = (-1.0, 40.0),
+        ];
+        for invalidate in invalid_emitters.iter() {
+            let mut scenario = fountain.clone();
+            invalidate(&mut scenario.lifecycle.emitters[0]);
+            assert!(scenario.check().is_err());
+        }
+        let mut scenario = fountain.clone();
+        scenario.lifecycle.emitters[0].species = Some(species_count - 1);
+        assert!(scenario.check().is_ok());
+        scenario.lifecycle.emitters[0].species = Some(species_count);
+        assert!(scenario.check().is_err());
+
+        let invalid_shapes = [
+            Shape::Circle {
+                center: Position::new(10.0, 10.0),
+                radius: -5.0,
+            },
+            Shape::Polygon {
+                vertices: vec![Position::new(0.0, 0.0), Position::new(10.0, 0.0)],
+            },
+        ];
+        for shape in invalid_shapes.iter() {
+            let mut scenario = fountain.clone();
+            scenario.obstacles.shapes.push(shape.clone());
+            assert!(scenario.check().is_err());
+            let mut scenario = fountain.clone();
+            scenario.lifecycle.death.absorbing.push(shape.clone());
+            assert!(scenario.check().is_err());
+        }
+    }
+}
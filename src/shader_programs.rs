@@ -1,3 +0,0 @@
-pub mod lines;
-pub mod points;
-pub mod triangle;
@@ -0,0 +1,169 @@
+use crate::parallel::*;
+use crate::point::{Point, Position, Velocity};
+use crate::utils::{hash_u32, hashed_random};
+
+// Points processed by a parallel task: inner loops run over contiguous slices of this size
+const CHUNK_SIZE: usize = 4096;
+
+/// Points stored as a structure of arrays (one array per component).
+/// Update kernels stream through the few components they need in simple loops
+/// the compiler can vectorize; [PointsSoA::write_points] produces the interleaved
+/// layout expected by the points shader.
+#[derive(Debug, Clone, Default)]
+pub struct PointsSoA {
+    pub x: Vec<f32>,
+    pub y: Vec<f32>,
+    pub vx: Vec<f32>,
+    pub vy: Vec<f32>,
+    pub species: Vec<u32>,
+}
+
+/// Wraps a coordinate moved by less than one `size` back into [0, size]
+#[inline(always)]
+fn wrap(value: f32, size: f32) -> f32 {
+    if value < 0.0 {
+        value + size
+    } else if value > size {
+        value - size
+    } else {
+        value
+    }
+}
+
+/// Sine and cosine of an angle in [-1, 1] rad (Taylor series, error < 1e-7),
+/// unlike `f32::sin_cos` it can be vectorized
+#[inline(always)]
+fn small_angle_sin_cos(angle: f32) -> (f32, f32) {
+    let a2 = angle * angle;
+    let sin = angle * (1.0 - a2 / 6.0 * (1.0 - a2 / 20.0 * (1.0 - a2 / 42.0 * (1.0 - a2 / 72.0))));
+    let cos = 1.0 - a2 / 2.0 * (1.0 - a2 / 12.0 * (1.0 - a2 / 30.0 * (1.0 - a2 / 56.0)));
+    (sin, cos)
+}
+
+impl PointsSoA {
+    pub fn from_points(points: &[Point]) -> Self {
+        PointsSoA {
+            x: points.iter().map(|p| p.position.x).collect(),
+            y: points.iter().map(|p| p.position.y).collect(),
+            vx: points.iter().map(|p| p.velocity.x).collect(),
+            vy: points.iter().map(|p| p.velocity.y).collect(),
+            species: points.iter().map(|p| p.species).collect(),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.x.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.x.is_empty()
+    }
+
+    /// Interleaved copy of the points (`points` is resized to the number of points)
+    pub fn write_points(&self, points: &mut Vec<Point>) {
+        points.resize(self.len(), Point::default());
+        points
+            .par_chunks_mut(CHUNK_SIZE)
+            .enumerate()
+            .for_each(|(chunk, points)| {
+                let first = chunk * CHUNK_SIZE;
+                let range = first..first + points.len();
+                let (x, y) = (&self.x[range.clone()], &self.y[range.clone()]);
+                let (vx, vy) = (&self.vx[range.clone()], &self.vy[range.clone()]);
+                let species = &self.species[range];
+                for (i, p) in points.iter_mut().enumerate() {
+                    *p = Point {
+                        position: Position::new(x[i], y[i]),
+                        velocity: Velocity::new(vx[i], vy[i]),
+                        species: species[i],
+                    };
+                }
+            });
+    }
+
+    /// Random walk velocities (same random values and formulas as the GPU step shader, up to
+    /// the sine/cosine approximation):
+    /// heading deviation in [-1, 1] rad and speed in [0, 10] scaled by `speed_factor(x, y)`
+    pub fn random_walk<F>(&mut self, seed: u32, speed_factor: F)
+    where
+        F: Fn(f32, f32) -> f32 + Sync,
+    {
+        let seed = hash_u32(seed);
+        let (x, y) = (&self.x, &self.y);
+        self.vx
+            .par_chunks_mut(CHUNK_SIZE)
+            .zip(self.vy.par_chunks_mut(CHUNK_SIZE))
+            .enumerate()
+            .for_each(|(chunk, (vx, vy))| {
+                let first = chunk * CHUNK_SIZE;
+                let (x, y) = (&x[first..first + vx.len()], &y[first..first + vx.len()]);
+                // hashing and polynomials only: vectorizable
+                for (i, (vx, vy)) in vx.iter_mut().zip(vy.iter_mut()).enumerate() {
+                    let mut state = hash_u32((first + i) as u32 ^ seed);
+                    let angle = -1.0 + 2.0 * hashed_random(&mut state);
+                    let magnitude = 10.0 * hashed_random(&mut state);
+                    let (sin, cos) = small_angle_sin_cos(angle);
+                    *vx = -magnitude * sin;
+                    *vy = magnitude * cos;
+                }
+                // field sampling (gather)
+                for i in 0..vx.len() {
+                    let factor = speed_factor(x[i], y[i]);
+                    vx[i] *= factor;
+                    vy[i] *= factor;
+                }
+            });
+    }
+
+    /// Moves the points along their velocity in a periodic space
+    pub fn integrate(&mut self, time_step: f32, space_size: (f32, f32)) {
+        let integrate = |positions: &mut Vec<f32>, velocities: &Vec<f32>, size: f32| {
+            positions
+                .par_chunks_mut(CHUNK_SIZE)
+                .zip(velocities.par_chunks(CHUNK_SIZE))
+                .for_each(|(positions, velocities)| {
+                    for (p, v) in positions.iter_mut().zip(velocities.iter()) {
+                        *p = wrap(*p + v * time_step, size);
+                    }
+                });
+        };
+        integrate(&mut self.x, &self.vx, space_size.0);
+        integrate(&mut self.y, &self.vy, space_size.1);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn small_angle_sin_cos_should_be_accurate() {
+        for i in -100..=100 {
+            let angle = i as f32 / 100.0;
+            let (sin, cos) = small_angle_sin_cos(angle);
+            assert!((sin - angle.sin()).abs() < 1e-6);
+            assert!((cos - angle.cos()).abs() < 1e-6);
+        }
+    }
+
+    #[test]
+    fn interleaved_adapter_should_round_trip() {
+        let points: Vec<Point> = (0..CHUNK_SIZE + 10)
+            .map(|i| Point {
+                position: Position::new(i as f32, 2.0 * i as f32),
+                velocity: Velocity::new(-(i as f32), 0.5),
+                species: (i % 3) as u32,
+            })
+            .collect();
+        let soa = PointsSoA::from_points(&points);
+        let mut interleaved = Vec::new();
+        soa.write_points(&mut interleaved);
+
+        assert_eq!(interleaved.len(), points.len());
+        for (a, b) in points.iter().zip(interleaved.iter()) {
+            assert_eq!(a.position, b.position);
+            assert_eq!(a.velocity, b.velocity);
+            assert_eq!(a.species, b.species);
+        }
+    }
+}
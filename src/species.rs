@@ -0,0 +1,426 @@
+use crate::point::Velocity;
+use cgmath::{InnerSpace, Vector2, Zero};
+use serde::{Deserialize, Serialize};
+use std::convert::TryFrom;
+use thiserror::Error;
+
+/// Maximum number of species (colours are sent to the points shader as a fixed size array)
+pub const MAX_SPECIES: usize = 8;
+
+/// Highest speed reached by the brightness random walk
+pub const RANDOM_WALK_MAX_SPEED: f32 = 12.0;
+
+#[derive(Error, Debug, PartialEq)]
+pub enum SpeciesError {
+    #[error("At least one species is required")]
+    Empty,
+    #[error("Too many species: {0} (at most {} supported)", MAX_SPECIES)]
+    TooMany(usize),
+    #[error("Interaction matrix has {0} coefficients ({1} expected)")]
+    InteractionMatrixSize(usize, usize),
+    #[error("Invalid parameters for species '{0}': {1}")]
+    InvalidParameters(String, String),
+}
+
+/// Weights of the rules combined into the new velocity of a point
+#[derive(Debug, Copy, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RuleWeights {
+    /// brightness driven random walk (the historical behaviour, cf. [crate::behavior::RandomWalk])
+    pub random_walk: f32,
+    /// part of the previous velocity which is kept
+    pub inertia: f32,
+    /// steering toward the mean velocity of neighbours of the same species
+    pub alignment: f32,
+    /// steering toward the centre of neighbours of the same species
+    pub cohesion: f32,
+    /// steering away from too close neighbours (of any species)
+    pub separation: f32,
+    /// attraction/avoidance toward neighbours given by the interaction matrix
+    pub interaction: f32,
+}
+
+impl RuleWeights {
+    pub const RANDOM_WALK: RuleWeights = RuleWeights {
+        random_walk: 1.0,
+        inertia: 0.0,
+        alignment: 0.0,
+        cohesion: 0.0,
+        separation: 0.0,
+        interaction: 0.0,
+    };
+
+    /// No rule at all: the velocity is given by the behaviours (cf. [Species::behaviors_only])
+    pub const NONE: RuleWeights = RuleWeights {
+        random_walk: 0.0,
+        inertia: 0.0,
+        alignment: 0.0,
+        cohesion: 0.0,
+        separation: 0.0,
+        interaction: 0.0,
+    };
+
+    pub fn uses_neighbours(&self) -> bool {
+        self.alignment != 0.0
+            || self.cohesion != 0.0
+            || self.separation != 0.0
+            || self.interaction != 0.0
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Species {
+    pub name: String,
+    pub color: [f32; 3],
+    /// relative share of the points
+    pub proportion: f32,
+    pub min_speed: f32,
+    pub max_speed: f32,
+    /// neighbours farther than this radius are ignored
+    pub perception_radius: f32,
+    /// neighbours closer than this radius are avoided
+    pub separation_radius: f32,
+    pub weights: RuleWeights,
+}
+
+/// Contributions of the surroundings (neighbours, predators, obstacles, fields) to the velocity of a point
+/// (cf. [RuleWeights])
+#[derive(Debug, Copy, Clone)]
+pub struct NeighbourForces {
+    pub alignment: Vector2<f32>,
+    pub cohesion: Vector2<f32>,
+    pub separation: Vector2<f32>,
+    pub interaction: Vector2<f32>,
+    /// flee from predators (already scaled, not weighted)
+    pub flee: Vector2<f32>,
+    /// obstacle avoidance (already scaled, not weighted)
+    pub avoidance: Vector2<f32>,
+    /// force fields (already scaled, not weighted)
+    pub field: Vector2<f32>,
+}
+
+impl Default for NeighbourForces {
+    fn default() -> Self {
+        NeighbourForces {
+            alignment: Vector2::zero(),
+            cohesion: Vector2::zero(),
+            separation: Vector2::zero(),
+            interaction: Vector2::zero(),
+            flee: Vector2::zero(),
+            avoidance: Vector2::zero(),
+            field: Vector2::zero(),
+        }
+    }
+}
+
+impl Species {
+    /// Today's behaviour: brightness random walk, no interaction
+    pub fn random_walker(name: &str, color: [f32; 3]) -> Self {
+        Species {
+            name: name.into(),
+            color,
+            proportion: 1.0,
+            min_speed: 0.0,
+            max_speed: RANDOM_WALK_MAX_SPEED,
+            perception_radius: 0.0,
+            separation_radius: 0.0,
+            weights: RuleWeights::RANDOM_WALK,
+        }
+    }
+
+    /// Velocity given by the behaviours only (no rule, no speed limit), as used by the
+    /// collective motion models (cf. [crate::models])
+    pub fn behaviors_only(name: &str, color: [f32; 3]) -> Self {
+        Species {
+            name: name.into(),
+            color,
+            proportion: 1.0,
+            min_speed: 0.0,
+            max_speed: f32::MAX,
+            perception_radius: 0.0,
+            separation_radius: 0.0,
+            weights: RuleWeights::NONE,
+        }
+    }
+
+    /// Combines the rules with the `behaviors` steering (cf. [crate::behavior::BehaviorPipeline])
+    /// into the new velocity (bounded by the speed limits)
+    pub fn steer(
+        &self,
+        velocity: Velocity,
+        behaviors: Velocity,
+        forces: &NeighbourForces,
+    ) -> Velocity {
+        let w = &self.weights;
+        let velocity = velocity * w.inertia
+            + behaviors
+            + forces.alignment * w.alignment
+            + forces.cohesion * w.cohesion
+            + forces.separation * w.separation
+            + forces.interaction * w.interaction
+            + forces.flee
+            + forces.avoidance
+            + forces.field;
+
+        let speed = velocity.magnitude();
+        if speed > self.max_speed {
+            velocity * (self.max_speed / speed)
+        } else if speed < self.min_speed && speed > 0.0 {
+            velocity * (self.min_speed / speed)
+        } else {
+            velocity
+        }
+    }
+
+    fn check(&self) -> Result<(), SpeciesError> {
+        let invalid = |reason: &str| {
+            Err(SpeciesError::InvalidParameters(
+                self.name.clone(),
+                reason.into(),
+            ))
+        };
+        if self.proportion.is_nan() || self.proportion <= 0.0 {
+            invalid("proportion must be positive")
+        } else if self.min_speed < 0.0 || self.min_speed > self.max_speed {
+            invalid("speed limits must satisfy 0 <= min_speed <= max_speed")
+        } else if self.weights.uses_neighbours() && self.perception_radius <= 0.0 {
+            invalid("neighbour rules require a positive perception radius")
+        } else if self.separation_radius > self.perception_radius {
+            invalid("separation radius must not exceed perception radius")
+        } else {
+            Ok(())
+        }
+    }
+}
+
+/// Species of a simulation with their N×N interaction matrix:
+/// `interaction(i, j)` > 0 attracts species `i` toward species `j`, < 0 makes it flee.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(try_from = "SpeciesSetDescription")]
+pub struct SpeciesSet {
+    pub name: String,
+    species: Vec<Species>,
+    interactions: Vec<f32>, // row-major
+}
+
+// Deserialized fields, checked by SpeciesSet::new
+#[derive(Deserialize)]
+struct SpeciesSetDescription {
+    name: String,
+    species: Vec<Species>,
+    interactions: Vec<f32>,
+}
+
+impl TryFrom<SpeciesSetDescription> for SpeciesSet {
+    type Error = SpeciesError;
+
+    fn try_from(description: SpeciesSetDescription) -> Result<Self, Self::Error> {
+        SpeciesSet::new(
+            &description.name,
+            description.species,
+            description.interactions,
+        )
+    }
+}
+
+impl SpeciesSet {
+    pub fn new(
+        name: &str,
+        species: Vec<Species>,
+        interactions: Vec<f32>,
+    ) -> Result<Self, SpeciesError> {
+        if species.is_empty() {
+            return Err(SpeciesError::Empty);
+        }
+        if species.len() > MAX_SPECIES {
+            return Err(SpeciesError::TooMany(species.len()));
+        }
+        let expected = species.len() * species.len();
+        if interactions.len() != expected {
+            return Err(SpeciesError::InteractionMatrixSize(
+                interactions.len(),
+                expected,
+            ));
+        }
+        for s in species.iter() {
+            s.check()?;
+        }
+        Ok(SpeciesSet {
+            name: name.into(),
+            species,
+            interactions,
+        })
+    }
+
+    /// A single random walking species (today's behaviour)
+    pub fn single() -> Self {
+        Self::new(
+            "single species",
+            vec![Species::random_walker("walkers", [1.0, 1.0, 1.0])],
+            vec![0.0],
+        )
+        .unwrap()
+    }
+
+    /// Three flocks avoiding each other
+    pub fn mixed_flocks() -> Self {
+        let flock = |name: &str, color| Species {
+            name: name.into(),
+            color,
+            proportion: 1.0,
+            min_speed: 2.0,
+            max_speed: 6.0,
+            perception_radius: 10.0,
+            separation_radius: 3.0,
+            weights: RuleWeights {
+                random_walk: 0.1,
+                inertia: 0.9,
+                alignment: 0.05,
+                cohesion: 0.01,
+                separation: 1.0,
+                interaction: 0.5,
+            },
+        };
+        #[rustfmt::skip]
+        let interactions = vec![
+             0.0, -1.0, -1.0,
+            -1.0,  0.0, -1.0,
+            -1.0, -1.0,  0.0,
+        ];
+        Self::new(
+            "mixed flocks",
+            vec![
+                flock("red", [1.0, 0.3, 0.3]),
+                flock("green", [0.3, 1.0, 0.3]),
+                flock("blue", [0.4, 0.5, 1.0]),
+            ],
+            interactions,
+        )
+        .unwrap()
+    }
+
+    /// A flock of prey fleeing a few faster predators
+    pub fn predator_prey() -> Self {
+        let prey = Species {
+            name: "prey".into(),
+            color: [0.9, 0.9, 1.0],
+            proportion: 0.99,
+            min_speed: 2.0,
+            max_speed: 6.0,
+            perception_radius: 10.0,
+            separation_radius: 3.0,
+            weights: RuleWeights {
+                random_walk: 0.1,
+                inertia: 0.9,
+                alignment: 0.05,
+                cohesion: 0.01,
+                separation: 1.0,
+                interaction: 1.0,
+            },
+        };
+        let predator = Species {
+            name: "predators".into(),
+            color: [1.0, 0.2, 0.1],
+            proportion: 0.01,
+            min_speed: 3.0,
+            max_speed: 8.0,
+            perception_radius: 25.0,
+            separation_radius: 5.0,
+            weights: RuleWeights {
+                random_walk: 0.1,
+                inertia: 0.9,
+                alignment: 0.0,
+                cohesion: 0.0,
+                separation: 0.5,
+                interaction: 1.0,
+            },
+        };
+        #[rustfmt::skip]
+        let interactions = vec![
+            0.0, -3.0, // prey flee predators
+            2.0, -0.5, // predators chase prey and spread out
+        ];
+        Self::new("predator/prey", vec![prey, predator], interactions).unwrap()
+    }
+
+    pub fn presets() -> Vec<SpeciesSet> {
+        vec![Self::single(), Self::mixed_flocks(), Self::predator_prey()]
+    }
+
+    /// Number of species (valid ids are below it)
+    pub fn count(&self) -> usize {
+        self.species.len()
+    }
+
+    pub fn get(&self, id: u32) -> &Species {
+        &self.species[id as usize]
+    }
+
+    pub fn interaction(&self, from: u32, to: u32) -> f32 {
+        self.interactions[from as usize * self.species.len() + to as usize]
+    }
+
+    pub fn colors(&self) -> Vec<[f32; 3]> {
+        self.species.iter().map(|s| s.color).collect()
+    }
+
+    pub fn uses_neighbours(&self) -> bool {
+        self.species.iter().any(|s| s.weights.uses_neighbours())
+    }
+
+    /// Smallest perception radius of species with neighbour rules (grid cell size)
+    pub fn min_perception_radius(&self) -> f32 {
+        self.species
+            .iter()
+            .filter(|s| s.weights.uses_neighbours())
+            .map(|s| s.perception_radius)
+            .fold(f32::INFINITY, f32::min)
+    }
+
+    /// True when all species only random walk (the only rules supported by the GPU backend)
+    pub fn random_walk_only(&self) -> bool {
+        self.species.iter().all(|s| {
+            s.weights == RuleWeights::RANDOM_WALK
+                && s.min_speed == 0.0
+                && s.max_speed >= RANDOM_WALK_MAX_SPEED
+        })
+    }
+
+    /// Species of the point `index` among `count` points (following species proportions)
+    pub fn species_for(&self, index: usize, count: usize) -> u32 {
+        let total: f32 = self.species.iter().map(|s| s.proportion).sum();
+        let target = (index as f32 + 0.5) / count as f32 * total;
+        let mut cumulated = 0.0;
+        for (id, s) in self.species.iter().enumerate() {
+            cumulated += s.proportion;
+            if target < cumulated {
+                return id as u32;
+            }
+        }
+        (self.species.len() - 1) as u32
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn interaction_matrix_should_match_species_count() {
+        let walker = Species::random_walker("walkers", [1.0; 3]);
+        let result = SpeciesSet::new("test", vec![walker.clone(), walker], vec![0.0; 3]);
+        assert_eq!(
+            result.unwrap_err(),
+            SpeciesError::InteractionMatrixSize(3, 4)
+        );
+    }
+
+    #[test]
+    fn points_should_be_split_following_proportions() {
+        let set = SpeciesSet::predator_prey();
+        let count = 1000;
+        let predators = (0..count)
+            .filter(|&i| set.species_for(i, count) == 1)
+            .count();
+        assert_eq!(predators, 10);
+    }
+}
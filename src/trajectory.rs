@@ -0,0 +1,398 @@
+//! Positions and velocities of all or a sample of the points dumped every few steps,
+//! to analyse trajectories (e.g. with numpy) without re-running the simulation.
+//!
+//! Points are stored as little endian records `x, y, vx, vy: f32, species: u32` (the [Point] layout)
+//! in one of two formats chosen by the file extension:
+//! - `.bin`: `BOIDTRAJ`, format version (`u32`), metadata length (`u32`), metadata (JSON),
+//!   then for each frame: step (`u64`), time (`f32`), point count (`u32`) and the points;
+//! - `.npy`: a `(frames, points)` structured array, with `<name>.steps.npy` (step of each frame)
+//!   and `<name>.json` (metadata) next to it.
+//!
+//! The stored points are picked when the writer is created: writing a frame fails once the
+//! point count differs from the initial one (`+`/`-` keys, emitters, deaths).
+
+use crate::point::Point;
+use crate::points_simulator::{PointsSimulator, TIME_STEP};
+use crate::scenario::Scenario;
+use anyhow::{anyhow, ensure, Context, Result};
+use serde::Serialize;
+use std::fs::File;
+use std::io::{BufWriter, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+
+pub const FORMAT_VERSION: u32 = 1;
+const BINARY_MAGIC: &[u8; 8] = b"BOIDTRAJ";
+/// numpy dtype of a [Point]
+const POINT_DTYPE: &str =
+    "[('x', '<f4'), ('y', '<f4'), ('vx', '<f4'), ('vy', '<f4'), ('species', '<u4')]";
+/// Size of the NPY headers, large enough to be rewritten with the final frame count
+const NPY_HEADER_SIZE: usize = 256;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TrajectoryFormat {
+    Binary,
+    Npy,
+}
+
+impl TrajectoryFormat {
+    /// `.bin` or `.npy`
+    pub fn from_path<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let path = path.as_ref();
+        match path.extension().and_then(|e| e.to_str()) {
+            Some(e) if e.eq_ignore_ascii_case("bin") => Ok(TrajectoryFormat::Binary),
+            Some(e) if e.eq_ignore_ascii_case("npy") => Ok(TrajectoryFormat::Npy),
+            _ => Err(anyhow!(
+                "Unknown trajectory format for {:?} (expected .bin or .npy)",
+                path
+            )),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct TrajectoryConfig {
+    /// a frame every `interval` steps (steps multiple of it)
+    pub interval: u64,
+    /// number of points evenly picked among all of them (all points if None)
+    pub sample: Option<usize>,
+}
+
+impl Default for TrajectoryConfig {
+    fn default() -> Self {
+        TrajectoryConfig {
+            interval: 10,
+            sample: None,
+        }
+    }
+}
+
+/// Run description stored with the frames
+#[derive(Debug, Clone, Serialize)]
+pub struct TrajectoryMetadata {
+    pub version: u32,
+    pub time_step: f32,
+    pub interval: u64,
+    /// point count when the dump started
+    pub point_count: usize,
+    /// index of the i-th stored point among all of them: `i * point_count / sample_count`
+    pub sample_count: usize,
+    /// name and weight of each behaviour
+    pub behaviors: Vec<(String, f32)>,
+    /// space size, species, predators, obstacles and population dynamics
+    pub scenario: Scenario,
+}
+
+impl TrajectoryMetadata {
+    fn new(simulator: &PointsSimulator, config: &TrajectoryConfig) -> Self {
+        let point_count = simulator.points().len();
+        TrajectoryMetadata {
+            version: FORMAT_VERSION,
+            time_step: TIME_STEP,
+            interval: config.interval,
+            point_count,
+            sample_count: config.sample.map_or(point_count, |n| n.min(point_count)),
+            behaviors: simulator
+                .behaviors()
+                .iter()
+                .map(|b| (b.behavior.name().to_string(), b.weight))
+                .collect(),
+            scenario: simulator.scenario(),
+        }
+    }
+}
+
+/// Indices of `count` points evenly picked among `point_count`
+fn sample(point_count: usize, count: usize) -> Vec<usize> {
+    let count = count.min(point_count);
+    (0..count).map(|i| i * point_count / count).collect()
+}
+
+fn write_point(writer: &mut impl Write, p: &Point) -> std::io::Result<()> {
+    writer.write_all(&p.position.x.to_le_bytes())?;
+    writer.write_all(&p.position.y.to_le_bytes())?;
+    writer.write_all(&p.velocity.x.to_le_bytes())?;
+    writer.write_all(&p.velocity.y.to_le_bytes())?;
+    writer.write_all(&p.species.to_le_bytes())
+}
+
+/// NPY 1.0 header padded to [NPY_HEADER_SIZE]
+fn npy_header(descr: &str, shape: &[usize]) -> Vec<u8> {
+    let shape: Vec<String> = shape.iter().map(|n| format!("{},", n)).collect();
+    let dict = format!(
+        "{{'descr': {}, 'fortran_order': False, 'shape': ({}), }}",
+        descr,
+        shape.join(" ")
+    );
+    let mut header = b"\x93NUMPY\x01\x00".to_vec();
+    header.extend_from_slice(&((NPY_HEADER_SIZE - 10) as u16).to_le_bytes());
+    header.extend_from_slice(dict.as_bytes());
+    assert!(header.len() < NPY_HEADER_SIZE, "NPY header too long");
+    header.resize(NPY_HEADER_SIZE - 1, b' ');
+    header.push(b'\n');
+    header
+}
+
+fn sidecar(path: &Path, suffix: &str) -> PathBuf {
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+    path.with_file_name(format!("{}{}", stem, suffix))
+}
+
+fn create(path: &Path) -> Result<BufWriter<File>> {
+    let file =
+        File::create(path).with_context(|| format!("Failed to create trajectory {:?}", path))?;
+    Ok(BufWriter::new(file))
+}
+
+enum Output {
+    Binary(BufWriter<File>),
+    Npy {
+        points: BufWriter<File>,
+        steps: BufWriter<File>,
+    },
+}
+
+/// Writes a frame every [TrajectoryConfig::interval] steps; NPY headers are completed
+/// by [TrajectoryWriter::finish], also called (ignoring errors) when the writer is dropped.
+///
+/// A winit event loop never returns nor drops its closure: finish on `Event::LoopDestroyed`.
+pub struct TrajectoryWriter {
+    output: Output,
+    metadata: TrajectoryMetadata,
+    /// of the stored points (cf. [TrajectoryMetadata::sample_count])
+    indices: Vec<usize>,
+    frames: usize,
+    last_step: Option<u64>,
+}
+
+impl TrajectoryWriter {
+    /// Creates (truncates) `path` (and the NPY companion files), the format being given by its extension;
+    /// the metadata describe the current state of `simulator`
+    pub fn create<P: AsRef<Path>>(
+        path: P,
+        simulator: &PointsSimulator,
+        config: TrajectoryConfig,
+    ) -> Result<Self> {
+        let path = path.as_ref();
+        let config = TrajectoryConfig {
+            interval: config.interval.max(1),
+            ..config
+        };
+        let metadata = TrajectoryMetadata::new(simulator, &config);
+        let output = match TrajectoryFormat::from_path(path)? {
+            TrajectoryFormat::Binary => {
+                let mut writer = create(path)?;
+                let json = serde_json::to_vec(&metadata)?;
+                writer.write_all(BINARY_MAGIC)?;
+                writer.write_all(&FORMAT_VERSION.to_le_bytes())?;
+                writer.write_all(&(json.len() as u32).to_le_bytes())?;
+                writer.write_all(&json)?;
+                Output::Binary(writer)
+            }
+            TrajectoryFormat::Npy => {
+                let json = sidecar(path, ".json");
+                serde_json::to_writer_pretty(create(&json)?, &metadata)
+                    .with_context(|| format!("Failed to write trajectory metadata {:?}", json))?;
+                let mut points = create(path)?;
+                points.write_all(&npy_header(POINT_DTYPE, &[0, metadata.sample_count]))?;
+                let mut steps = create(&sidecar(path, ".steps.npy"))?;
+                steps.write_all(&npy_header("'<u8'", &[0]))?;
+                Output::Npy { points, steps }
+            }
+        };
+        Ok(TrajectoryWriter {
+            output,
+            indices: sample(metadata.point_count, metadata.sample_count),
+            metadata,
+            frames: 0,
+            last_step: None,
+        })
+    }
+
+    pub fn metadata(&self) -> &TrajectoryMetadata {
+        &self.metadata
+    }
+
+    /// Number of frames written
+    pub fn frames(&self) -> usize {
+        self.frames
+    }
+
+    /// Whether `step` is to be dumped (and has not been yet)
+    pub fn is_due(&self, step: u64) -> bool {
+        step % self.metadata.interval == 0 && self.last_step != Some(step)
+    }
+
+    /// Dumps the points of `simulator` if due; returns whether it did
+    pub fn record(&mut self, simulator: &PointsSimulator) -> Result<bool> {
+        if !self.is_due(simulator.step()) {
+            return Ok(false);
+        }
+        self.write(simulator.step(), simulator.time(), simulator.points())?;
+        Ok(true)
+    }
+
+    /// Writes a frame regardless of the interval; fails if the point count changed
+    pub fn write(&mut self, step: u64, time: f32, points: &[Point]) -> Result<()> {
+        ensure!(
+            points.len() == self.metadata.point_count,
+            "Point count changed from {} to {}: the stored points would not be the same",
+            self.metadata.point_count,
+            points.len()
+        );
+        let sampled = self.indices.iter().map(|&index| &points[index]);
+        match &mut self.output {
+            Output::Binary(writer) => {
+                writer.write_all(&step.to_le_bytes())?;
+                writer.write_all(&time.to_le_bytes())?;
+                writer.write_all(&(self.indices.len() as u32).to_le_bytes())?;
+                for p in sampled {
+                    write_point(writer, p)?;
+                }
+            }
+            Output::Npy {
+                points: writer,
+                steps,
+            } => {
+                for p in sampled {
+                    write_point(writer, p)?;
+                }
+                steps.write_all(&step.to_le_bytes())?;
+            }
+        }
+        self.frames += 1;
+        self.last_step = Some(step);
+        Ok(())
+    }
+
+    /// Flushes the frames (and updates the frame count of NPY headers)
+    pub fn finish(&mut self) -> Result<()> {
+        let frames = self.frames;
+        let count = self.metadata.sample_count;
+        match &mut self.output {
+            Output::Binary(writer) => writer.flush()?,
+            Output::Npy { points, steps } => {
+                let rewrite = |writer: &mut BufWriter<File>, header: Vec<u8>| {
+                    writer.seek(SeekFrom::Start(0))?;
+                    writer.write_all(&header)?;
+                    writer.seek(SeekFrom::End(0))?;
+                    writer.flush()
+                };
+                rewrite(points, npy_header(POINT_DTYPE, &[frames, count]))?;
+                rewrite(steps, npy_header("'<u8'", &[frames]))?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Drop for TrajectoryWriter {
+    fn drop(&mut self) {
+        // errors cannot be reported here: call finish() to handle them
+        let _ = self.finish();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::convert::TryInto;
+
+    fn dump(path: &Path, sample: Option<usize>) -> PointsSimulator {
+        let mut simulator = PointsSimulator::new((400, 300)).unwrap();
+        simulator.set_point_count(1000);
+        let config = TrajectoryConfig {
+            interval: 5,
+            sample,
+        };
+        let mut writer = TrajectoryWriter::create(path, &simulator, config).unwrap();
+        for _ in 0..10 {
+            simulator.update();
+            writer.record(&simulator).unwrap();
+        }
+        assert_eq!(writer.frames(), 2);
+        writer.finish().unwrap();
+        simulator
+    }
+
+    fn u32_at(bytes: &[u8], offset: usize) -> u32 {
+        u32::from_le_bytes(bytes[offset..offset + 4].try_into().unwrap())
+    }
+
+    fn f32_at(bytes: &[u8], offset: usize) -> f32 {
+        f32::from_le_bytes(bytes[offset..offset + 4].try_into().unwrap())
+    }
+
+    #[test]
+    fn binary_frames_should_hold_the_sampled_points() {
+        let path = std::env::temp_dir().join("life_of_boids_trajectory_test.bin");
+        let simulator = dump(&path, Some(100));
+        let bytes = std::fs::read(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(&bytes[..8], BINARY_MAGIC);
+        assert_eq!(u32_at(&bytes, 8), FORMAT_VERSION);
+        let json_len = u32_at(&bytes, 12) as usize;
+        let metadata: serde_json::Value =
+            serde_json::from_slice(&bytes[16..16 + json_len]).unwrap();
+        assert_eq!(metadata["sample_count"], 100);
+        assert_eq!(metadata["scenario"]["space_size"][0], 400.0);
+
+        let frame_size = 16 + 100 * 20;
+        let frames = &bytes[16 + json_len..];
+        assert_eq!(frames.len(), 2 * frame_size);
+        let last = &frames[frame_size..];
+        assert_eq!(u64::from_le_bytes(last[..8].try_into().unwrap()), 10);
+        assert_eq!(u32_at(last, 12), 100);
+        // 3rd stored point is the 21st one
+        let p = &simulator.points()[20];
+        assert_eq!(f32_at(last, 16 + 2 * 20), p.position.x);
+        assert_eq!(f32_at(last, 16 + 2 * 20 + 12), p.velocity.y);
+    }
+
+    #[test]
+    fn npy_header_should_be_completed_on_finish() {
+        let path = std::env::temp_dir().join("life_of_boids_trajectory_test.npy");
+        let simulator = dump(&path, None);
+        let bytes = std::fs::read(&path).unwrap();
+        let steps = std::fs::read(sidecar(&path, ".steps.npy")).unwrap();
+        let metadata = std::fs::read_to_string(sidecar(&path, ".json")).unwrap();
+        for file in &[
+            path.clone(),
+            sidecar(&path, ".steps.npy"),
+            sidecar(&path, ".json"),
+        ] {
+            std::fs::remove_file(file).unwrap();
+        }
+
+        let header = String::from_utf8_lossy(&bytes[10..NPY_HEADER_SIZE]);
+        assert!(header.contains("'shape': (2, 1000,)"), "{}", header);
+        assert!(header.ends_with('\n'));
+        assert_eq!(bytes.len(), NPY_HEADER_SIZE + 2 * 1000 * 20);
+        let last = &bytes[NPY_HEADER_SIZE + 1000 * 20..];
+        assert_eq!(
+            f32_at(last, 20 * 999 + 4),
+            simulator.points()[999].position.y
+        );
+
+        assert!(String::from_utf8_lossy(&steps[10..NPY_HEADER_SIZE]).contains("'shape': (2,)"));
+        assert_eq!(&steps[NPY_HEADER_SIZE + 8..], &10u64.to_le_bytes());
+        assert!(metadata.contains("\"point_count\": 1000"));
+    }
+
+    #[test]
+    fn point_count_change_should_stop_the_dump() {
+        let path = std::env::temp_dir().join("life_of_boids_trajectory_count_test.bin");
+        let mut simulator = PointsSimulator::new((400, 300)).unwrap();
+        simulator.set_point_count(1000);
+        let mut writer =
+            TrajectoryWriter::create(&path, &simulator, TrajectoryConfig::default()).unwrap();
+        assert!(writer.record(&simulator).unwrap());
+        simulator.set_point_count(1200);
+        simulator.update();
+        assert!(writer
+            .write(simulator.step(), simulator.time(), simulator.points())
+            .is_err());
+        drop(writer);
+        std::fs::remove_file(&path).unwrap();
+    }
+}
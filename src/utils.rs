@@ -1,3 +1,13 @@
+#[cfg(not(feature = "images"))]
+use anyhow::anyhow;
+#[cfg(feature = "images")]
+use anyhow::Context;
+use anyhow::Result;
+#[cfg(feature = "images")]
+use image::io::Reader as ImageReader;
+use image::RgbImage;
+use std::path::Path;
+
 pub fn calculate_relative_brightness(red: u8, green: u8, blue: u8) -> f32 {
     let red_f = (red as f32) / 255.0;
     let green_f = (green as f32) / 255.0;
@@ -5,3 +15,43 @@ pub fn calculate_relative_brightness(red: u8, green: u8, blue: u8) -> f32 {
 
     f32::sqrt((red_f * red_f) * 0.299 + (green_f * green_f) * 0.587 + (blue_f * blue_f) * 0.114)
 }
+
+/// PCG based integer hash (same as `hash` in GPU shaders: both must stay identical)
+pub fn hash_u32(value: u32) -> u32 {
+    let state = value.wrapping_mul(747_796_405).wrapping_add(2_891_336_453);
+    let word = ((state >> ((state >> 28) + 4)) ^ state).wrapping_mul(277_803_737);
+    (word >> 22) ^ word
+}
+
+/// Uniform random value in [0, 1) from a hash state (same as `random` in GPU shaders)
+pub fn hashed_random(state: &mut u32) -> f32 {
+    *state = hash_u32(*state);
+    (*state >> 8) as f32 / 16_777_216.0
+}
+
+/// Two independent standard normal values from a hash state (Box–Muller transform)
+pub fn hashed_normals(state: &mut u32) -> (f32, f32) {
+    let radius = (-2.0 * (1.0 - hashed_random(state)).ln()).sqrt();
+    let angle = std::f32::consts::TAU * hashed_random(state);
+    (radius * angle.cos(), radius * angle.sin())
+}
+
+/// Loads an image file as RGB
+#[cfg(feature = "images")]
+pub fn load_image<P: AsRef<Path>>(path: P) -> Result<RgbImage> {
+    let path = path.as_ref();
+    let img = ImageReader::open(path)
+        .with_context(|| format!("Failed to open image {:?}", path))?
+        .decode()
+        .with_context(|| format!("Failed to decode image {:?}", path))?;
+    Ok(img.to_rgb8())
+}
+
+/// Image decoding is disabled (no `images` feature): always fails
+#[cfg(not(feature = "images"))]
+pub fn load_image<P: AsRef<Path>>(path: P) -> Result<RgbImage> {
+    Err(anyhow!(
+        "Cannot load image {:?}: built without the `images` feature",
+        path.as_ref()
+    ))
+}